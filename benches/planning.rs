@@ -0,0 +1,208 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use ed25519_dalek::Keypair;
+use rand::{rngs::SmallRng, SeedableRng};
+use site_exec::{
+    generate, planning, site, AssetData, AssetId, ComputeArgs, Instruction, Logger, Msg,
+    NullLogger, Site, SiteConfig, SiteId,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// (number of sites, number of initial assets, number of computes)
+const SIZES: [(usize, u32, u32); 4] = [(3, 4, 4), (10, 20, 30), (30, 60, 100), (100, 200, 300)];
+/// A problem too large to execute in a benchmark, but where finding an asset's holders dominates
+const LARGE_PLAN_SIZE: (usize, u32, u32) = (300, 2000, 1000);
+
+fn null_loggers(num_sites: usize) -> Vec<Box<dyn Logger>> {
+    (0..num_sites).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect()
+}
+
+fn bench_plan(c: &mut Criterion) {
+    let mut group = c.benchmark_group("plan");
+    for &(num_sites, num_initial_assets, num_computes) in SIZES.iter().chain(Some(&LARGE_PLAN_SIZE))
+    {
+        let (site_ids, _sites) = site::new_sites(null_loggers(num_sites));
+        let mut rng = SmallRng::seed_from_u64(0);
+        let problem =
+            generate::random_problem(&mut rng, &site_ids, num_initial_assets, num_computes);
+        let id = size_id((num_sites, num_initial_assets, num_computes));
+        group.bench_with_input(id, &problem, |b, problem| {
+            b.iter(|| planning::plan(problem).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Sites with the planned instructions and initial data of a random problem of the given size
+fn prepare_random_problem(size: (usize, u32, u32), config: &SiteConfig) -> HashMap<SiteId, Site> {
+    let (num_sites, num_initial_assets, num_computes) = size;
+    let (site_ids, mut sites) =
+        site::new_sites_with_config(null_loggers(num_sites), config).unwrap();
+    let mut rng = SmallRng::seed_from_u64(0);
+    let problem = generate::random_problem(&mut rng, &site_ids, num_initial_assets, num_computes);
+    for (site_id, instructions) in planning::plan(&problem).unwrap() {
+        sites.get_mut(&site_id).unwrap().todo_instructions.extend(instructions);
+    }
+    for &(site_id, asset_id) in problem.site_has_asset.iter() {
+        let data = AssetData::deterministic(asset_id);
+        sites.get_mut(&site_id).unwrap().insert_asset(asset_id, data);
+    }
+    sites
+}
+
+/// Step the sites in turn on this thread until all their instructions are done. Messages arrive
+/// as soon as they are sent, and no site waits for messages, so no timeouts are measured.
+fn step_to_completion(sites: &mut HashMap<SiteId, Site>) {
+    while sites.values().any(|site| !site.todo_instructions.is_empty()) {
+        let mut progressed = false;
+        for site in sites.values_mut() {
+            progressed |= site.step().unwrap();
+        }
+        assert!(progressed, "the sites are stuck");
+    }
+}
+
+fn size_id((num_sites, num_initial_assets, num_computes): (usize, u32, u32)) -> BenchmarkId {
+    BenchmarkId::from_parameter(format!("{}x{}x{}", num_sites, num_initial_assets, num_computes))
+}
+
+fn bench_execute(c: &mut Criterion) {
+    let config = SiteConfig::default();
+    let mut group = c.benchmark_group("execute");
+    group.sample_size(10);
+    for &size in SIZES[..3].iter() {
+        group.bench_function(size_id(size), |b| {
+            b.iter_batched(
+                || prepare_random_problem(size, &config),
+                |mut sites| step_to_completion(&mut sites),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Signing a message and verifying it on receipt, against sending it unsigned (see
+/// `SiteConfig::untrusted_local`), per message and over a whole run
+fn bench_signing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("signing");
+    let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+    for &len in [64, 64 * 1024].iter() {
+        let msg = || Msg::AssetData {
+            asset_id: AssetId(0),
+            asset_data: AssetData { bytes: vec![7; len] },
+        };
+        group.bench_function(BenchmarkId::new("signed", len), |b| {
+            b.iter_batched(msg, |msg| msg.sign(&keypair).verify().unwrap(), BatchSize::SmallInput)
+        });
+        group.bench_function(BenchmarkId::new("unsigned", len), |b| {
+            b.iter_batched(msg, |msg| msg.unsigned(keypair.public), BatchSize::SmallInput)
+        });
+    }
+    group.sample_size(10);
+    for &untrusted_local in [false, true].iter() {
+        let config = SiteConfig { untrusted_local, ..SiteConfig::default() };
+        let id = if untrusted_local { "unsigned_run" } else { "signed_run" };
+        group.bench_function(id, |b| {
+            b.iter_batched(
+                || prepare_random_problem(SIZES[2], &config),
+                |mut sites| step_to_completion(&mut sites),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// Verifying received messages, building their signed bytes anew or in a reused buffer
+fn bench_verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify");
+    let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+    let asset_data = AssetData { bytes: vec![7; 4096] };
+    let signed_msg = Msg::AssetData { asset_id: AssetId(0), asset_data }.sign(&keypair);
+    group.bench_function("allocating", |b| b.iter(|| signed_msg.verify().unwrap()));
+    let mut buffer = vec![];
+    group.bench_function("buffered", |b| b.iter(|| signed_msg.verify_into(&mut buffer).unwrap()));
+    group.finish();
+}
+
+/// A site acquiring many large assets, each the input of a compute hashing it, verifying the
+/// messages in its execute loop or on a dedicated thread (see `SiteConfig::verify_on_thread`).
+/// The site serving them is stepped on this thread until the executing site exits.
+fn bench_verify_on_thread(c: &mut Criterion) {
+    const NUM_ASSETS: u32 = 64;
+    const ASSET_BYTES: usize = 64 * 1024;
+    let mut group = c.benchmark_group("verify_on_thread");
+    group.sample_size(10);
+    for &verify_on_thread in [false, true].iter() {
+        // The executing site exits soon after its last compute, once no more messages arrive
+        let config = SiteConfig {
+            verify_on_thread,
+            idle_timeout: Duration::from_millis(1),
+            ..SiteConfig::default()
+        };
+        let setup = || {
+            let (site_ids, mut sites) =
+                site::new_sites_with_config(null_loggers(2), &config).unwrap();
+            let [amy, bob] = [site_ids[0], site_ids[1]];
+            let (mut amy_site, mut bob_site) =
+                (sites.remove(&amy).unwrap(), sites.remove(&bob).unwrap());
+            let f = AssetId(0);
+            amy_site.insert_asset(f, AssetData::deterministic(f));
+            for i in 1..=NUM_ASSETS {
+                let [x, y] = [AssetId(i), AssetId(NUM_ASSETS + i)];
+                bob_site.insert_asset(x, AssetData { bytes: vec![i as u8; ASSET_BYTES] });
+                amy_site.todo_instructions.push(Instruction::AcquireAssetFrom {
+                    asset_id: x,
+                    site_id: bob,
+                    priority: 0,
+                    timeout: None,
+                });
+                amy_site.todo_instructions.push(Instruction::ComputeAssetData(ComputeArgs {
+                    inputs: vec![x],
+                    optional_inputs: vec![],
+                    min_optional_inputs: 0,
+                    input_range: None,
+                    outputs: vec![y],
+                    compute_asset: f,
+                    alt_compute_assets: vec![],
+                    consume_inputs: false,
+                    reducible: false,
+                    weight: 1,
+                    label: None,
+                    result_site: None,
+                }));
+            }
+            (amy_site, bob_site)
+        };
+        let id = if verify_on_thread { "threaded" } else { "inline" };
+        group.bench_function(id, |b| {
+            b.iter_batched(
+                setup,
+                |(mut amy_site, mut bob_site)| {
+                    std::thread::scope(|s| {
+                        let amy_thread = s.spawn(|| amy_site.execute().unwrap());
+                        while !amy_thread.is_finished() {
+                            if !bob_site.step().unwrap() {
+                                std::thread::yield_now();
+                            }
+                        }
+                    });
+                    assert!(amy_site.todo_instructions.is_empty());
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_plan,
+    bench_execute,
+    bench_signing,
+    bench_verify,
+    bench_verify_on_thread
+);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+use super::*;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl AccessControl {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self { grants: Default::default(), clock }
+    }
+    /// Grants all of the given (site, asset) access rights indefinitely, e.g., `Problem::may_access`
+    pub fn from_may_access(may_access: &HashSet<(SiteId, AssetId)>, clock: Arc<dyn Clock>) -> Self {
+        Self { grants: may_access.iter().map(|&pair| (pair, None)).collect(), clock }
+    }
+    /// Permit the given site to request the given asset, until the given expiry (if any)
+    pub fn grant(&mut self, site_id: SiteId, asset_id: AssetId, expires_at: Option<Instant>) {
+        self.grants.insert((site_id, asset_id), expires_at);
+    }
+    pub fn check(&self, site_id: SiteId, asset_id: AssetId) -> AccessCheck {
+        match self.grants.get(&(site_id, asset_id)) {
+            None => AccessCheck::Denied,
+            Some(Some(at)) if *at <= self.clock.now() => AccessCheck::Expired { at: *at },
+            Some(_) => AccessCheck::Granted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A clock which only moves when told to
+    #[derive(Debug)]
+    struct ManualClock(Mutex<Instant>);
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn grant_expires() {
+        let start = Instant::now();
+        let clock = Arc::new(ManualClock(Mutex::new(start)));
+        let site_id = SiteId(Keypair::generate(&mut rand_core::OsRng).public);
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let expires_at = start + Duration::from_secs(10);
+        let mut access_control = AccessControl::new(clock.clone());
+        access_control.grant(site_id, x, Some(expires_at));
+        access_control.grant(site_id, y, None);
+        assert_eq!(access_control.check(site_id, x), AccessCheck::Granted);
+        *clock.0.lock().unwrap() = expires_at;
+        assert_eq!(access_control.check(site_id, x), AccessCheck::Expired { at: expires_at });
+        assert_eq!(access_control.check(site_id, y), AccessCheck::Granted);
+    }
+}
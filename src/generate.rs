@@ -0,0 +1,64 @@
+use super::*;
+use rand::{seq::SliceRandom, Rng};
+
+/// Generate a random problem over the given sites which is guaranteed to be plannable.
+/// Assets `[0..num_initial_assets)` are data initially held by random sites. Each of the
+/// `num_computes` computes consumes some previously-available assets to produce one new asset.
+pub fn random_problem<R: Rng>(
+    rng: &mut R,
+    site_ids: &[SiteId],
+    num_initial_assets: u32,
+    num_computes: u32,
+) -> Problem {
+    assert!(!site_ids.is_empty() && num_initial_assets > 0);
+    let mut next_asset_id = 0;
+    let mut fresh_asset_id = || {
+        let asset_id = AssetId(next_asset_id);
+        next_asset_id += 1;
+        asset_id
+    };
+    let mut problem = Problem {
+        may_access: Default::default(),
+        may_compute: Default::default(),
+        site_has_asset: Default::default(),
+        do_compute: Default::default(),
+        links: None,
+        immovable: Default::default(),
+    };
+    let mut available_assets = vec![];
+    for _ in 0..num_initial_assets {
+        let asset_id = fresh_asset_id();
+        problem.site_has_asset.insert((*site_ids.choose(rng).unwrap(), asset_id));
+        available_assets.push(asset_id);
+    }
+    for _ in 0..num_computes {
+        let compute_asset = fresh_asset_id();
+        problem.site_has_asset.insert((*site_ids.choose(rng).unwrap(), compute_asset));
+        let num_inputs = rng.gen_range(1, 4).min(available_assets.len());
+        let inputs: Vec<AssetId> =
+            available_assets.choose_multiple(rng, num_inputs).copied().collect();
+        let output = fresh_asset_id();
+        // A random site may do this compute, and may access everything it needs
+        let compute_site = *site_ids.choose(rng).unwrap();
+        problem.may_compute.insert((compute_site, compute_asset));
+        for &asset_id in inputs.iter().chain(Some(&compute_asset)).chain(Some(&output)) {
+            problem.may_access.insert((compute_site, asset_id));
+        }
+        problem.do_compute.push(ComputeArgs {
+            inputs,
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![output],
+            compute_asset,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: None,
+        });
+        available_assets.push(output);
+    }
+    problem
+}
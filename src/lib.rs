@@ -0,0 +1,802 @@
+macro_rules! log {
+    ($logger:expr, $($arg:tt)*) => {{
+        if let Some(w) = $logger.line_writer() {
+            let _ = writeln!(w, $($arg)*);
+            $logger.line_done();
+        }
+    }};
+}
+
+mod access;
+pub mod generate;
+mod logging;
+pub mod planning;
+pub mod problem;
+#[cfg(feature = "cli")]
+pub mod repl;
+pub mod scenario;
+pub mod site;
+mod store;
+
+use core::hash::Hash;
+use crossbeam_channel::{Receiver, Sender};
+use ed25519_dalek::{ed25519, Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
+    fs::File,
+    io::Write,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+#[repr(transparent)]
+pub struct SiteId(PublicKey);
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AssetId(pub u32);
+
+/// Message structure communicated between sites (over channels)
+#[derive(Debug)]
+pub enum Msg {
+    AssetDataRequest { asset_id: AssetId, priority: u8 }, // requester is implicit because messages are signed
+    AssetData { asset_id: AssetId, asset_data: AssetData },
+    Register { public_key: PublicKey }, // announces a site which joined the network
+    // one of `count` parts of an asset's data too large for one message
+    AssetChunk { asset_id: AssetId, index: u32, count: u32, chunk: AssetData },
+    Subscribe { asset_id: AssetId }, // requests the asset's data be pushed whenever it changes
+    Nack { asset_id: AssetId, reason: NackReason }, // a request for the asset will not be served now
+    StealRequest, // an idle site asks for a compute to take over. See `SiteConfig::work_stealing`
+    // hands over a pending compute to the site which asked, once the assets it needs were sent
+    Delegate { compute_args: ComputeArgs },
+    Heartbeat, // the sender is alive. See `SiteConfig::heartbeat_interval`
+}
+
+/// Why a site did not serve a request for an asset
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NackReason {
+    Missing, // the site lacks the asset, but will send it once it has it
+    Denied,  // the requester may not access the asset
+}
+
+/// Why a validly-signed message breaks the invariants of messages sent by sites, so is rejected.
+/// See `SiteConfig::check_msg_structure`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MalformedMsg {
+    ChunkIndexOutOfRange { index: u32, count: u32 }, // includes a count of zero
+    EmptyChunk,                                      // assets are split into non-empty chunks
+    ForeignRegister, // a site announces itself only, so registers its sender's key
+}
+
+/// Why a received message is rejected by `SignedMsg::verify_structure`
+#[derive(Debug)]
+pub enum MsgRejection {
+    BadSignature(ed25519::Error),
+    Malformed(MalformedMsg),
+}
+
+/// Which validly-signed messages a site accepts, by their sender
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum SenderPolicy {
+    #[default]
+    AnySigner, // any sender whose signature verifies
+    KnownPeers, // only sites in the site's network, i.e., those it can send to
+}
+
+/// Which data a site keeps on receiving an asset it holds already, with different data,
+/// e.g., from two sources whose copies diverged
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub enum ReplyPolicy {
+    #[default]
+    LatestWins, // the received data replaces the held data, e.g., as subscribers are pushed updates
+    FirstWins,     // the received data is dropped
+    FlagConflicts, // as `FirstWins`, but logged and counted in `SiteMetrics::conflicting_replies`
+}
+
+#[derive(Debug)]
+pub struct SignedMsg {
+    pub sender_public_key: PublicKey,
+    pub signature: Option<Signature>, // None iff sent by a site in `untrusted_local` mode
+    pub msg: Msg,
+}
+
+/// The logical content of an asset. Hashes and computes are over these bytes, so any encoding
+/// (e.g., compression) in transit or at rest must be undone before data is wrapped in this.
+#[derive(Clone, Eq, PartialEq)]
+pub struct AssetData {
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
+pub struct ComputeArgs {
+    pub inputs: Vec<AssetId>,
+    // routed to the compute site if available, but not waited for. The compute is passed those
+    // which arrived in time, so `ComputeFn`s must incorporate which were present into outputs.
+    #[serde(default)]
+    pub optional_inputs: Vec<AssetId>,
+    // the compute waits until at least this many optional inputs arrived, e.g., for a reduction
+    // over however many of its candidate inputs are available by then
+    #[serde(default)]
+    pub min_optional_inputs: usize,
+    // every other asset in this range is an optional input too, as discovered by the compute site
+    // when it tries the compute, e.g., to reduce over all assets of one kind available by then
+    #[serde(default)]
+    pub input_range: Option<AssetRange>,
+    pub outputs: Vec<AssetId>,
+    pub compute_asset: AssetId,
+    pub alt_compute_assets: Vec<AssetId>, // equivalent to `compute_asset`. Planning chooses one
+    pub consume_inputs: bool, // inputs are removed from the compute site's store once computed
+    // computing from the results of computing with subsets of inputs gives the same outputs,
+    // so planning may pre-reduce inputs at the sites holding them. Requires a single output.
+    pub reducible: bool,
+    pub weight: u64, // estimated duration of the compute, in arbitrary units, for scheduling
+    #[serde(default)]
+    pub label: Option<String>, // shown in logs, to correlate this compute's steps across sites
+    // the outputs are routed here to be kept, and the compute site drops them once it's done with them
+    #[serde(default)]
+    pub result_site: Option<SiteId>,
+}
+
+/// The asset IDs from `start` up to but excluding `end`, e.g., the assets of one kind
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AssetRange {
+    pub start: AssetId,
+    pub end: AssetId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Instruction {
+    // Higher priority transfers are more urgent
+    SendAssetTo {
+        asset_id: AssetId,
+        site_id: SiteId,
+        priority: u8,
+    },
+    AcquireAssetFrom {
+        asset_id: AssetId,
+        site_id: SiteId,
+        priority: u8,
+        // execution fails unless the asset is acquired within this long of first trying
+        #[serde(default)]
+        timeout: Option<Duration>,
+    },
+    ComputeAssetData(ComputeArgs),
+    Verify {
+        asset_id: AssetId,
+        expected_hash: u64,
+    }, // see `AssetData::content_hash`
+}
+
+/// A site's instructions, signed by the planner such that the site can check their origin
+#[derive(Debug, Clone)]
+pub struct SignedInstructions {
+    pub site_id: SiteId, // the site these are for, such that they cannot be replayed to others
+    pub instructions: Vec<Instruction>,
+    pub signature: Option<Signature>, // None if unsigned
+}
+
+/// Source of the current time, injectable for tests
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug)]
+pub struct SystemClock;
+
+/// Which sites may request which assets from a site. Grants may expire.
+#[derive(Debug, Clone)]
+pub struct AccessControl {
+    grants: HashMap<(SiteId, AssetId), Option<Instant>>, // None: never expires
+    clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AccessCheck {
+    Granted,
+    Denied,
+    Expired { at: Instant },
+}
+
+/// Shared among sites to notify waiters once every goal (site, asset) pair has been stored
+#[derive(Debug, Clone)]
+pub struct CompletionWatcher {
+    remaining_goals: Arc<Mutex<HashSet<(SiteId, AssetId)>>>,
+    all_reached: Arc<Condvar>,
+}
+
+/// Shareable view of a site's progress, readable while it executes. Updated each loop iteration.
+#[derive(Debug, Clone, Default)]
+pub struct SiteProgress {
+    asset_count: Arc<AtomicUsize>,
+    pending_count: Arc<AtomicUsize>,
+}
+
+/// Shareable switch pausing a site's `execute`, e.g., to inspect part of the system while frozen.
+/// A paused site neither completes instructions nor handles messages, which wait in its inbox.
+#[derive(Debug, Clone, Default)]
+pub struct SitePause {
+    paused: Arc<Mutex<bool>>,
+    resumed: Arc<Condvar>,
+}
+
+/// Sends commands to a site, carried out even while it executes, e.g., from another thread.
+/// See `Site::control_handle`.
+#[derive(Debug, Clone)]
+pub struct SiteControl {
+    commands: Sender<SiteCommand>,
+}
+
+/// What a `SiteControl` asks of its site
+#[derive(Debug)]
+pub enum SiteCommand {
+    CancelCompute(ComputeArgs),          // see `Site::cancel_compute`
+    AddInstructions(SignedInstructions), // see `Site::accept_instructions`
+}
+
+/// Delivers assets to a site as if sent by the site itself, e.g., to simulate external deliveries
+/// while it executes. See `Site::asset_injector`.
+#[cfg(feature = "test-hooks")]
+#[derive(Debug)]
+pub struct AssetInjector {
+    keypair: Keypair, // of the site, signing the deliveries
+    inbox: Sender<SignedMsg>,
+}
+
+/// Per-site settings, fixed when the sites are constructed
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    /// How long a site without instructions to do keeps serving requests after the last message.
+    pub idle_timeout: Duration,
+    /// How long a site with instructions to do waits for a message before giving up on the network.
+    /// Note that without `heartbeat_interval`, a silent peer (e.g., doing a long compute) looks
+    /// the same as a dead one. This must exceed the longest silence expected of a live network.
+    pub quiescence_timeout: Duration,
+    /// Skip signing and verifying messages, trusting all sites to be cooperative.
+    /// Only for in-process simulations! Never use this over a network.
+    pub untrusted_local: bool,
+    /// Bound on the number of messages in flight to each site. Unbounded if None.
+    /// Sends to a full site are deferred rather than blocking the sender.
+    pub channel_capacity: Option<usize>,
+    /// How often a site without progress logs what its instructions are blocked on. Never if None.
+    pub watchdog_interval: Option<Duration>,
+    /// Messages larger than this many bytes are dropped on receipt. Unlimited if None.
+    /// Larger assets are sent in chunks within the limit, assuming all sites share this setting.
+    pub max_msg_bytes: Option<usize>,
+    /// How long a site holding all its goals keeps serving requests before exiting, however busy
+    /// its peers keep it. Sites only exit once idle if None.
+    pub goal_grace: Option<Duration>,
+    /// How long an acquired asset may go unused before it is evicted, to be re-fetched from its
+    /// source if needed again. Goals and assets needed by instructions to do are kept. Never if None.
+    pub asset_ttl: Option<Duration>,
+    /// Store (and push to subscribers) each output of a compute as soon as the `ComputeFn`
+    /// streams it, such that consumers can start on it while the others are computed.
+    /// Streaming computes read copies of their needed assets.
+    pub stream_compute_outputs: bool,
+    /// Fraction by which the period between requests for the same asset randomly varies either
+    /// way, e.g., 0.2 for +-20%, such that sites awaiting many assets spread out their requests.
+    pub request_jitter: f64,
+    /// Seeds all of each site's random choices, combined with its site ID such that sites differ.
+    /// Randomly seeded if `None`. See `Site::set_seed`.
+    pub seed: Option<u64>,
+    /// Messages from senders not allowed by this policy are logged and dropped
+    pub sender_policy: SenderPolicy,
+    /// Whether received assets replace differing data held already
+    pub reply_policy: ReplyPolicy,
+    /// Once a bounded inbox (see `channel_capacity`) is full, make room for new messages by
+    /// dropping the oldest droppable one (see `Msg::is_droppable`), rather than deferring sends.
+    pub evict_when_inbox_full: bool,
+    /// Sites without instructions to do keep serving until all sites reach their goals (see
+    /// `Site::set_completion_watcher`), rather than exiting once idle, as peers may yet request
+    /// assets from them, e.g., after evicting them. They exit after `quiescence_timeout` regardless.
+    pub await_all_goals: bool,
+    /// Of the computes ready at once, start those of greatest `ComputeArgs::weight` first, such
+    /// that long computes on which much depends are not held up, rather than in instruction order.
+    pub heaviest_computes_first: bool,
+    /// Request all the missing inputs of pending computes at the start of each instruction scan,
+    /// such that their transfers overlap with other work, e.g., computes ahead of them, rather
+    /// than as the scan reaches their acquisitions.
+    pub prefetch_inputs: bool,
+    /// While executing, verify received messages on a dedicated thread, which passes on only
+    /// those which pass (in the order received), such that the execute loop need not.
+    pub verify_on_thread: bool,
+    /// Log each instruction a site has to do once it starts executing, one per line, such that
+    /// the plan can be reconstructed from the sites' logs alone
+    pub log_initial_instructions: bool,
+    /// Reject validly-signed messages which no site would send (see `MalformedMsg`), e.g., a
+    /// chunk beyond its asset's chunk count, after verifying their signatures.
+    pub check_msg_structure: bool,
+    /// Record each request for an asset this site handles, whether granted or denied, for
+    /// auditing after the run. See `Site::access_log`.
+    pub audit_access: bool,
+    /// Idle sites ask their peers for work, and sites with several ready computes hand one over to
+    /// a site which asked, if it may compute it (see `Site::set_may_compute`) and access its needed
+    /// assets, sending them along. Only sites awaiting all goals (see `await_all_goals`) ask, as
+    /// they stay to complete the computes they take over.
+    pub work_stealing: bool,
+    /// Sites store their assets in a `SharedContentStore`, keeping one copy of identical
+    /// content received under distinct asset IDs. Unless replaced, see `Site::set_asset_store`.
+    pub share_identical_content: bool,
+    /// Executing sites send each peer a heartbeat this often. A site awaiting assets from a peer
+    /// it has not heard from in several intervals suspects it failed, and reports it to its
+    /// failure watcher (see `Site::set_failure_watcher`), e.g., to reassign its computes with
+    /// `Failover`. Computes block heartbeats, so this must exceed the longest compute.
+    pub heartbeat_interval: Option<Duration>,
+}
+
+/// Counts of a site's work and repeated messages. The latter stay near zero unless messages are
+/// lost or the plan has sites waiting on one another, so high counts flag network or planning problems.
+#[derive(Debug, Default, Clone)]
+pub struct SiteMetrics {
+    pub re_requests: HashMap<AssetId, u64>, // requests after the first, per asset
+    pub retransmissions: u64,               // asset data sent to a site which was sent it before
+    pub messages_sent: u64,
+    pub bytes_sent: u64, // as measured by `SignedMsg::wire_len`
+    pub computes: u64,
+    pub messages_evicted: u64, // dropped from a full inbox. See `SiteConfig::evict_when_inbox_full`
+    pub duplicates_received: u64, // received assets already held with the same content, so not re-stored
+    pub verification_failures: u64, // received messages dropped for failing signature verification
+    pub conflicting_replies: u64, // see `ReplyPolicy::FlagConflicts`
+    pub malformed_msgs: u64, // validly-signed but dropped. See `SiteConfig::check_msg_structure`
+    pub computes_delegated: u64, // handed over to idle sites. See `SiteConfig::work_stealing`
+    pub computes_taken_over: u64, // from busy sites
+}
+
+type Outboxes = Arc<RwLock<HashMap<SiteId, Sender<SignedMsg>>>>;
+type PartialAsset = (u32, HashMap<u32, Vec<u8>>); // chunk count, and chunks received so far
+
+/// Handle for adding sites to a network of sites, possibly while they execute
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    outboxes: Outboxes,
+    config: SiteConfig,
+}
+
+#[derive(Debug)]
+struct SiteInner {
+    keypair: Keypair,
+    config: SiteConfig,
+    outboxes: Outboxes,
+    asset_store: Box<dyn AssetStore>,
+    inbox: Receiver<SignedMsg>,
+    signing_buffer: Vec<u8>, // reused to build the signed bytes of each message sent and received
+    inbox_buffer: VecDeque<SignedMsg>, // received but not yet handled. See `SiteConfig::evict_when_inbox_full`
+    inbox_verified: bool, // the inbox is fed by a verifier thread. See `SiteConfig::verify_on_thread`
+    commands: Receiver<SiteCommand>, // from the site's `SiteControl`s
+    command_sender: Sender<SiteCommand>, // cloned into each `SiteControl`
+    compute_fn: Option<Arc<dyn ComputeFn>>, // for compute assets without one in `compute_fns`
+    compute_fns: HashMap<AssetId, Arc<dyn ComputeFn>>, // by compute asset
+    rate_limiter: Option<RateLimiter>,
+    completion_watcher: Option<CompletionWatcher>,
+    progress: SiteProgress,
+    pause: SitePause,
+    access_control: Option<AccessControl>, // None: all requests are granted
+    request_again_at: HashMap<AssetId, Instant>, // for requested assets. alternative: Sorted vector of (Instant, AssetId).
+    seeder: rand::rngs::SmallRng, // seeds the RNGs below, such that one seed determines them all
+    request_jitter_rng: rand::rngs::SmallRng,
+    // sources not to request these assets from again, until the instant if any. See `is_nacked`
+    nacked: HashMap<(SiteId, AssetId), (NackReason, Option<Instant>)>,
+    full_outboxes: HashSet<SiteId>, // sends to these are deferred until the next loop iteration
+    scan_shuffle: Option<rand::rngs::SmallRng>, // None: instructions are scanned in order
+    sent_assets: HashSet<(SiteId, AssetId)>,
+    partial_assets: HashMap<(SiteId, AssetId), PartialAsset>, // by sender
+    // by destination: content hash, and the next chunk to send, once a full outbox interrupted it
+    chunks_sent: HashMap<(SiteId, AssetId), (u64, u32)>,
+    metrics: SiteMetrics,
+    subscribers: HashMap<AssetId, HashSet<SiteId>>,
+    stale_subscribers: HashSet<(SiteId, AssetId)>, // yet to be pushed the latest version of the asset
+    goals: HashSet<AssetId>,                       // this site's work is done once it holds these
+    planner_key: Option<PublicKey>, // None: instructions are accepted without signatures
+    last_accessed_at: HashMap<AssetId, Instant>, // only tracked given `SiteConfig::asset_ttl`
+    acquired_from: HashMap<AssetId, SiteId>, // where to re-fetch evicted assets from
+    acquiring_since: HashMap<AssetId, Instant>, // when acquisitions with timeouts were first tried
+    access_log: Vec<AccessRecord>,  // only recorded given `SiteConfig::audit_access`
+    may_compute: HashSet<(SiteId, AssetId)>, // to whom computes may be delegated. See `Site::set_may_compute`
+    steal_asked: HashSet<SiteId>, // peers asked for work since `steal_asked_at`, yet to delegate any
+    steal_asked_at: Option<Instant>, // when peers were last asked for work
+    heartbeat_sent_at: Option<Instant>, // see `SiteConfig::heartbeat_interval`
+    last_heard: HashMap<SiteId, Instant>, // by each peer this site awaits assets from
+    suspected: HashSet<SiteId>,   // peers reported to `failure_watcher`, until heard from again
+    failure_watcher: Option<Sender<SiteId>>,
+    results_elsewhere: HashSet<AssetId>, // computed here for another result site. Dropped once unused
+    logger: Box<dyn Logger>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span, // parents the site's `send`, `recv`, and `compute` events
+}
+
+/// Token bucket limiting how often a site performs computes and sends
+#[derive(Debug)]
+pub struct RateLimiter {
+    tokens_per_sec: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    starved: bool, // Some action was deferred for want of tokens since this was last reset
+}
+
+#[derive(Debug)]
+pub struct Site {
+    pub(crate) inner: SiteInner,
+    pub todo_instructions: Vec<Instruction>, // Order is preserved (unless shuffled). Earlier instructions are completed first.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    pub may_access: HashSet<(SiteId, AssetId)>,
+    pub may_compute: HashSet<(SiteId, AssetId)>,
+    pub site_has_asset: HashSet<(SiteId, AssetId)>,
+    pub do_compute: Vec<ComputeArgs>, // outputs are implicit goals
+    #[serde(default)]
+    pub links: Option<HashSet<(SiteId, SiteId)>>, // (from, to) sites which can communicate directly. All pairs if None
+    // never routed, e.g., large reference data. Computes needing these are placed at their holders
+    #[serde(default)]
+    pub immovable: HashSet<AssetId>,
+}
+
+#[derive(Debug)]
+pub enum PlanError<'a> {
+    CyclicCausality(&'a ComputeArgs),
+    NoSiteForCompute(&'a ComputeArgs),
+    ImmovableInputsApart(&'a ComputeArgs), // no site holds all its immovable inputs. See `Problem::immovable`
+    ConsumedInput { compute_args: &'a ComputeArgs, asset_id: AssetId },
+    // no source of the asset can reach an eligible compute site, even via relays. E.g., `from` to `to`.
+    // Boxed, as site IDs are large
+    Unreachable { asset_id: AssetId, from: Box<SiteId>, to: Box<SiteId> },
+    Invalid(ProblemError<'a>), // see `Problem::validate`
+    // the compute's `result_site` may not access this output, so cannot keep it
+    ResultSiteDenied { compute_args: &'a ComputeArgs, asset_id: AssetId },
+}
+
+/// Whether a compute of a problem can be planned, as estimated by `Problem::feasibility_report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComputeFeasibility<'a> {
+    Feasible(&'a ComputeArgs),
+    NoEligibleSite(&'a ComputeArgs), // no site may compute with its compute asset and access its inputs
+    MissingInput { compute_args: &'a ComputeArgs, asset_id: AssetId }, // neither held nor producible
+    Invalid { compute_args: &'a ComputeArgs, error: ComputeArgsError }, // see `ComputeArgs::validate`
+}
+
+/// A permission to add to a problem, e.g., such that it can be planned. See `planning::suggest_grants`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Grant {
+    MayAccess(SiteId, AssetId),
+    MayCompute(SiteId, AssetId),
+}
+
+/// Elements added and removed between two versions of a collection
+#[derive(Debug)]
+pub struct SetDiff<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+}
+
+/// Changes from one `Problem` to another, field by field
+#[derive(Debug)]
+pub struct ProblemDiff {
+    pub may_access: SetDiff<(SiteId, AssetId)>,
+    pub may_compute: SetDiff<(SiteId, AssetId)>,
+    pub site_has_asset: SetDiff<(SiteId, AssetId)>,
+    pub do_compute: SetDiff<ComputeArgs>,
+    pub links: SetDiff<(SiteId, SiteId)>, // of the listed links. Unrestricted links list none
+    pub links_restricted: Option<bool>,   // Some iff changed, e.g., `Some(true)` from unrestricted
+    pub immovable: SetDiff<AssetId>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ComputeArgsError {
+    NoInputs,
+    InputIsOutput(AssetId),
+    ComputeAssetIsOutput,
+    DuplicateAsset(AssetId),
+    ReducibleWithoutSingleOutput,
+    TooFewOptionalInputs, // fewer than `min_optional_inputs`
+}
+
+#[derive(Debug)]
+pub enum ProblemError<'a> {
+    InvalidCompute(&'a ComputeArgs, ComputeArgsError),
+    MultipleProducers { asset_id: AssetId },
+}
+
+#[derive(Debug)]
+pub enum MergeConflict {
+    ConflictingProducers { asset_id: AssetId, computes: Box<[ComputeArgs; 2]> },
+    // the merged problem fails `Problem::validate`
+    InvalidCompute { compute_args: Box<ComputeArgs>, error: ComputeArgsError },
+    MultipleProducers { asset_id: AssetId },
+}
+
+/// Why sites could not be created from the given identities, see `site::new_sites_with_keypairs`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum NewSitesError {
+    KeypairCountMismatch { keypairs: usize, loggers: usize }, // one keypair is needed per logger
+    InvalidConfig(SiteConfigError),
+}
+
+/// Why a `SiteConfig` is unusable, see `SiteConfig::validate`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SiteConfigError {
+    // some messages could never be sent, e.g., any chunk of an asset
+    MaxMsgBytesTooSmall { max_msg_bytes: usize, min: usize },
+}
+
+/// Sites given their planned instructions and initial data, see `scenario::prepare`
+#[derive(Debug)]
+pub struct PreparedRun {
+    pub sites: HashMap<SiteId, Site>,
+    pub planned: HashMap<SiteId, Vec<Instruction>>, // each site's instructions, e.g., to show
+    pub final_placement: Placement,                 // predicted by planning
+    pub goals: HashSet<(SiteId, AssetId)>,
+    pub completion_watcher: CompletionWatcher,
+}
+
+/// Outcome of running a scenario: each site's final state, and which goals were met
+#[derive(Debug)]
+pub struct RunReport {
+    pub elapsed: Duration,
+    pub sites: HashMap<SiteId, SiteReport>,
+    pub goals_met: HashSet<(SiteId, AssetId)>,
+    pub goals_missed: HashSet<(SiteId, AssetId)>,
+}
+
+/// A request for an asset which a site handled, as recorded given `SiteConfig::audit_access`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AccessRecord {
+    pub requester: SiteId,
+    pub asset_id: AssetId,
+    pub granted: bool, // false: denied by the site's `AccessControl`
+    pub at: Instant,
+}
+
+/// A site's state once it stopped executing
+#[derive(Debug)]
+pub struct SiteReport {
+    pub result: Result<(), ExecError>,
+    pub held_assets: BTreeSet<AssetId>,
+    pub remaining_instructions: usize,
+    pub unhandled_messages: usize, // left in the inbox once all sites stopped. See `Site::drain_inbox`
+    pub metrics: SiteMetrics,
+    pub access_log: Vec<AccessRecord>, // see `SiteConfig::audit_access`
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    InvalidProblem(String),
+    PlanFailed(String),
+    UnknownSite(SiteId),
+    MissingInitialData { site_id: SiteId, asset_id: AssetId },
+    MissingFinalAsset { site_id: SiteId, asset_id: AssetId },
+    // a site's inbox held messages once every goal was met, e.g., requests left unanswered
+    UnhandledMessages { site_id: SiteId, count: usize },
+    InstructionsRejected(InstructionsRejected),
+    Io(std::io::Error),
+}
+
+#[derive(Debug)]
+pub enum InstructionsRejected {
+    Unsigned,
+    WrongSite, // the instructions were for another site
+    BadSignature(ed25519::Error),
+}
+
+#[derive(Debug)]
+pub enum ExecError {
+    ComputeFailed { compute_args: Box<ComputeArgs>, error: ComputeError },
+    VerifyFailed { asset_id: AssetId, expected_hash: u64, actual_hash: u64 },
+    AcquireTimedOut { asset_id: AssetId, timeout: Duration }, // see `Instruction::AcquireAssetFrom`
+}
+
+/// The part a site plays in a plan, derived from the instructions it was assigned
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SiteRole {
+    Producer, // only sends
+    Consumer, // only acquires
+    Relay,    // sends and acquires, but does not compute
+    Compute,  // computes
+}
+
+/// One compute symbolically executed by the planner, with the (asset, source site) routes it needed
+#[derive(Debug, Clone)]
+pub struct PlanTraceStep<'a> {
+    pub compute_args: &'a ComputeArgs,
+    pub compute_site: SiteId,
+    pub routes: Vec<(AssetId, SiteId)>,
+}
+
+pub type Placement = HashSet<(SiteId, AssetId)>;
+/// The data of each asset which a site initially has
+pub type InitialData = HashMap<(SiteId, AssetId), AssetData>;
+
+#[derive(Debug, Default)]
+pub struct PlanTrace<'a> {
+    pub steps: Vec<PlanTraceStep<'a>>,
+    pub duplicate_routes: Vec<(AssetId, SiteId)>, // (asset, destination) transfers planned only once
+    pub reused_outputs: Vec<&'a ComputeArgs>, // computes not planned, as their outputs existed already
+    pub final_placement: Placement, // predicted end state. Empty unless planning succeeded
+    pub estimated_makespan: u64,    // total weight of the critical path of computes
+    pub stats: PlanStats,
+    pub strategy: PlanStrategy, // applied to choose compute sites
+    // every planned instruction, ordered such that each asset is sent before it is needed
+    pub sequence: Vec<(SiteId, Instruction)>,
+}
+
+/// Reassigns the work of failed sites to live ones while the sites execute a plan, e.g., as
+/// sites report failures detected by heartbeats. See `SiteConfig::heartbeat_interval`.
+pub struct Failover {
+    problem: Problem,
+    instructions: HashMap<SiteId, Vec<Instruction>>, // of each site, as planned, then as reassigned
+    failed: HashSet<SiteId>,
+}
+
+/// Why the work of a failed site could not be reassigned, see `Failover::reassign`
+#[derive(Debug)]
+pub enum FailoverError {
+    NoSiteForCompute(Box<ComputeArgs>), // no live site may do this compute of the failed site
+    Lost { asset_id: AssetId },         // no live site holds (or is to produce) the asset
+}
+
+/// Plans computes as they are added, e.g., for streaming workloads, without re-planning the
+/// computes added before. See `Planner::add_compute`.
+pub struct Planner {
+    problem: Problem, // computes are appended as they are added
+    // Computes of `problem` added successfully. Any after these were rejected, and are kept only
+    // for the errors referring to them
+    accepted: usize,
+    pending: Vec<usize>, // indices of added computes not yet planned, as their inputs are not yet available
+    state: planning::PlanState,
+}
+
+/// How the planner chooses among the sites eligible to perform each compute.
+/// Remaining ties are broken in favor of the lowest `SiteId`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PlanStrategy {
+    /// Prefer sites holding the most needed assets, then those with the fewest computes so far
+    #[default]
+    Balance,
+    /// Prefer sites already computing, then those holding the most needed assets,
+    /// minimizing the number of distinct compute sites
+    Consolidate,
+    /// The first eligible site, regardless of where assets are
+    FirstFit,
+    /// Prefer sites holding the most intermediate assets which only this compute needs, i.e.,
+    /// computing where they were produced, then those holding the most needed assets.
+    /// Each such intermediate need not be transferred at all.
+    Colocate,
+}
+
+/// Counts of the work done by the planner's scans, which grow quadratically in the worst case
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlanStats {
+    pub feasibility_checks: usize,   // computes checked for feasibility
+    pub site_selection_scans: usize, // (site, compute asset) permissions scanned to select compute sites
+    pub having_site_lookups: usize,  // holders of assets considered to find senders
+}
+
+#[derive(Debug)]
+pub enum PlanConsistencyError<'a> {
+    UnmatchedAcquire { site_id: SiteId, asset_id: AssetId, from: SiteId },
+    UnsatisfiedCompute { site_id: SiteId, compute_args: &'a ComputeArgs },
+}
+
+pub trait Logger: std::fmt::Debug + Send {
+    fn line_writer(&mut self) -> Option<&mut dyn Write>;
+    /// Called after each line is written to the `line_writer`
+    fn line_done(&mut self) {}
+}
+
+/// Computes the data of a compute's outputs, given the asset store holding its needed assets.
+/// Computes with no outputs are pure checks.
+pub trait ComputeFn: std::fmt::Debug + Send + Sync {
+    fn compute(
+        &self,
+        store: &dyn AssetStore,
+        compute_args: &ComputeArgs,
+    ) -> Result<HashMap<AssetId, AssetData>, ComputeError>;
+    /// As `compute`, but sending each output through `outputs` as soon as it is ready.
+    /// See `SiteConfig::stream_compute_outputs`. By default, outputs are sent once all are computed.
+    fn compute_streaming(
+        &self,
+        store: &dyn AssetStore,
+        compute_args: &ComputeArgs,
+        outputs: &Sender<(AssetId, AssetData)>,
+    ) -> Result<(), ComputeError> {
+        for output in self.compute(store, compute_args)? {
+            let _ = outputs.send(output);
+        }
+        Ok(())
+    }
+}
+
+/// Why a `ComputeFn` failed
+#[derive(Debug, Clone)]
+pub struct ComputeError {
+    pub message: String,
+    pub input: Option<AssetId>, // the offending input, if any
+}
+
+/// A site's local storage of asset data. `HashMap` is the default, in-memory store.
+pub trait AssetStore: std::fmt::Debug + Send {
+    fn get(&self, asset_id: &AssetId) -> Option<Cow<'_, AssetData>>;
+    fn insert(&mut self, asset_id: AssetId, asset_data: AssetData);
+    fn contains_key(&self, asset_id: &AssetId) -> bool;
+    /// Returns whether the asset was present
+    fn remove(&mut self, asset_id: &AssetId) -> bool;
+    /// The assets currently stored, in no particular order
+    fn asset_ids(&self) -> Vec<AssetId>;
+    fn len(&self) -> usize {
+        self.asset_ids().len()
+    }
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An `AssetStore` keeping each asset's data in its own file in the given directory
+#[derive(Debug)]
+pub struct DirAssetStore {
+    dir: PathBuf,
+}
+
+/// An in-memory `AssetStore` keeping each asset gzipped, and decoding it on `get`, such that
+/// hashes and computes are over the same bytes as for any other store
+#[derive(Debug, Default)]
+pub struct GzipAssetStore {
+    assets: HashMap<AssetId, Vec<u8>>, // gzipped data
+}
+
+/// An in-memory `AssetStore` keeping one copy of each distinct content, shared by all the assets
+/// with that content, e.g., the same data received under several asset IDs
+#[derive(Debug, Default)]
+pub struct SharedContentStore {
+    assets: HashMap<AssetId, Arc<AssetData>>,
+    // each distinct content, by `AssetData::content_hash`. Usually one per hash.
+    contents: HashMap<u64, Vec<Arc<AssetData>>>,
+}
+
+/// The default `ComputeFn`, deriving outputs by hashing the needed assets' data with `H`.
+/// All sites must use the same `H` (and input order) to agree on the outputs of the same compute.
+/// By default, inputs are hashed in the order listed, so listing them otherwise changes outputs.
+pub struct HashCompute<H = fnv::FnvHasher> {
+    sort_inputs: bool, // inputs are hashed in `AssetId` order, whatever their listed order
+    phantom: PhantomData<fn() -> H>,
+}
+
+#[derive(Debug)]
+pub struct FileLogger {
+    file: std::fs::File,
+    path: PathBuf,
+    rotation: Option<LogRotation>,
+    sync_each_line: bool,
+    rolled_count: usize,
+    // joined on drop, such that no rolled file is left half-compressed. Failures are logged.
+    compressions: Vec<(PathBuf, std::thread::JoinHandle<std::io::Result<()>>)>,
+}
+
+/// Logs each line as a `tracing` event within a span of its site, for existing `tracing`
+/// subscribers to collect. Independent of the logger, sites emit structured `send`, `recv`, and
+/// `compute` events whenever the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+#[derive(Debug)]
+pub struct TracingLogger {
+    span: tracing::Span,
+    line: Vec<u8>, // the line being written, emitted as an event once done
+}
+
+/// When a `FileLogger` rolls its file over to `{stem}.{n}.{ext}`, starting a fresh one
+#[derive(Debug, Clone)]
+pub struct LogRotation {
+    pub max_bytes: u64,
+    pub compress: bool, // gzip rolled files to `{stem}.{n}.{ext}.gz` in the background
+}
+
+#[derive(Debug)]
+pub struct NullLogger;
+////////////////////////////////////////////////
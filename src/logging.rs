@@ -0,0 +1,192 @@
+use super::*;
+use std::io::Seek;
+
+impl FileLogger {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(path: impl AsRef<Path>) -> Box<dyn Logger> {
+        Self::with_rotation(path, None)
+    }
+    /// As `new`, but rolling over the file as configured by `rotation`
+    pub fn with_rotation(path: impl AsRef<Path>, rotation: Option<LogRotation>) -> Box<dyn Logger> {
+        Box::new(Self::create(path.as_ref(), rotation, false)) as Box<dyn Logger>
+    }
+    /// As `with_rotation`, but each line reaches the disk before logging continues, such that
+    /// lines survive even an OS crash or power loss. Lines already reach the OS as they are
+    /// written, so a crash of this process alone loses nothing either way.
+    pub fn with_sync_each_line(
+        path: impl AsRef<Path>,
+        rotation: Option<LogRotation>,
+    ) -> Box<dyn Logger> {
+        Box::new(Self::create(path.as_ref(), rotation, true)) as Box<dyn Logger>
+    }
+    fn create(path: &Path, rotation: Option<LogRotation>, sync_each_line: bool) -> Self {
+        Self {
+            file: File::create(path).unwrap(),
+            path: path.to_path_buf(),
+            rotation,
+            sync_each_line,
+            rolled_count: 0,
+            compressions: vec![],
+        }
+    }
+    /// Path of the `n`th rolled file, e.g., `logs/amy.1.txt` for `logs/amy.txt`
+    fn rolled_path(&self, n: usize) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match self.path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, n, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, n),
+        };
+        self.path.with_file_name(file_name)
+    }
+    fn roll_over(&mut self, compress: bool) -> std::io::Result<()> {
+        self.rolled_count += 1;
+        let rolled_path = self.rolled_path(self.rolled_count);
+        std::fs::rename(&self.path, &rolled_path)?;
+        self.file = File::create(&self.path)?;
+        if compress {
+            let path = rolled_path.clone();
+            self.compressions.push((rolled_path, std::thread::spawn(move || gzip_file(&path))));
+        }
+        Ok(())
+    }
+    /// Log the failures of the compressions which are done, awaiting them all if `wait`
+    fn log_compression_failures(&mut self, wait: bool) {
+        let (done, pending) = std::mem::take(&mut self.compressions)
+            .into_iter()
+            .partition(|(_, compression)| wait || compression.is_finished());
+        self.compressions = pending;
+        for (rolled_path, compression) in done {
+            let error = match compression.join() {
+                Ok(Ok(())) => continue,
+                Ok(Err(e)) => format!("{:?}", e),
+                Err(_) => "the compressing thread panicked".to_owned(),
+            };
+            let _ = writeln!(
+                &mut self.file,
+                ">> Failed to compress rolled log {:?}: {}",
+                rolled_path, error
+            );
+        }
+    }
+}
+
+/// Replaces the file at `path` with its gzipped version at `{path}.gz`
+fn gzip_file(path: &Path) -> std::io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let mut encoder =
+        flate2::write::GzEncoder::new(File::create(gz_path)?, flate2::Compression::default());
+    std::io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    std::fs::remove_file(path)
+}
+
+impl Drop for FileLogger {
+    fn drop(&mut self) {
+        self.log_compression_failures(true);
+    }
+}
+#[cfg(feature = "tracing")]
+impl TracingLogger {
+    /// Logger for the site of the given name, e.g., as in `new_sites_with_logger_factory`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(site_name: &str) -> Box<dyn Logger> {
+        let span = tracing::info_span!("site", name = site_name);
+        Box::new(Self { span, line: vec![] }) as Box<dyn Logger>
+    }
+}
+
+impl Logger for NullLogger {
+    fn line_writer(&mut self) -> Option<&mut dyn Write> {
+        None
+    }
+}
+impl Logger for FileLogger {
+    fn line_writer(&mut self) -> Option<&mut dyn Write> {
+        // Roll over between lines, such that no line is split across files
+        if let Some(LogRotation { max_bytes, compress }) = self.rotation {
+            if self.file.stream_position().unwrap() >= max_bytes {
+                self.roll_over(compress).unwrap();
+            }
+        }
+        self.log_compression_failures(false);
+        write!(&mut self.file, ">> ").unwrap();
+        Some(&mut self.file)
+    }
+    fn line_done(&mut self) {
+        if self.sync_each_line {
+            let _ = self.file.sync_data();
+        }
+    }
+}
+#[cfg(feature = "tracing")]
+impl Logger for TracingLogger {
+    fn line_writer(&mut self) -> Option<&mut dyn Write> {
+        self.line.clear();
+        Some(&mut self.line)
+    }
+    fn line_done(&mut self) {
+        let line = String::from_utf8_lossy(&self.line);
+        let line = line.trim_end();
+        tracing::info!(parent: &self.span, "{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn rolled_logs_are_gzipped_intact() {
+        let dir = std::env::temp_dir().join(format!("site_exec_logs_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("amy.txt");
+        let rotation = LogRotation { max_bytes: 16, compress: true };
+        let mut logger = FileLogger::with_rotation(&path, Some(rotation));
+        log!(logger, "The first line, which fills the file");
+        log!(logger, "The second line");
+        // Dropping the logger awaits the compression in the background
+        drop(logger);
+        assert!(!dir.join("amy.1.txt").exists());
+        let mut rolled = String::new();
+        let gz_file = File::open(dir.join("amy.1.txt.gz")).unwrap();
+        flate2::read::GzDecoder::new(gz_file).read_to_string(&mut rolled).unwrap();
+        assert_eq!(rolled, ">> The first line, which fills the file\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ">> The second line\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn synced_lines_are_readable_while_logging() {
+        let dir = std::env::temp_dir().join(format!("site_exec_sync_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bob.txt");
+        let mut logger = FileLogger::with_sync_each_line(&path, None);
+        log!(logger, "Durable line");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ">> Durable line\n");
+        drop(logger);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_compression_is_logged() {
+        let dir = std::env::temp_dir().join(format!("site_exec_gz_fail_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // the compressed file cannot be created where a directory is in the way
+        std::fs::create_dir_all(dir.join("cho.1.txt.gz")).unwrap();
+        let path = dir.join("cho.txt");
+        let rotation = LogRotation { max_bytes: 16, compress: true };
+        let mut logger = FileLogger::with_rotation(&path, Some(rotation));
+        log!(logger, "The first line, which fills the file");
+        log!(logger, "The second line");
+        drop(logger);
+        // the rolled file is left as it was
+        let rolled = std::fs::read_to_string(dir.join("cho.1.txt")).unwrap();
+        assert_eq!(rolled, ">> The first line, which fills the file\n");
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.contains(">> The second line\n"));
+        assert!(log.contains(">> Failed to compress rolled log"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,14 +1,170 @@
 use super::*;
+use std::cmp::Reverse;
 
+#[derive(Clone)]
 struct SymbolicStore {
     site_has_asset: HashSet<(SiteId, AssetId)>,
-    someone_has_asset: HashSet<AssetId>,
+    holders: HashMap<AssetId, Vec<SiteId>>, // index of `site_has_asset` by asset. No empty entries
+    consumed: HashSet<AssetId>,
 }
 struct SymbolicProgress<'a> {
     computes_todo: Vec<&'a ComputeArgs>,
+    chain_weights: HashMap<&'a ComputeArgs, u64>,
+}
+/// State of planning, as computes are symbolically executed one by one
+#[derive(Clone)]
+pub(crate) struct PlanState {
+    symbolic_store: SymbolicStore,
+    // Assets are routed along links, relayed by intermediate sites as necessary
+    links: Links,
+    // (destination site, asset) pairs for which transfers were already planned
+    routed: HashSet<(SiteId, AssetId)>,
+    // How many sends were planned for each site so far, to spread the load of popular assets
+    sends_planned: HashMap<SiteId, usize>,
+    // How many computes were planned for each site so far, to guide the choice of compute sites
+    computes_planned: HashMap<SiteId, usize>,
+    // Outputs which their compute sites drop once sent on to their result sites
+    dropped: HashSet<(SiteId, AssetId)>,
+    // Intermediate assets introduced by planning (e.g., partial reductions) get fresh IDs
+    next_asset_id: u32,
+    instructions: HashMap<SiteId, Vec<Instruction>>,
+    // The same instructions in dependency order, where each compute follows its transfers
+    sequence: Vec<(SiteId, Instruction)>,
+}
+/// Which sites can send to which, directly or via relays. See `Problem::links`.
+#[derive(Clone)]
+struct Links {
+    successors: Option<HashMap<SiteId, Vec<SiteId>>>, // None: all pairs are linked
 }
 //////////////////
 
+impl Links {
+    fn new(problem: &Problem) -> Self {
+        let successors = problem.links.as_ref().map(|links| {
+            let mut successors = HashMap::<SiteId, Vec<SiteId>>::default();
+            for &(from, to) in links.iter() {
+                successors.entry(from).or_default().push(to);
+            }
+            successors
+        });
+        Self { successors }
+    }
+    /// A shortest path of sites from `from` to `to`, including both, if there is any
+    fn path(&self, from: SiteId, to: SiteId) -> Option<Vec<SiteId>> {
+        let successors = match &self.successors {
+            None if from == to => return Some(vec![from]),
+            None => return Some(vec![from, to]),
+            Some(successors) => successors,
+        };
+        // Breadth-first search, remembering how each site was first reached
+        let mut predecessors = HashMap::<SiteId, SiteId>::default();
+        let mut frontier = std::collections::VecDeque::from(vec![from]);
+        while let Some(site_id) = frontier.pop_front() {
+            if site_id == to {
+                let mut path = vec![to];
+                while let Some(&predecessor) = predecessors.get(path.last().unwrap()) {
+                    path.push(predecessor);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &next in successors.get(&site_id).into_iter().flatten() {
+                if next != from && !predecessors.contains_key(&next) {
+                    predecessors.insert(next, site_id);
+                    frontier.push_back(next);
+                }
+            }
+        }
+        None
+    }
+    fn reaches(&self, from: SiteId, to: SiteId) -> bool {
+        self.successors.is_none() || self.path(from, to).is_some()
+    }
+}
+
+/// Check upfront that each compute has an eligible site reachable from some potential source of
+/// each of its needed assets, i.e., a site initially holding it, or eligible to compute it.
+fn check_reachability<'a>(problem: &'a Problem, links: &Links) -> Result<(), PlanError<'a>> {
+    if links.successors.is_none() {
+        return Ok(());
+    }
+    let sources = asset_sources(problem);
+    for compute_args in problem.do_compute.iter() {
+        let mut unreachable = None;
+        'choices: for compute_asset in compute_args.compute_assets() {
+            for to in eligible_sites(problem, compute_args, compute_asset) {
+                // Assets without any source are reported by planning itself
+                let unreachable_here =
+                    compute_args.inputs.iter().chain(Some(compute_asset)).find_map(|asset_id| {
+                        let from = sources.get(asset_id)?;
+                        if from.iter().any(|&from| links.reaches(from, to)) {
+                            None
+                        } else {
+                            Some((*asset_id, *from.iter().next()?, to))
+                        }
+                    });
+                match unreachable_here {
+                    None => {
+                        unreachable = None;
+                        break 'choices;
+                    }
+                    Some(found) => {
+                        unreachable.get_or_insert(found);
+                    }
+                }
+            }
+        }
+        if let Some((asset_id, from, to)) = unreachable {
+            return Err(PlanError::Unreachable {
+                asset_id,
+                from: Box::new(from),
+                to: Box::new(to),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Sites permitted to do the given compute with the given compute asset, in order
+fn eligible_sites(
+    problem: &Problem,
+    compute_args: &ComputeArgs,
+    compute_asset: &AssetId,
+) -> Vec<SiteId> {
+    let mut sites: Vec<SiteId> = problem
+        .may_compute
+        .iter()
+        .filter_map(asset_filter_mapper(compute_asset))
+        .filter(|site_id| {
+            compute_args
+                .inputs
+                .iter()
+                .chain(Some(compute_asset))
+                .all(|asset_id| problem.may_access.contains(&(*site_id, *asset_id)))
+        })
+        .collect();
+    sites.sort();
+    sites
+}
+
+/// The potential sources of each asset: sites initially holding it, or eligible to compute it
+fn asset_sources(problem: &Problem) -> HashMap<AssetId, BTreeSet<SiteId>> {
+    let mut sources = HashMap::<AssetId, BTreeSet<SiteId>>::default();
+    for &(site_id, asset_id) in problem.site_has_asset.iter() {
+        sources.entry(asset_id).or_default().insert(site_id);
+    }
+    for compute_args in problem.do_compute.iter() {
+        for compute_asset in compute_args.compute_assets() {
+            for site_id in eligible_sites(problem, compute_args, compute_asset) {
+                for &output in compute_args.outputs.iter() {
+                    sources.entry(output).or_default().insert(site_id);
+                }
+            }
+        }
+    }
+    sources
+}
+
 fn asset_filter_mapper(
     filter_asset: &AssetId,
 ) -> impl Fn(&(SiteId, AssetId)) -> Option<SiteId> + '_ {
@@ -21,126 +177,1954 @@ fn asset_filter_mapper(
     }
 }
 
-fn site_for_compute(problem: &Problem, compute_args: &ComputeArgs) -> Option<SiteId> {
-    // assuming all-pairs site reachability. A site is eligible to compute iff...
-    // ... (a) it is permitted to use the given asset as compute, and ...
-    let sites_that_may_compute =
-        problem.may_compute.iter().filter_map(asset_filter_mapper(&compute_args.compute_asset));
-    // ... (b) it is permitted to access all needed assets.
-    let mut sites_that_may_also_access = sites_that_may_compute.filter(|site_id| {
-        compute_args
-            .needed_assets()
-            .all(|needed_asset| problem.may_access.contains(&(*site_id, *needed_asset)))
-    });
-    // We select the first satisfactory site
-    sites_that_may_also_access.next()
+/// Ordering of a site's suitability to compute. Higher is better.
+type SiteScore = ((usize, usize), Reverse<SiteId>);
+
+/// Selects the site to do the given compute, and which of its acceptable compute assets to use
+fn site_for_compute(
+    problem: &Problem,
+    store: &SymbolicStore,
+    compute_args: &ComputeArgs,
+    links: &Links,
+    strategy: PlanStrategy,
+    computes_planned: &HashMap<SiteId, usize>,
+    stats: &mut PlanStats,
+) -> Option<(SiteId, ComputeArgs)> {
+    let mut best: Option<(SiteScore, SiteId, ComputeArgs)> = None;
+    // Any acceptable compute asset will do, so long as some site has it
+    for compute_asset in compute_args.compute_assets() {
+        if !store.someone_has(compute_asset) {
+            continue;
+        }
+        let chosen = compute_args.with_compute_asset(*compute_asset);
+        // A site is eligible to compute iff...
+        // ... (a) it is permitted to use the given asset as compute, and ...
+        let sites_that_may_compute =
+            problem.may_compute.iter().filter_map(asset_filter_mapper(compute_asset));
+        // ... (b) it is permitted to access all needed assets, and ...
+        let sites_that_may_also_access = sites_that_may_compute.filter(|site_id| {
+            chosen
+                .needed_assets()
+                .all(|needed_asset| problem.may_access.contains(&(*site_id, *needed_asset)))
+        });
+        // ... (c) some holder of each needed asset can reach it, perhaps via relays, and ...
+        let sites_that_may_also_access = sites_that_may_also_access.filter(|&site_id| {
+            chosen.needed_assets().all(|needed_asset| {
+                store.holders(needed_asset).iter().any(|&holder| links.reaches(holder, site_id))
+            })
+        });
+        // ... (d) it holds those needed assets which are immovable, and ...
+        let sites_that_may_also_access = sites_that_may_also_access.filter(|&site_id| {
+            chosen.needed_assets().all(|needed_asset| {
+                !problem.immovable.contains(needed_asset)
+                    || store.site_has_asset.contains(&(site_id, *needed_asset))
+            })
+        });
+        // ... (e) it may access, and be routed, at least the minimum of optional inputs.
+        let sites_that_may_also_access = sites_that_may_also_access.filter(|&site_id| {
+            let candidates = chosen.optional_candidates(store.holders.keys().copied());
+            let routable = candidates.iter().filter(|optional_input| {
+                let holders = store.holders(optional_input);
+                problem.may_access.contains(&(site_id, **optional_input))
+                    && if problem.immovable.contains(optional_input) {
+                        holders.contains(&site_id)
+                    } else {
+                        holders.iter().any(|&holder| links.reaches(holder, site_id))
+                    }
+            });
+            routable.count() >= chosen.min_optional_inputs
+        });
+        // We select the satisfactory site scoring highest by the strategy. Holding more needed
+        // assets minimizes transfers. Asset sizes are unknown to planning, so all count the same.
+        stats.site_selection_scans += problem.may_compute.len();
+        let sole_intermediates: Vec<AssetId> = match strategy {
+            PlanStrategy::Colocate => chosen
+                .needed_assets()
+                .copied()
+                .filter(|&asset_id| is_sole_intermediate(problem, asset_id))
+                .collect(),
+            _ => vec![],
+        };
+        for site_id in sites_that_may_also_access {
+            let held_count = chosen
+                .needed_assets()
+                .filter(|asset_id| store.site_has_asset.contains(&(site_id, **asset_id)))
+                .count();
+            let computes = computes_planned.get(&site_id).copied().unwrap_or(0);
+            let score = match strategy {
+                PlanStrategy::Balance => (held_count, usize::MAX - computes),
+                PlanStrategy::Consolidate => ((computes > 0) as usize, held_count),
+                PlanStrategy::FirstFit => (0, 0),
+                PlanStrategy::Colocate => {
+                    let colocated = sole_intermediates
+                        .iter()
+                        .filter(|asset_id| store.site_has_asset.contains(&(site_id, **asset_id)))
+                        .count();
+                    (colocated, held_count)
+                }
+            };
+            let score = (score, Reverse(site_id));
+            if best.as_ref().map(|(best_score, ..)| score > *best_score).unwrap_or(true) {
+                best = Some((score, site_id, chosen.clone()));
+            }
+        }
+    }
+    best.map(|(_held_count, site_id, chosen)| (site_id, chosen))
 }
 
-impl<'a> SymbolicStore {
-    fn with_assets(site_has_asset: &HashSet<(SiteId, AssetId)>) -> Self {
-        Self {
-            someone_has_asset: site_has_asset
+/// Whether the given asset is output by a compute, and needed by exactly one compute
+fn is_sole_intermediate(problem: &Problem, asset_id: AssetId) -> bool {
+    let produced =
+        problem.do_compute.iter().any(|compute_args| compute_args.outputs.contains(&asset_id));
+    let consumers = problem
+        .do_compute
+        .iter()
+        .filter(|compute_args| compute_args.needed_assets().any(|needed| *needed == asset_id))
+        .count();
+    produced && consumers == 1
+}
+
+/// Plans partial computes of a reducible compute at the sites which hold several of its inputs,
+/// such that only the partial results must be routed to `compute_site` rather than the inputs.
+/// Only sites which already hold the compute asset pre-reduce, to avoid routing it around.
+/// Returns the partial computes with their sites, and the compute combining their results.
+fn pre_reduce(
+    problem: &Problem,
+    store: &mut SymbolicStore,
+    compute_args: &ComputeArgs,
+    compute_site: SiteId,
+    links: &Links,
+    next_asset_id: &mut u32,
+) -> (Vec<(SiteId, ComputeArgs)>, ComputeArgs) {
+    let mut partials = vec![];
+    let mut remaining_inputs = compute_args.inputs.clone();
+    let mut combine_inputs = vec![];
+    if compute_args.reducible && !compute_args.consume_inputs && compute_args.outputs.len() == 1 {
+        loop {
+            // Find the site which could pre-reduce the most inputs not already at the compute site
+            let f = compute_args.compute_asset;
+            let best = store
+                .holders(&f)
                 .iter()
-                .map(|(_site_id, asset_id)| *asset_id)
-                .collect(),
-            site_has_asset: site_has_asset.clone(),
+                .copied()
+                .filter(|&site_id| {
+                    site_id != compute_site
+                        && problem.may_compute.contains(&(site_id, f))
+                        && problem.may_access.contains(&(site_id, f))
+                        && links.reaches(site_id, compute_site)
+                })
+                .map(|site_id| {
+                    let held: Vec<AssetId> = remaining_inputs
+                        .iter()
+                        .copied()
+                        .filter(|&asset_id| {
+                            store.site_has_asset.contains(&(site_id, asset_id))
+                                && !store.site_has_asset.contains(&(compute_site, asset_id))
+                                && problem.may_access.contains(&(site_id, asset_id))
+                        })
+                        .collect();
+                    (site_id, held)
+                })
+                .max_by_key(|(site_id, held)| (held.len(), std::cmp::Reverse(*site_id)));
+            match best {
+                // Pre-reducing a single input would not save any transfers
+                Some((site_id, held)) if held.len() >= 2 => {
+                    let partial_asset = AssetId(*next_asset_id);
+                    *next_asset_id += 1;
+                    remaining_inputs.retain(|asset_id| !held.contains(asset_id));
+                    store.insert(site_id, partial_asset);
+                    combine_inputs.push(partial_asset);
+                    let partial = ComputeArgs {
+                        inputs: held,
+                        optional_inputs: vec![],
+                        min_optional_inputs: 0,
+                        input_range: None,
+                        outputs: vec![partial_asset],
+                        compute_asset: f,
+                        alt_compute_assets: vec![],
+                        consume_inputs: false,
+                        reducible: false,
+                        weight: compute_args.weight,
+                        label: compute_args
+                            .label
+                            .as_ref()
+                            .map(|label| format!("{} (partial)", label)),
+                        result_site: None,
+                    };
+                    partials.push((site_id, partial));
+                }
+                _ => break,
+            }
+        }
+    }
+    combine_inputs.extend(remaining_inputs);
+    let combine = ComputeArgs { inputs: combine_inputs, reducible: false, ..compute_args.clone() };
+    (partials, combine)
+}
+
+impl SymbolicStore {
+    fn with_assets(site_has_asset: &HashSet<(SiteId, AssetId)>) -> Self {
+        let mut store = Self {
+            site_has_asset: Default::default(),
+            holders: Default::default(),
+            consumed: Default::default(),
+        };
+        for &(site_id, asset_id) in site_has_asset.iter() {
+            store.insert(site_id, asset_id);
         }
+        store
     }
     fn insert(&mut self, site_id: SiteId, asset_id: AssetId) {
-        self.site_has_asset.insert((site_id, asset_id));
-        self.someone_has_asset.insert(asset_id);
+        if self.site_has_asset.insert((site_id, asset_id)) {
+            self.holders.entry(asset_id).or_default().push(site_id);
+        }
+    }
+    fn consume(&mut self, asset_id: AssetId) {
+        for site_id in self.holders.remove(&asset_id).unwrap_or_default() {
+            self.site_has_asset.remove(&(site_id, asset_id));
+        }
+        self.consumed.insert(asset_id);
+    }
+    /// Whether some site has each output of the given compute already, e.g., cached from an
+    /// earlier run, such that the compute need not be repeated. Consuming computes are repeated.
+    fn has_outputs_of(&self, compute_args: &ComputeArgs) -> bool {
+        !compute_args.consume_inputs
+            && !compute_args.outputs.is_empty()
+            && compute_args.outputs.iter().all(|asset_id| self.someone_has(asset_id))
+    }
+    /// Whether some site has all the given assets. True if there are none.
+    fn holds_together<'a>(&self, mut assets: impl Iterator<Item = &'a AssetId>) -> bool {
+        match assets.next() {
+            Some(first) => {
+                let assets: Vec<&AssetId> = assets.collect();
+                self.holders(first).iter().any(|&site_id| {
+                    assets
+                        .iter()
+                        .all(|asset_id| self.site_has_asset.contains(&(site_id, **asset_id)))
+                })
+            }
+            None => true,
+        }
+    }
+    fn someone_has(&self, asset_id: &AssetId) -> bool {
+        self.holders.contains_key(asset_id)
+    }
+    /// The sites which have the given asset, in the order they got it
+    fn holders(&self, asset_id: &AssetId) -> &[SiteId] {
+        self.holders.get(asset_id).map(Vec::as_slice).unwrap_or(&[])
     }
 }
 impl<'a> SymbolicProgress<'a> {
-    fn with_compute_to_do(iter: impl Iterator<Item = &'a ComputeArgs>) -> Self {
-        Self { computes_todo: iter.collect() }
+    fn with_compute_to_do(problem: &'a Problem) -> Self {
+        Self {
+            computes_todo: problem.do_compute.iter().collect(),
+            chain_weights: chain_costs(problem, |compute_args| compute_args.weight),
+        }
     }
     fn take_feasible_compute<'b>(
         &'b mut self,
         store: &'b SymbolicStore,
+        stats: &mut PlanStats,
     ) -> Result<&'a ComputeArgs, Option<&'a ComputeArgs>> {
-        // "feasible" means that all input assets are available
+        stats.feasibility_checks += self.computes_todo.len();
+        // "feasible" means that all input assets (and the minimum of optional inputs) are available, and
+        // consuming them does not deprive any other remaining compute.
+        // Of these, the compute heading the heaviest chain of computes is started first.
+        // Computes whose outputs already exist are trivially feasible, as they are not repeated.
+        let feasible = (0..self.computes_todo.len()).filter(|&i| {
+            let compute_args = self.computes_todo[i];
+            store.has_outputs_of(compute_args)
+                || compute_args.inputs.iter().all(|asset_id| store.someone_has(asset_id))
+                    && compute_args.compute_assets().any(|asset_id| store.someone_has(asset_id))
+                    && compute_args
+                        .optional_candidates(store.holders.keys().copied())
+                        .iter()
+                        .filter(|a| store.someone_has(a))
+                        .count()
+                        >= compute_args.min_optional_inputs
+                    && !(compute_args.consume_inputs && self.deprives_others(i))
+        });
+        let heaviest =
+            feasible.min_by_key(|&i| std::cmp::Reverse(self.chain_weights[self.computes_todo[i]]));
+        match heaviest {
+            Some(i) => Ok(self.computes_todo.remove(i)),
+            None => Err(self.computes_todo.iter().copied().next()),
+        }
+    }
+    /// Would consuming the inputs of the compute at index `i` deprive another remaining compute?
+    fn deprives_others(&self, i: usize) -> bool {
+        let inputs = &self.computes_todo[i].inputs;
+        self.computes_todo.iter().enumerate().any(|(j, other)| {
+            j != i && other.needed_assets().any(|asset_id| inputs.contains(asset_id))
+        })
+    }
+    /// Find a remaining compute needing an asset which is (or must be) consumed by another
+    fn consumption_conflict(&self, store: &SymbolicStore) -> Option<(&'a ComputeArgs, AssetId)> {
         for (i, compute_args) in self.computes_todo.iter().enumerate() {
-            if compute_args
-                .needed_assets()
-                .all(|asset_id| store.someone_has_asset.contains(asset_id))
-            {
-                return Ok(self.computes_todo.remove(i));
+            for &asset_id in compute_args.needed_assets() {
+                let consumed_by_other = self.computes_todo.iter().enumerate().any(|(j, other)| {
+                    j != i && other.consume_inputs && other.inputs.contains(&asset_id)
+                });
+                if consumed_by_other || store.consumed.contains(&asset_id) {
+                    return Some((compute_args, asset_id));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// For each compute, by index in `problem.do_compute`, the indices of the others needing its outputs
+fn dependents(problem: &Problem) -> Vec<Vec<usize>> {
+    let mut producers = HashMap::<AssetId, Vec<usize>>::default();
+    for (i, compute_args) in problem.do_compute.iter().enumerate() {
+        for &output in compute_args.outputs.iter() {
+            producers.entry(output).or_default().push(i);
+        }
+    }
+    let mut dependents = vec![vec![]; problem.do_compute.len()];
+    for (j, compute_args) in problem.do_compute.iter().enumerate() {
+        for asset_id in compute_args.needed_assets() {
+            for &i in producers.get(asset_id).into_iter().flatten().filter(|&&i| i != j) {
+                dependents[i].push(j);
             }
         }
-        Err(self.computes_todo.iter().copied().next())
     }
+    for dependents in dependents.iter_mut() {
+        dependents.sort_unstable();
+        dependents.dedup();
+    }
+    dependents
+}
+
+/// Indices of computes, each before its dependents. Computes on (or after) cycles are appended
+/// in index order. Cyclic problems fail planning anyway.
+fn topological_order(dependents: &[Vec<usize>]) -> Vec<usize> {
+    let mut in_degree = vec![0usize; dependents.len()];
+    for &j in dependents.iter().flatten() {
+        in_degree[j] += 1;
+    }
+    let mut order: Vec<usize> = (0..dependents.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut next = 0;
+    while let Some(&i) = order.get(next) {
+        next += 1;
+        for &j in dependents[i].iter() {
+            in_degree[j] -= 1;
+            if in_degree[j] == 0 {
+                order.push(j);
+            }
+        }
+    }
+    if order.len() < dependents.len() {
+        order.extend((0..dependents.len()).filter(|&i| in_degree[i] > 0));
+    }
+    order
+}
+
+/// For each compute, by index, the greatest total `cost` of any chain of computes depending on
+/// it (including itself)
+fn indexed_chain_costs(
+    problem: &Problem,
+    dependents: &[Vec<usize>],
+    cost: impl Fn(&ComputeArgs) -> u64,
+) -> Vec<u64> {
+    let mut chain_costs = vec![0u64; dependents.len()];
+    // Dependents are costed before the computes they depend on
+    for &i in topological_order(dependents).iter().rev() {
+        let rest = dependents[i].iter().map(|&j| chain_costs[j]).max().unwrap_or(0);
+        chain_costs[i] = cost(&problem.do_compute[i]).saturating_add(rest);
+    }
+    chain_costs
+}
+
+/// For each compute, the greatest total `cost` of any chain of computes depending on it (including itself)
+fn chain_costs(
+    problem: &Problem,
+    cost: impl Fn(&ComputeArgs) -> u64,
+) -> HashMap<&ComputeArgs, u64> {
+    let indexed = indexed_chain_costs(problem, &dependents(problem), cost);
+    let mut chain_costs = HashMap::<&ComputeArgs, u64>::default();
+    for (compute_args, chain_cost) in problem.do_compute.iter().zip(indexed) {
+        let entry = chain_costs.entry(compute_args).or_default();
+        *entry = (*entry).max(chain_cost);
+    }
+    chain_costs
+}
+
+/// Prioritizes each compute by the length of the longest chain of computes depending on it
+/// (including itself), such that transfers on the critical path are the most urgent.
+fn critical_path_priorities(problem: &Problem) -> HashMap<&ComputeArgs, u8> {
+    chain_costs(problem, |_| 1)
+        .into_iter()
+        .map(|(compute_args, len)| (compute_args, len.min(u8::MAX as u64) as u8))
+        .collect()
+}
+
+/// The chain of dependent computes with the greatest total `weight`, and that total.
+/// This estimates the makespan of the problem, ignoring the time spent on transfers.
+pub fn critical_path(problem: &Problem) -> (u64, Vec<&ComputeArgs>) {
+    let dependents = dependents(problem);
+    let chain_weights =
+        indexed_chain_costs(problem, &dependents, |compute_args| compute_args.weight);
+    let mut path: Vec<&ComputeArgs> = vec![];
+    let mut on_path = vec![false; dependents.len()];
+    let mut next = (0..dependents.len()).max_by_key(|&i| chain_weights[i]);
+    let makespan = next.map(|i| chain_weights[i]).unwrap_or(0);
+    while let Some(i) = next {
+        path.push(&problem.do_compute[i]);
+        on_path[i] = true;
+        // Continue along the dependent which accounts for the rest of this chain's weight
+        let weight = problem.do_compute[i].weight;
+        next = dependents[i]
+            .iter()
+            .copied()
+            .find(|&j| !on_path[j] && chain_weights[j].saturating_add(weight) == chain_weights[i]);
+    }
+    (makespan, path)
 }
 
 /// Compute a set of instructions to plan for a set of sites, for the given problem
-pub(crate) fn plan<'a>(
+pub fn plan<'a>(problem: &'a Problem) -> Result<HashMap<SiteId, Vec<Instruction>>, PlanError<'a>> {
+    plan_traced(problem, |_| {}).0
+}
+
+/// As `plan`, but returning the instructions as one sequence of (site, instruction) pairs, ordered
+/// such that completing them one by one completes each: every asset is sent to a site before
+/// the site acquires it or computes with it. E.g., for single-threaded simulation.
+pub fn plan_sequence<'a>(
+    problem: &'a Problem,
+) -> Result<Vec<(SiteId, Instruction)>, PlanError<'a>> {
+    let (result, trace) = plan_traced(problem, |_| {});
+    result.map(|_| trace.sequence)
+}
+
+/// As `plan`, but also returns the predicted final placement of assets at sites once the plan is executed.
+/// Consumed assets are not predicted to be anywhere.
+pub fn plan_with_placement<'a>(
+    problem: &'a Problem,
+) -> Result<(HashMap<SiteId, Vec<Instruction>>, Placement), PlanError<'a>> {
+    let (result, trace) = plan_traced(problem, |_| {});
+    result.map(|instructions| (instructions, trace.final_placement))
+}
+
+/// As `plan`, but also invokes `on_step` for each symbolically-executed compute, in order.
+/// Returns the trace of these steps alongside the result, e.g., to inspect the partial schedule on error.
+pub fn plan_traced<'a>(
+    problem: &'a Problem,
+    on_step: impl FnMut(&PlanTraceStep<'a>),
+) -> (Result<HashMap<SiteId, Vec<Instruction>>, PlanError<'a>>, PlanTrace<'a>) {
+    plan_with_strategy(problem, PlanStrategy::default(), on_step)
+}
+
+/// As `plan_traced`, but choosing compute sites with the given strategy, recorded in the trace
+pub fn plan_with_strategy<'a>(
+    problem: &'a Problem,
+    strategy: PlanStrategy,
+    mut on_step: impl FnMut(&PlanTraceStep<'a>),
+) -> (Result<HashMap<SiteId, Vec<Instruction>>, PlanError<'a>>, PlanTrace<'a>) {
+    let mut trace = PlanTrace { strategy, ..Default::default() };
+    let result = plan_into_trace(problem, &mut trace, &mut on_step);
+    (result, trace)
+}
+
+fn plan_into_trace<'a>(
     problem: &'a Problem,
+    trace: &mut PlanTrace<'a>,
+    on_step: &mut dyn FnMut(&PlanTraceStep<'a>),
 ) -> Result<HashMap<SiteId, Vec<Instruction>>, PlanError<'a>> {
-    // `instructions` is incrementally populated before being ultimately returned.
-    // We symbolically execute
-    let mut instructions = HashMap::<SiteId, Vec<Instruction>>::default();
-    let mut push_instruction = |site_id: SiteId, ins: Instruction| {
-        instructions.entry(site_id).or_insert_with(Default::default).push(ins);
-    };
-    // Our symbolic execution starts with an initial state where...
-    // ... sites' initial asset storage is given by the problem spec, and
-    let mut symbolic_store = SymbolicStore::with_assets(&problem.site_has_asset);
+    // We symbolically execute the computes, planning instructions for each in turn.
+    let mut state = PlanState::new(problem);
+    if problem
+        .do_compute
+        .iter()
+        .all(|compute_args| state.symbolic_store.has_outputs_of(compute_args))
+    {
+        // Nothing to do, e.g., for a warm start. Skip the analyses needed only to plan computes.
+        trace.reused_outputs.extend(problem.do_compute.iter());
+        trace.final_placement = state.symbolic_store.site_has_asset;
+        return Ok(Default::default());
+    }
+    let priorities = critical_path_priorities(problem);
     // ... all compute tasks in the problem spec remain to be done.
-    let mut symbolic_progress = SymbolicProgress::with_compute_to_do(problem.do_compute.iter());
+    let mut symbolic_progress = SymbolicProgress::with_compute_to_do(problem);
+    trace.estimated_makespan = critical_path(problem).0;
+    check_reachability(problem, &state.links)?;
     loop {
         // Select the next compute task to do
-        match symbolic_progress.take_feasible_compute(&symbolic_store) {
+        match symbolic_progress.take_feasible_compute(&state.symbolic_store, &mut trace.stats) {
             Err(remaining_compute) => {
                 // Stop! There is no more progress possible because...
                 return match remaining_compute {
-                    None => Ok(instructions), // ... we completed all the compute steps
+                    None => {
+                        // ... we completed all the compute steps
+                        let dropped = &state.dropped;
+                        trace.final_placement = state.symbolic_store.site_has_asset;
+                        trace.final_placement.retain(|pair| !dropped.contains(pair));
+                        trace.sequence = state.sequence;
+                        Ok(state.instructions)
+                    }
                     Some(remaining_compute) => {
-                        // ... we found an example of a compute task we cannot complete
-                        Err(PlanError::CyclicCausality(remaining_compute))
+                        // ... we found an example of a compute task we cannot complete, because...
+                        Err(match symbolic_progress.consumption_conflict(&state.symbolic_store) {
+                            // ... another compute consumes its input, or
+                            Some((compute_args, asset_id)) => {
+                                PlanError::ConsumedInput { compute_args, asset_id }
+                            }
+                            // ... it depends on its own outputs.
+                            None => PlanError::CyclicCausality(remaining_compute),
+                        })
                     }
                 };
             }
             Ok(next_compute) => {
-                // Symbolically execute `next_compute`.
-                // Find a feasible site to complete the computation instruction
-                let compute_site = site_for_compute(problem, next_compute)
-                    .ok_or(PlanError::NoSiteForCompute(next_compute))?;
-                push_instruction(compute_site, Instruction::ComputeAssetData(next_compute.clone()));
-                // Route the instruction's input assets to `compute_site` as necessary.
-                for needed_asset in next_compute.needed_assets() {
-                    if symbolic_store.site_has_asset.contains(&(compute_site, *needed_asset)) {
-                        // This asset is already present at the compute site.
-                        continue;
-                    }
-                    // The compute site DOES NOT have this needed asset yet!
-                    // Find a site that does have the asset already
-                    // (`take_feasible_compute` ensures such a site must exist).
-                    let having_site = symbolic_store
-                        .site_has_asset
+                let step =
+                    state.plan_compute(problem, next_compute, priorities[next_compute], trace)?;
+                if let Some(step) = step {
+                    on_step(&step);
+                    trace.steps.push(step);
+                }
+            }
+        }
+    }
+}
+
+impl PlanState {
+    fn new(problem: &Problem) -> Self {
+        Self {
+            // Our symbolic execution starts with an initial state where sites' initial asset
+            // storage is given by the problem spec.
+            symbolic_store: SymbolicStore::with_assets(&problem.site_has_asset),
+            links: Links::new(problem),
+            routed: Default::default(),
+            sends_planned: Default::default(),
+            computes_planned: Default::default(),
+            dropped: Default::default(),
+            next_asset_id: problem.assets().last().map(|asset_id| asset_id.0 + 1).unwrap_or(0),
+            instructions: Default::default(),
+            sequence: Default::default(),
+        }
+    }
+    fn push_instruction(&mut self, site_id: SiteId, ins: Instruction) {
+        self.instructions.entry(site_id).or_default().push(ins);
+    }
+    /// Plan the transfers of `asset_id` along each hop of `path`. Usually there is just one.
+    fn push_transfers(&mut self, path: &[SiteId], asset_id: AssetId, priority: u8) {
+        let dest = path[path.len() - 1];
+        for hop in path.windows(2) {
+            let (from, to) = (hop[0], hop[1]);
+            if to != dest && !self.routed.insert((to, asset_id)) {
+                // This relay is already planned to have the asset.
+                continue;
+            }
+            self.symbolic_store.insert(to, asset_id);
+            *self.sends_planned.entry(from).or_insert(0) += 1;
+            // Tell sender and receiver sites to send and receive respectively.
+            // (Including either of these would suffice)
+            let send = Instruction::SendAssetTo { asset_id, site_id: to, priority };
+            let acquire =
+                Instruction::AcquireAssetFrom { asset_id, site_id: from, priority, timeout: None };
+            self.sequence.push((from, send.clone()));
+            self.sequence.push((to, acquire.clone()));
+            self.push_instruction(from, send);
+            self.push_instruction(to, acquire);
+        }
+    }
+    /// Symbolically execute `next_compute`, which must be feasible, planning its instructions.
+    /// Returns None if its outputs exist already, such that it needs no instructions.
+    fn plan_compute<'a>(
+        &mut self,
+        problem: &'a Problem,
+        next_compute: &'a ComputeArgs,
+        priority: u8,
+        trace: &mut PlanTrace<'a>,
+    ) -> Result<Option<PlanTraceStep<'a>>, PlanError<'a>> {
+        if self.symbolic_store.has_outputs_of(next_compute) {
+            // Dependents are routed the existing outputs instead
+            trace.reused_outputs.push(next_compute);
+            return Ok(None);
+        }
+        if let Some(result_site) = next_compute.result_site {
+            let denied = next_compute
+                .outputs
+                .iter()
+                .find(|&&asset_id| !problem.may_access.contains(&(result_site, asset_id)));
+            if let Some(&asset_id) = denied {
+                return Err(PlanError::ResultSiteDenied { compute_args: next_compute, asset_id });
+            }
+        }
+        // Find a feasible site (and compute asset) to complete the computation instruction
+        let (compute_site, chosen) = site_for_compute(
+            problem,
+            &self.symbolic_store,
+            next_compute,
+            &self.links,
+            trace.strategy,
+            &self.computes_planned,
+            &mut trace.stats,
+        )
+        .ok_or_else(|| {
+            if self.symbolic_store.holds_together(
+                next_compute
+                    .needed_assets()
+                    .filter(|asset_id| problem.immovable.contains(asset_id)),
+            ) {
+                PlanError::NoSiteForCompute(next_compute)
+            } else {
+                PlanError::ImmovableInputsApart(next_compute)
+            }
+        })?;
+        let (partials, combine) = pre_reduce(
+            problem,
+            &mut self.symbolic_store,
+            &chosen,
+            compute_site,
+            &self.links,
+            &mut self.next_asset_id,
+        );
+        for (partial_site, partial) in partials {
+            *self.computes_planned.entry(partial_site).or_insert(0) += 1;
+            self.sequence.push((partial_site, Instruction::ComputeAssetData(partial.clone())));
+            self.push_instruction(partial_site, Instruction::ComputeAssetData(partial));
+        }
+        *self.computes_planned.entry(compute_site).or_insert(0) += 1;
+        self.push_instruction(compute_site, Instruction::ComputeAssetData(combine.clone()));
+        // Route the instruction's input assets to `compute_site` as necessary.
+        let mut routes = vec![];
+        // Optional inputs are routed too, if the compute site may access them.
+        // Immovable optional inputs are only passed if the compute site has them already
+        let optional_candidates =
+            combine.optional_candidates(self.symbolic_store.holders.keys().copied());
+        let optional_inputs = optional_candidates.iter().filter(|asset_id| {
+            problem.may_access.contains(&(compute_site, **asset_id))
+                && !problem.immovable.contains(asset_id)
+        });
+        let required = combine.needed_assets().map(|asset_id| (asset_id, true));
+        for (needed_asset, required) in
+            required.chain(optional_inputs.map(|asset_id| (asset_id, false)))
+        {
+            if self.symbolic_store.site_has_asset.contains(&(compute_site, *needed_asset)) {
+                // This asset is already present at the compute site.
+                continue;
+            }
+            // The compute site DOES NOT have this needed asset yet!
+            // Find the nearest, then least-loaded site that does have the asset already
+            // (`take_feasible_compute` and `site_for_compute` ensure such a site must exist,
+            // unless the asset is optional).
+            trace.stats.having_site_lookups += self.symbolic_store.holders(needed_asset).len();
+            let path = self
+                .symbolic_store
+                .holders(needed_asset)
+                .iter()
+                .filter_map(|&site_id| self.links.path(site_id, compute_site))
+                .min_by_key(|path| {
+                    let sends = self.sends_planned.get(&path[0]).copied().unwrap_or(0);
+                    (path.len(), sends, path[0])
+                });
+            let path = match path {
+                Some(path) => path,
+                None if !required => continue,
+                None => panic!("`compute_sequence` ensures SOME site has this asset!"),
+            };
+            let having_site = path[0];
+            self.symbolic_store.insert(compute_site, *needed_asset);
+            if !self.routed.insert((compute_site, *needed_asset)) {
+                // A transfer of this asset to this site was already planned. Don't repeat it.
+                trace.duplicate_routes.push((*needed_asset, compute_site));
+                continue;
+            }
+            routes.push((*needed_asset, having_site));
+            self.push_transfers(&path, *needed_asset, priority);
+        }
+        self.sequence.push((compute_site, Instruction::ComputeAssetData(combine)));
+        // Update our symbolic store of sites' assets.
+        for output_asset in next_compute.outputs.iter() {
+            self.symbolic_store.insert(compute_site, *output_asset);
+        }
+        match next_compute.result_site {
+            Some(result_site) if result_site != compute_site => {
+                for &output_asset in next_compute.outputs.iter() {
+                    let path = self.links.path(compute_site, result_site).ok_or_else(|| {
+                        PlanError::Unreachable {
+                            asset_id: output_asset,
+                            from: Box::new(compute_site),
+                            to: Box::new(result_site),
+                        }
+                    })?;
+                    if self.routed.insert((result_site, output_asset)) {
+                        self.push_transfers(&path, output_asset, priority);
+                    }
+                    self.dropped.insert((compute_site, output_asset));
+                }
+            }
+            _ => {}
+        }
+        if next_compute.consume_inputs {
+            for input_asset in next_compute.inputs.iter() {
+                self.symbolic_store.consume(*input_asset);
+            }
+        }
+        Ok(Some(PlanTraceStep { compute_args: next_compute, compute_site, routes }))
+    }
+}
+
+impl Planner {
+    /// Planner for the sites, assets, and permissions of `problem`, with no computes.
+    /// Any computes in `problem` are discarded; add them with `add_compute`.
+    pub fn new(mut problem: Problem) -> Self {
+        problem.do_compute.clear();
+        let state = PlanState::new(&problem);
+        Self { problem, accepted: 0, pending: vec![], state }
+    }
+    /// Plan the given compute, and any earlier-added computes it made feasible, returning the
+    /// instructions to dispatch in addition to those returned before, ordered as by `plan_sequence`.
+    /// A compute whose inputs are not yet available remains pending until they are.
+    /// Adding computes in the order in which `plan` plans them yields the instructions `plan`
+    /// returns for all of them, except for the priorities of transfers, and the IDs of partial
+    /// results, as these depend on the computes yet to be added.
+    /// Planning assigns fresh IDs to partial results of `reducible` computes, greater than the ID
+    /// of every asset added so far. Computes added later must not mention these.
+    /// On error, the compute is not added, and the planner is unchanged.
+    pub fn add_compute(
+        &mut self,
+        compute_args: ComputeArgs,
+    ) -> Result<Vec<(SiteId, Instruction)>, PlanError<'_>> {
+        let Self { problem, accepted, pending, state } = self;
+        // Forget the compute rejected by the previous call, if any
+        problem.do_compute.truncate(*accepted);
+        problem.do_compute.push(compute_args);
+        let problem = &*problem;
+        let added = problem.do_compute.len() - 1;
+        problem.validate().map_err(PlanError::Invalid)?;
+        check_reachability(problem, &state.links)?;
+        // Plan into a copy of the state, kept only if all the computes made feasible succeed
+        let mut scratch = state.clone();
+        let compute_args = &problem.do_compute[added];
+        let mentioned = compute_args
+            .needed_assets()
+            .chain(compute_args.optional_inputs.iter())
+            .chain(compute_args.alt_compute_assets.iter())
+            .chain(compute_args.outputs.iter());
+        if let Some(max_id) = mentioned.map(|asset_id| asset_id.0).max() {
+            scratch.next_asset_id = scratch.next_asset_id.max(max_id + 1);
+        }
+        let priorities = critical_path_priorities(problem);
+        let mut progress = SymbolicProgress {
+            computes_todo: pending
+                .iter()
+                .chain(Some(&added))
+                .map(|&i| &problem.do_compute[i])
+                .collect(),
+            chain_weights: chain_costs(problem, |compute_args| compute_args.weight),
+        };
+        let mut trace = PlanTrace::default();
+        while let Ok(next_compute) =
+            progress.take_feasible_compute(&scratch.symbolic_store, &mut trace.stats)
+        {
+            let priority = priorities[next_compute];
+            scratch.plan_compute(problem, next_compute, priority, &mut trace)?;
+        }
+        pending.push(added);
+        pending.retain(|&i| {
+            progress.computes_todo.iter().any(|todo| std::ptr::eq(*todo, &problem.do_compute[i]))
+        });
+        *accepted += 1;
+        let sequenced = state.sequence.len();
+        *state = scratch;
+        Ok(state.sequence[sequenced..].to_vec())
+    }
+    /// The added computes yet to be planned, as their inputs are not yet available
+    pub fn pending(&self) -> impl Iterator<Item = &ComputeArgs> + '_ {
+        self.pending.iter().map(move |&i| &self.problem.do_compute[i])
+    }
+    /// Every instruction planned so far, by site
+    pub fn instructions(&self) -> &HashMap<SiteId, Vec<Instruction>> {
+        &self.state.instructions
+    }
+}
+
+impl Failover {
+    /// Failover for sites executing the given instructions, planned for `problem`
+    pub fn new(problem: Problem, instructions: HashMap<SiteId, Vec<Instruction>>) -> Self {
+        Self { problem, instructions, failed: Default::default() }
+    }
+    /// Reassign the computes of the failed site to other eligible sites, and have the assets
+    /// it was to send sent by live sites instead. Returns the instructions to add to each live
+    /// site, e.g., with `SiteControl::add_instructions`. The failed site is assumed to have
+    /// computed nothing, so all its computes are repeated. Nothing is returned for sites which
+    /// failed already. On error, nothing is reassigned.
+    pub fn reassign(
+        &mut self,
+        failed: SiteId,
+    ) -> Result<HashMap<SiteId, Vec<Instruction>>, FailoverError> {
+        if !self.failed.insert(failed) {
+            return Ok(Default::default());
+        }
+        let result = self.reassignment(failed);
+        match &result {
+            Ok(added) => {
+                for (site_id, instructions) in added.iter() {
+                    self.instructions
+                        .entry(*site_id)
+                        .or_default()
+                        .extend(instructions.iter().cloned());
+                }
+            }
+            Err(_) => {
+                self.failed.remove(&failed);
+            }
+        }
+        result
+    }
+    fn reassignment(
+        &self,
+        failed: SiteId,
+    ) -> Result<HashMap<SiteId, Vec<Instruction>>, FailoverError> {
+        let Self { problem, instructions, failed: all_failed } = self;
+        let live = |site_id: &SiteId| !all_failed.contains(site_id);
+        let linked = |from: SiteId, to: SiteId| {
+            problem.links.as_ref().is_none_or(|links| links.contains(&(from, to)))
+        };
+        let orphaned = instructions.get(&failed).map(Vec::as_slice).unwrap_or_default();
+        let mut added = HashMap::<SiteId, Vec<Instruction>>::default();
+        // Whether the site is to hold the asset, initially, or once it computed it, or if
+        // `acquired` is set, once it acquired it from a live site
+        let holds = |added: &HashMap<SiteId, Vec<Instruction>>,
+                     site_id: SiteId,
+                     asset_id: AssetId,
+                     acquired: bool| {
+            let site_instructions =
+                instructions.get(&site_id).into_iter().chain(added.get(&site_id)).flatten();
+            problem.site_has_asset.contains(&(site_id, asset_id))
+                || site_instructions.clone().any(|ins| match ins {
+                    Instruction::AcquireAssetFrom { asset_id: a, site_id: from, .. } => {
+                        acquired && *a == asset_id && live(from)
+                    }
+                    Instruction::ComputeAssetData(compute_args) => {
+                        compute_args.outputs.contains(&asset_id)
+                    }
+                    _ => false,
+                })
+        };
+        let mut sites: Vec<SiteId> = problem.sites().into_iter().filter(live).collect();
+        sites.sort();
+        sites.dedup();
+        // Send the asset to `to` from the first live site to hold it, not counting relays, which
+        // may themselves await the asset from the failed site
+        let route = |added: &mut HashMap<SiteId, Vec<Instruction>>,
+                     asset_id: AssetId,
+                     to: SiteId,
+                     priority: u8| {
+            let from = sites
+                .iter()
+                .copied()
+                .find(|&from| from != to && linked(from, to) && holds(added, from, asset_id, false))
+                .ok_or(FailoverError::Lost { asset_id })?;
+            added.entry(from).or_default().push(Instruction::SendAssetTo {
+                asset_id,
+                site_id: to,
+                priority,
+            });
+            added.entry(to).or_default().push(Instruction::AcquireAssetFrom {
+                asset_id,
+                site_id: from,
+                priority,
+                timeout: None,
+            });
+            Ok(())
+        };
+        // Computes go to the eligible site missing the fewest of their needed assets.
+        // These are in planned order, so those producing the inputs of others come first.
+        for ins in orphaned {
+            let compute_args = match ins {
+                Instruction::ComputeAssetData(compute_args) => compute_args,
+                _ => continue,
+            };
+            let choices = compute_args.compute_assets().flat_map(|compute_asset| {
+                let compute_args = compute_args.with_compute_asset(*compute_asset);
+                sites
+                    .iter()
+                    .filter(|&&site_id| problem.may_compute.contains(&(site_id, *compute_asset)))
+                    .filter(|&&site_id| {
+                        compute_args
+                            .needed_assets()
+                            .all(|asset_id| problem.may_access.contains(&(site_id, *asset_id)))
+                    })
+                    .map(|&site_id| (site_id, compute_args.clone()))
+                    .collect::<Vec<_>>()
+            });
+            let (site_id, compute_args) = choices
+                .min_by_key(|(site_id, compute_args)| {
+                    let missing = compute_args
+                        .needed_assets()
+                        .filter(|asset_id| !holds(&added, *site_id, **asset_id, true))
+                        .count();
+                    (missing, *site_id)
+                })
+                .ok_or_else(|| FailoverError::NoSiteForCompute(Box::new(compute_args.clone())))?;
+            for &asset_id in compute_args.needed_assets() {
+                if !holds(&added, site_id, asset_id, true) {
+                    route(&mut added, asset_id, site_id, 0)?;
+                }
+            }
+            added.entry(site_id).or_default().push(Instruction::ComputeAssetData(compute_args));
+        }
+        // Sites awaiting assets from the failed site acquire them elsewhere
+        for &site_id in sites.iter() {
+            for ins in instructions.get(&site_id).into_iter().flatten() {
+                if let Instruction::AcquireAssetFrom { asset_id, site_id: from, priority, .. } = ins
+                {
+                    if *from == failed {
+                        route(&mut added, *asset_id, site_id, *priority)?;
+                    }
+                }
+            }
+        }
+        Ok(added)
+    }
+}
+
+/// Suggest permissions to add to `problem` such that `plan` no longer fails with
+/// `NoSiteForCompute`, e.g., to fix a problem's authoring mistakes. Each compute without a site
+/// is granted the fewest permissions which make some site eligible. Greedy, so the grants are
+/// minimal for each compute, but not necessarily overall. Returns no grants if planning already
+/// succeeds, and None if grants alone cannot make it succeed.
+pub fn suggest_grants(problem: &Problem) -> Option<Vec<Grant>> {
+    let mut problem = problem.clone();
+    let mut grants = vec![];
+    loop {
+        let missing = match plan(&problem) {
+            Ok(_) => return Some(grants),
+            Err(PlanError::NoSiteForCompute(compute_args)) => {
+                grants_to_place(&problem, compute_args)?
+            }
+            Err(_) => return None,
+        };
+        for grant in missing {
+            match grant {
+                Grant::MayAccess(site_id, asset_id) => {
+                    problem.may_access.insert((site_id, asset_id))
+                }
+                Grant::MayCompute(site_id, asset_id) => {
+                    problem.may_compute.insert((site_id, asset_id))
+                }
+            };
+            grants.push(grant);
+        }
+    }
+}
+
+/// The fewest grants which would let some site do the given compute. Only sites reachable from
+/// a potential source of each needed asset are candidates, as grants cannot conjure assets or
+/// links. None if no grants would help.
+fn grants_to_place(problem: &Problem, compute_args: &ComputeArgs) -> Option<Vec<Grant>> {
+    let links = Links::new(problem);
+    let sources = asset_sources(problem);
+    let mut best: Option<Vec<Grant>> = None;
+    for site_id in problem.sites() {
+        for &compute_asset in compute_args.compute_assets() {
+            let mut missing = vec![];
+            if !problem.may_compute.contains(&(site_id, compute_asset)) {
+                missing.push(Grant::MayCompute(site_id, compute_asset));
+            }
+            let chosen = compute_args.with_compute_asset(compute_asset);
+            // Grants cannot move immovable assets, so only their holders are candidates
+            if chosen.needed_assets().any(|asset_id| {
+                problem.immovable.contains(asset_id)
+                    && !problem.site_has_asset.contains(&(site_id, *asset_id))
+            }) {
+                continue;
+            }
+            if chosen.needed_assets().any(|asset_id| {
+                !sources
+                    .get(asset_id)
+                    .is_some_and(|from| from.iter().any(|&from| links.reaches(from, site_id)))
+            }) {
+                continue;
+            }
+            missing.extend(
+                chosen
+                    .needed_assets()
+                    .filter(|&&asset_id| !problem.may_access.contains(&(site_id, asset_id)))
+                    .map(|&asset_id| Grant::MayAccess(site_id, asset_id)),
+            );
+            if best.as_ref().map(|best| missing.len() < best.len()).unwrap_or(true) {
+                best = Some(missing);
+            }
+        }
+    }
+    best.filter(|best| !best.is_empty())
+}
+
+/// Check that the given plan is self-consistent for the given problem, without running any sites
+pub fn validate_plan<'a>(
+    plan: &'a HashMap<SiteId, Vec<Instruction>>,
+    problem: &Problem,
+) -> Result<(), Vec<PlanConsistencyError<'a>>> {
+    let mut errors = vec![];
+    // Every acquisition must be matched by a send from the source site.
+    for (&site_id, instructions) in plan.iter() {
+        for ins in instructions.iter() {
+            if let Instruction::AcquireAssetFrom { asset_id, site_id: from, .. } = ins {
+                let matched = plan.get(from).map(|from_instructions| {
+                    from_instructions.iter().any(|from_ins| match from_ins {
+                        Instruction::SendAssetTo { asset_id: a, site_id: to, .. } => {
+                            a == asset_id && *to == site_id
+                        }
+                        _ => false,
+                    })
+                });
+                if matched != Some(true) {
+                    errors.push(PlanConsistencyError::UnmatchedAcquire {
+                        site_id,
+                        asset_id: *asset_id,
+                        from: *from,
+                    });
+                }
+            }
+        }
+    }
+    // Symbolically re-walk the instructions until no more progress is possible.
+    // Any instruction still pending by then would never complete when executed.
+    let mut site_has_asset = problem.site_has_asset.clone();
+    let mut pending: Vec<(SiteId, &'a Instruction)> = plan
+        .iter()
+        .flat_map(|(&site_id, instructions)| instructions.iter().map(move |ins| (site_id, ins)))
+        .collect();
+    loop {
+        let pending_before = pending.len();
+        pending.retain(|&(site_id, ins)| {
+            let gained: Vec<(SiteId, AssetId)> = match ins {
+                Instruction::SendAssetTo { asset_id, site_id: to, .. } => {
+                    if !site_has_asset.contains(&(site_id, *asset_id)) {
+                        return true;
+                    }
+                    vec![(*to, *asset_id)]
+                }
+                Instruction::AcquireAssetFrom { asset_id, site_id: from, .. } => {
+                    if !site_has_asset.contains(&(*from, *asset_id)) {
+                        return true;
+                    }
+                    vec![(site_id, *asset_id)]
+                }
+                Instruction::ComputeAssetData(compute_args) => {
+                    let held =
+                        |asset_id: &&AssetId| site_has_asset.contains(&(site_id, **asset_id));
+                    let held_assets = site_has_asset
                         .iter()
-                        .filter_map(asset_filter_mapper(needed_asset))
-                        .next()
-                        .expect("`compute_sequence` ensurees SOME site should have this asset!");
-                    symbolic_store.insert(compute_site, *needed_asset);
-                    // Tell sender and receiver sites to send and receive respectively.
-                    // (Including either of these would suffice)
-                    push_instruction(
-                        having_site,
-                        Instruction::SendAssetTo { asset_id: *needed_asset, site_id: compute_site },
-                    );
-                    push_instruction(
-                        compute_site,
-                        Instruction::AcquireAssetFrom {
-                            asset_id: *needed_asset,
-                            site_id: having_site,
-                        },
-                    );
-                }
-                // Update our symbolic store of sites' assets.
-                for output_asset in next_compute.outputs.iter() {
-                    symbolic_store.insert(compute_site, *output_asset);
+                        .filter(|&&(holder, _)| holder == site_id)
+                        .map(|&(_, asset_id)| asset_id);
+                    let optional_candidates = compute_args.optional_candidates(held_assets);
+                    if !compute_args.needed_assets().all(|asset_id| held(&asset_id))
+                        || optional_candidates.iter().filter(held).count()
+                            < compute_args.min_optional_inputs
+                    {
+                        return true;
+                    }
+                    if compute_args.consume_inputs {
+                        for asset_id in compute_args.inputs.iter() {
+                            site_has_asset.remove(&(site_id, *asset_id));
+                        }
+                    }
+                    compute_args.outputs.iter().map(|asset_id| (site_id, *asset_id)).collect()
+                }
+                Instruction::Verify { asset_id, .. } => {
+                    if !site_has_asset.contains(&(site_id, *asset_id)) {
+                        return true;
+                    }
+                    vec![]
+                }
+            };
+            site_has_asset.extend(gained);
+            false
+        });
+        if pending.len() == pending_before {
+            break;
+        }
+    }
+    for (site_id, ins) in pending {
+        if let Instruction::ComputeAssetData(compute_args) = ins {
+            errors.push(PlanConsistencyError::UnsatisfiedCompute { site_id, compute_args });
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Derive the role of each site in the given plan
+pub fn classify_sites(plan: &HashMap<SiteId, Vec<Instruction>>) -> HashMap<SiteId, SiteRole> {
+    plan.iter()
+        .map(|(&site_id, instructions)| {
+            let (mut sends, mut acquires, mut computes) = (0, 0, 0);
+            for ins in instructions.iter() {
+                match ins {
+                    Instruction::SendAssetTo { .. } => sends += 1,
+                    Instruction::AcquireAssetFrom { .. } => acquires += 1,
+                    Instruction::ComputeAssetData(_) => computes += 1,
+                    Instruction::Verify { .. } => {}
+                }
+            }
+            let role = if computes > 0 {
+                SiteRole::Compute
+            } else if sends > 0 && acquires > 0 {
+                SiteRole::Relay
+            } else if sends > 0 {
+                SiteRole::Producer
+            } else {
+                SiteRole::Consumer
+            };
+            (site_id, role)
+        })
+        .collect()
+}
+
+/// The (site, asset) pairs which the given plan has its sites compute
+pub fn goals(plan: &HashMap<SiteId, Vec<Instruction>>) -> HashSet<(SiteId, AssetId)> {
+    let mut goals = HashSet::default();
+    for (&site_id, instructions) in plan.iter() {
+        for ins in instructions.iter() {
+            if let Instruction::ComputeAssetData(compute_args) = ins {
+                let site_id = compute_args.result_site.unwrap_or(site_id);
+                goals.extend(compute_args.outputs.iter().map(|&asset_id| (site_id, asset_id)));
+            }
+        }
+    }
+    goals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    fn site_ids(n: usize) -> Vec<SiteId> {
+        (0..n).map(|_| SiteId(Keypair::generate(&mut rand_core::OsRng).public)).collect()
+    }
+
+    fn compute(inputs: Vec<AssetId>, output: AssetId, compute_asset: AssetId) -> ComputeArgs {
+        ComputeArgs {
+            inputs,
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![output],
+            compute_asset,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: None,
+        }
+    }
+
+    /// Transfer priorities depend on the computes yet to come, so incremental plans differ in these
+    fn without_priority(ins: &Instruction) -> Instruction {
+        match ins.clone() {
+            Instruction::SendAssetTo { asset_id, site_id, .. } => {
+                Instruction::SendAssetTo { asset_id, site_id, priority: 0 }
+            }
+            Instruction::AcquireAssetFrom { asset_id, site_id, timeout, .. } => {
+                Instruction::AcquireAssetFrom { asset_id, site_id, priority: 0, timeout }
+            }
+            ins => ins,
+        }
+    }
+
+    fn without_priorities(
+        instructions: &HashMap<SiteId, Vec<Instruction>>,
+    ) -> HashMap<SiteId, Vec<Instruction>> {
+        instructions
+            .iter()
+            .map(|(site_id, instructions)| {
+                (*site_id, instructions.iter().map(without_priority).collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn incremental_plan_equals_batch_plan() {
+        let site_ids = site_ids(4);
+        for seed in 0..10 {
+            let problem =
+                generate::random_problem(&mut SmallRng::seed_from_u64(seed), &site_ids, 6, 12);
+            let (batch, trace) = plan_traced(&problem, |_| {});
+            let batch = batch.unwrap();
+            let mut planner = Planner::new(problem.clone());
+            let mut dispatched = vec![];
+            for step in trace.steps.iter() {
+                dispatched.extend(planner.add_compute(step.compute_args.clone()).unwrap());
+            }
+            assert_eq!(planner.pending().count(), 0);
+            assert_eq!(without_priorities(planner.instructions()), without_priorities(&batch));
+            let sequence = |sequence: &[(SiteId, Instruction)]| -> Vec<(SiteId, Instruction)> {
+                sequence.iter().map(|(site_id, ins)| (*site_id, without_priority(ins))).collect()
+            };
+            assert_eq!(sequence(&dispatched), sequence(&trace.sequence));
+        }
+    }
+
+    #[test]
+    fn pending_compute_planned_once_inputs_are_added() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, f] = [AssetId(0), AssetId(1), AssetId(2), AssetId(3)];
+        let problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (bob, y), (bob, z), (bob, f) },
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (bob, f) },
+            do_compute: vec![],
+            links: None,
+            immovable: Default::default(),
+        };
+        let mut planner = Planner::new(problem);
+        // z needs y, which is not yet produced
+        assert_eq!(planner.add_compute(compute(vec![y], z, f)).unwrap(), vec![]);
+        assert_eq!(planner.pending().count(), 1);
+        let dispatched = planner.add_compute(compute(vec![x], y, f)).unwrap();
+        assert_eq!(planner.pending().count(), 0);
+        let computed: Vec<AssetId> = dispatched
+            .iter()
+            .filter_map(|(_site_id, ins)| match ins {
+                Instruction::ComputeAssetData(compute_args) => Some(compute_args.outputs[0]),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(computed, vec![y, z]);
+    }
+
+    #[test]
+    fn rejected_compute_leaves_planner_unchanged() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, f] = [AssetId(0), AssetId(1), AssetId(2), AssetId(3)];
+        let problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (bob, y), (bob, z), (bob, f), (amy, z) },
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (bob, f) },
+            do_compute: vec![],
+            // bob cannot send back to amy
+            links: Some(maplit::hashset! { (amy, bob) }),
+            immovable: Default::default(),
+        };
+        let mut planner = Planner::new(problem);
+        planner.add_compute(compute(vec![x], y, f)).unwrap();
+        let before = planner.instructions().clone();
+        // Invalid: y has a producer already
+        let err = planner.add_compute(compute(vec![x], y, f)).unwrap_err();
+        assert!(matches!(err, PlanError::Invalid(ProblemError::MultipleProducers { .. })));
+        // The result site is unreachable from the compute site
+        let unreachable = ComputeArgs { result_site: Some(amy), ..compute(vec![y], z, f) };
+        let err = planner.add_compute(unreachable).unwrap_err();
+        assert!(matches!(err, PlanError::Unreachable { .. }));
+        assert_eq!(planner.instructions(), &before);
+        assert_eq!(planner.pending().count(), 0);
+        // Later computes are unaffected by the rejected ones
+        let dispatched = planner.add_compute(compute(vec![y], z, f)).unwrap();
+        assert_eq!(dispatched, vec![(bob, Instruction::ComputeAssetData(compute(vec![y], z, f)))]);
+    }
+
+    #[test]
+    fn result_site_must_access_outputs() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, f] = [AssetId(0), AssetId(1), AssetId(2)];
+        let mut problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (bob, y), (bob, f) },
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (bob, x), (bob, f) },
+            do_compute: vec![ComputeArgs { result_site: Some(amy), ..compute(vec![x], y, f) }],
+            links: None,
+            immovable: Default::default(),
+        };
+        let err = plan(&problem).unwrap_err();
+        assert!(matches!(err, PlanError::ResultSiteDenied { asset_id, .. } if asset_id == y));
+        problem.may_access.insert((amy, y));
+        assert!(plan(&problem).is_ok());
+    }
+
+    #[test]
+    fn failed_compute_site_is_replaced() {
+        let config = SiteConfig {
+            idle_timeout: Duration::from_millis(100),
+            quiescence_timeout: Duration::from_secs(10),
+            await_all_goals: true,
+            heartbeat_interval: Some(Duration::from_millis(20)),
+            ..Default::default()
+        };
+        let loggers = (0..4).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = site::new_sites_with_config(loggers, &config).unwrap();
+        let [src, left, right, sink] = [site_ids[0], site_ids[1], site_ids[2], site_ids[3]];
+        let [x, y, z, f, g] = [AssetId(0), AssetId(1), AssetId(2), AssetId(3), AssetId(4)];
+        let problem = Problem {
+            may_access: maplit::hashset! {
+                (left, x), (left, y), (left, f),
+                (right, x), (right, y), (right, f),
+                (sink, y), (sink, z), (sink, g),
+            },
+            may_compute: maplit::hashset! { (left, f), (right, f), (sink, g) },
+            site_has_asset: maplit::hashset! {
+                (src, x), (left, f), (right, f), (sink, g)
+            },
+            do_compute: vec![compute(vec![x], y, f), compute(vec![y], z, g)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let planned = plan(&problem).unwrap();
+        let mut failover = Failover::new(problem.clone(), planned.clone());
+        let worker =
+            if planned.get(&left).is_some_and(|ins| !ins.is_empty()) { left } else { right };
+        let (failure_sender, failures) = crossbeam_channel::unbounded();
+        let completion_watcher = CompletionWatcher::new(Some((sink, z)));
+        for (site_id, site) in sites.iter_mut() {
+            for &(_, asset_id) in problem.site_has_asset.iter().filter(|(s, _)| s == site_id) {
+                site.insert_asset(asset_id, AssetData::deterministic(asset_id));
+            }
+            site.todo_instructions = planned.get(site_id).cloned().unwrap_or_default();
+            site.set_failure_watcher(failure_sender.clone());
+            site.set_completion_watcher(completion_watcher.clone());
+        }
+        let mut worker_site = sites.remove(&worker).unwrap();
+        let controls: HashMap<SiteId, SiteControl> =
+            sites.iter().map(|(site_id, site)| (*site_id, site.control_handle())).collect();
+        let executing: Vec<_> = sites
+            .into_iter()
+            .map(|(site_id, mut site)| {
+                std::thread::spawn(move || {
+                    site.execute().unwrap();
+                    (site_id, site)
+                })
+            })
+            .collect();
+        // The worker receives its input, but fails before computing
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !worker_site.has_asset(&x) {
+            assert!(Instant::now() < deadline, "the worker never received its input");
+            worker_site.step().unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!worker_site.has_asset(&y));
+        drop(worker_site);
+        assert_eq!(failures.recv_timeout(Duration::from_secs(5)), Ok(worker));
+        for (site_id, instructions) in failover.reassign(worker).unwrap() {
+            let signed = SignedInstructions { site_id, instructions, signature: None };
+            controls[&site_id].add_instructions(signed);
+        }
+        assert!(completion_watcher.wait_timeout(Duration::from_secs(5)));
+        let sites: HashMap<SiteId, Site> =
+            executing.into_iter().map(|handle| handle.join().unwrap()).collect();
+        let replacement = if worker == left { right } else { left };
+        assert!(sites[&replacement].has_asset(&y));
+        assert!(sites[&sink].has_asset(&z));
+    }
+
+    #[test]
+    fn single_missing_grant_is_suggested() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, f] = [AssetId(0), AssetId(1), AssetId(2)];
+        let problem = Problem {
+            may_access: maplit::hashset! { (amy, x), (bob, x), (bob, f) },
+            may_compute: maplit::hashset! {},
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f) },
+            do_compute: vec![compute(vec![x], y, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        assert!(matches!(plan(&problem), Err(PlanError::NoSiteForCompute(_))));
+        assert_eq!(suggest_grants(&problem), Some(vec![Grant::MayCompute(bob, f)]));
+    }
+
+    #[test]
+    fn grants_are_only_suggested_for_reachable_sites() {
+        let site_ids = site_ids(3);
+        let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
+        let [x, y, f] = [AssetId(0), AssetId(1), AssetId(2)];
+        let problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (cho, x) },
+            may_compute: maplit::hashset! { (cho, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f) },
+            do_compute: vec![compute(vec![x], y, f)],
+            // cho lacks only one grant, but nothing can reach it
+            links: Some(maplit::hashset! { (amy, bob) }),
+            immovable: Default::default(),
+        };
+        let grants = suggest_grants(&problem).unwrap();
+        assert_eq!(grants, vec![Grant::MayCompute(bob, f), Grant::MayAccess(bob, f)]);
+
+        // No grant conjures an asset which no site holds
+        let unheld = Problem { site_has_asset: maplit::hashset! { (amy, x) }, ..problem };
+        assert_eq!(suggest_grants(&unheld), None);
+    }
+
+    #[test]
+    fn critical_path_is_the_heaviest_chain() {
+        let [x, y, z, w, v, f] = [0, 1, 2, 3, 4, 5].map(AssetId);
+        let weighted =
+            |inputs, output, weight| ComputeArgs { weight, ..compute(inputs, output, f) };
+        let [a, b, c, d] = [
+            weighted(vec![x], y, 3),
+            weighted(vec![y], z, 1),
+            weighted(vec![y], w, 5),
+            weighted(vec![z, w], v, 2),
+        ];
+        let problem = Problem {
+            may_access: Default::default(),
+            may_compute: Default::default(),
+            site_has_asset: Default::default(),
+            do_compute: vec![d.clone(), b, c.clone(), a.clone()],
+            links: None,
+            immovable: Default::default(),
+        };
+        assert_eq!(critical_path(&problem), (10, vec![&a, &c, &d]));
+    }
+
+    #[test]
+    fn long_chains_are_costed_in_linear_time() {
+        let n = 50_000;
+        let f = AssetId(u32::MAX);
+        // Listed last to first, such that each compute precedes those it depends on
+        let do_compute =
+            (0..n).rev().map(|i| compute(vec![AssetId(i)], AssetId(i + 1), f)).collect();
+        let problem = Problem {
+            may_access: Default::default(),
+            may_compute: Default::default(),
+            site_has_asset: Default::default(),
+            do_compute,
+            links: None,
+            immovable: Default::default(),
+        };
+        let (makespan, path) = critical_path(&problem);
+        assert_eq!(makespan, n as u64);
+        assert_eq!(path.len(), n as usize);
+        assert_eq!(path[0].inputs, vec![AssetId(0)]);
+    }
+
+    #[test]
+    fn dropped_send_is_inconsistent() {
+        let (problem, _, _) = crate::scenario::amy_bob_cho(|_| Box::new(NullLogger));
+        let mut planned = plan(&problem).unwrap();
+        assert!(validate_plan(&planned, &problem).is_ok());
+        let is_send = |ins: &Instruction| matches!(ins, Instruction::SendAssetTo { .. });
+        let (&sender, instructions) =
+            planned.iter_mut().find(|(_, instructions)| instructions.iter().any(is_send)).unwrap();
+        let i = instructions.iter().position(is_send).unwrap();
+        let dropped = instructions.remove(i);
+        let errors = validate_plan(&planned, &problem).unwrap_err();
+        assert!(errors.iter().any(|e| match (e, &dropped) {
+            (
+                PlanConsistencyError::UnmatchedAcquire { site_id, asset_id, from },
+                Instruction::SendAssetTo { asset_id: sent, site_id: to, .. },
+            ) => (site_id, asset_id, *from) == (to, sent, sender),
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn trace_lists_computes_in_execution_order() {
+        let amy = site_ids(1)[0];
+        let [w, x, y, z, f] = [0, 1, 2, 3, 4].map(AssetId);
+        let [a, b, c] = [compute(vec![w], x, f), compute(vec![x], y, f), compute(vec![y], z, f)];
+        let problem = Problem {
+            may_access: [w, x, y, z, f].iter().map(|&asset_id| (amy, asset_id)).collect(),
+            may_compute: maplit::hashset! { (amy, f) },
+            site_has_asset: maplit::hashset! { (amy, w), (amy, f) },
+            // Listed in reverse, such that each depends on the computes after it
+            do_compute: vec![c.clone(), b.clone(), a.clone()],
+            links: None,
+            immovable: Default::default(),
+        };
+        let mut stepped = vec![];
+        let (result, trace) = plan_traced(&problem, |step| stepped.push(step.compute_args.clone()));
+        assert!(result.is_ok());
+        let traced: Vec<&ComputeArgs> = trace.steps.iter().map(|step| step.compute_args).collect();
+        assert_eq!(traced, vec![&a, &b, &c]);
+        assert_eq!(stepped, vec![a, b, c]);
+        assert!(trace.steps.iter().all(|step| step.compute_site == amy));
+    }
+
+    #[test]
+    fn compute_needing_a_consumed_input_is_infeasible() {
+        let amy = site_ids(1)[0];
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        let consumer = ComputeArgs { consume_inputs: true, ..compute(vec![x], y, f) };
+        let problem = Problem {
+            may_access: [x, y, z, f].iter().map(|&asset_id| (amy, asset_id)).collect(),
+            may_compute: maplit::hashset! { (amy, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f) },
+            // The second must follow the first, by which time x is consumed
+            do_compute: vec![consumer.clone(), compute(vec![x, y], z, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        match plan(&problem) {
+            Err(PlanError::ConsumedInput { compute_args, asset_id }) => {
+                assert_eq!((compute_args, asset_id), (&problem.do_compute[1], x));
+            }
+            other => panic!("expected x to be consumed, got {:?}", other),
+        }
+        // Without the later compute, consuming x is fine
+        let alone = Problem { do_compute: vec![consumer], ..problem.clone() };
+        assert!(plan(&alone).is_ok());
+    }
+
+    #[test]
+    fn sites_are_classified_by_their_instructions() {
+        let site_ids = site_ids(4);
+        let [amy, bob, cho, dan] = [site_ids[0], site_ids[1], site_ids[2], site_ids[3]];
+        let [x, y, f] = [AssetId(0), AssetId(1), AssetId(2)];
+        let send = |asset_id, site_id| Instruction::SendAssetTo { asset_id, site_id, priority: 0 };
+        let acquire = |asset_id, site_id| Instruction::AcquireAssetFrom {
+            asset_id,
+            site_id,
+            priority: 0,
+            timeout: None,
+        };
+        let plan = maplit::hashmap! {
+            amy => vec![send(x, bob)],
+            bob => vec![acquire(x, amy), send(x, cho)],
+            cho => vec![acquire(x, bob), Instruction::ComputeAssetData(compute(vec![x], y, f)), send(y, dan)],
+            dan => vec![acquire(y, cho)],
+        };
+        let expected = maplit::hashmap! {
+            amy => SiteRole::Producer,
+            bob => SiteRole::Relay,
+            cho => SiteRole::Compute,
+            dan => SiteRole::Consumer,
+        };
+        assert_eq!(classify_sites(&plan), expected);
+    }
+
+    #[test]
+    fn duplicate_route_is_collapsed() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        let problem = Problem {
+            may_access: [x, y, z, f].iter().map(|&asset_id| (bob, asset_id)).collect(),
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (bob, f) },
+            do_compute: vec![compute(vec![x], y, f), compute(vec![x], z, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let mut state = PlanState::new(&problem);
+        let mut trace = PlanTrace::default();
+        state.plan_compute(&problem, &problem.do_compute[0], 0, &mut trace).unwrap();
+        // Lose track of bob holding x, such that the next compute would route it to bob again
+        let store = &mut state.symbolic_store;
+        store.site_has_asset.remove(&(bob, x));
+        store.holders.get_mut(&x).unwrap().retain(|&site_id| site_id != bob);
+        state.plan_compute(&problem, &problem.do_compute[1], 0, &mut trace).unwrap();
+        assert_eq!(trace.duplicate_routes, vec![(x, bob)]);
+        let sends = state.instructions[&amy]
+            .iter()
+            .filter(|ins| matches!(ins, Instruction::SendAssetTo { asset_id, site_id, .. } if (*asset_id, *site_id) == (x, bob)))
+            .count();
+        assert_eq!(sends, 1);
+    }
+
+    #[test]
+    fn hot_asset_is_sent_by_each_holder() {
+        let site_ids = site_ids(5);
+        let (sources, consumers) = site_ids.split_at(2);
+        let x = AssetId(0);
+        // Each consumer computes its own output with its own compute asset
+        let compute_asset = |i: usize| AssetId(10 + i as u32);
+        let output = |i: usize| AssetId(20 + i as u32);
+        let mut problem = Problem {
+            may_access: Default::default(),
+            may_compute: Default::default(),
+            site_has_asset: sources.iter().map(|&source| (source, x)).collect(),
+            do_compute: vec![],
+            // Only the sources send, such that consumers do not pass x on
+            links: Some(
+                sources
+                    .iter()
+                    .flat_map(|&source| consumers.iter().map(move |&consumer| (source, consumer)))
+                    .collect(),
+            ),
+            immovable: Default::default(),
+        };
+        for (i, &consumer) in consumers.iter().enumerate() {
+            problem.may_access.extend(vec![
+                (consumer, x),
+                (consumer, compute_asset(i)),
+                (consumer, output(i)),
+            ]);
+            problem.may_compute.insert((consumer, compute_asset(i)));
+            problem.site_has_asset.insert((consumer, compute_asset(i)));
+            problem.do_compute.push(compute(vec![x], output(i), compute_asset(i)));
+        }
+        let planned = plan(&problem).unwrap();
+        for source in sources {
+            let sends = planned[source]
+                .iter()
+                .filter(|ins| matches!(ins, Instruction::SendAssetTo { asset_id, .. } if *asset_id == x))
+                .count();
+            assert!(sends > 0);
+        }
+    }
+
+    #[test]
+    fn reducible_inputs_are_pre_reduced_where_they_are() {
+        let ids = site_ids(2);
+        let [amy, bob] = [ids[0], ids[1]];
+        let [x0, x1, x2, y, f] = [0, 1, 2, 3, 4].map(AssetId);
+        let reduce = ComputeArgs { reducible: true, ..compute(vec![x0, x1, x2], y, f) };
+        let problem = Problem {
+            // only bob may access all inputs, so bob combines
+            may_access: maplit::hashset! {
+                (amy, x0), (amy, x1), (amy, f),
+                (bob, x0), (bob, x1), (bob, x2), (bob, y), (bob, f),
+            },
+            may_compute: maplit::hashset! { (amy, f), (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x0), (amy, x1), (amy, f), (bob, x2), (bob, f) },
+            do_compute: vec![reduce],
+            links: None,
+            immovable: Default::default(),
+        };
+        let instructions = plan(&problem).unwrap();
+        let partial = match &instructions[&amy][..] {
+            [Instruction::ComputeAssetData(partial), Instruction::SendAssetTo { asset_id, site_id, .. }] =>
+            {
+                assert_eq!(partial.inputs, vec![x0, x1]);
+                assert_eq!((*asset_id, *site_id), (partial.outputs[0], bob));
+                partial.outputs[0]
+            }
+            other => panic!("expected amy to pre-reduce and send the result, got {:?}", other),
+        };
+        // bob combines the partial result with its own input, receiving nothing else
+        let combine = instructions[&bob].iter().find_map(|ins| match ins {
+            Instruction::ComputeAssetData(combine) => Some(combine),
+            _ => None,
+        });
+        let mut inputs = combine.unwrap().inputs.clone();
+        inputs.sort();
+        assert_eq!(inputs, vec![x2, partial]);
+        let acquired = instructions[&bob].iter().filter_map(|ins| match ins {
+            Instruction::AcquireAssetFrom { asset_id, .. } => Some(*asset_id),
+            _ => None,
+        });
+        assert_eq!(acquired.collect::<Vec<_>>(), vec![partial]);
+    }
+
+    #[test]
+    fn compute_is_placed_at_the_site_holding_an_input() {
+        let ids = site_ids(3);
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        // amy and bob are equally capable, so only the placement of x tells them apart
+        for &(holder, other) in [(ids[0], ids[1]), (ids[1], ids[0])].iter() {
+            let cho = ids[2];
+            let capable = [holder, other];
+            let problem = Problem {
+                may_access: capable
+                    .iter()
+                    .flat_map(|&site_id| [x, y, z, f].map(|asset_id| (site_id, asset_id)))
+                    .collect(),
+                may_compute: capable.iter().map(|&site_id| (site_id, f)).collect(),
+                site_has_asset: maplit::hashset! { (holder, x), (cho, y), (cho, f) },
+                do_compute: vec![compute(vec![x, y], z, f)],
+                links: None,
+                immovable: Default::default(),
+            };
+            let instructions = plan(&problem).unwrap();
+            let computes_at = |site_id| {
+                instructions
+                    .get(&site_id)
+                    .into_iter()
+                    .flatten()
+                    .any(|ins| matches!(ins, Instruction::ComputeAssetData(..)))
+            };
+            assert!(computes_at(holder));
+            assert!(!computes_at(other));
+        }
+    }
+
+    #[test]
+    fn either_acceptable_compute_asset_is_chosen() {
+        let ids = site_ids(3);
+        let [amy, bob, cho] = [ids[0], ids[1], ids[2]];
+        let [x, y, f1, f2] = [0, 1, 2, 3].map(AssetId);
+        // amy and bob each hold, and may compute with, a different version of the function
+        let problem = Problem {
+            may_access: maplit::hashset! {
+                (amy, x), (amy, y), (amy, f1),
+                (bob, x), (bob, y), (bob, f2),
+            },
+            may_compute: maplit::hashset! { (amy, f1), (bob, f2) },
+            site_has_asset: maplit::hashset! { (amy, f1), (bob, f2), (cho, x) },
+            do_compute: vec![ComputeArgs {
+                alt_compute_assets: vec![f2],
+                ..compute(vec![x], y, f1)
+            }],
+            links: None,
+            immovable: Default::default(),
+        };
+        let instructions = plan(&problem).unwrap();
+        let computes: Vec<(SiteId, &ComputeArgs)> = instructions
+            .iter()
+            .flat_map(|(&site_id, instructions)| {
+                instructions.iter().filter_map(move |ins| match ins {
+                    Instruction::ComputeAssetData(compute_args) => Some((site_id, compute_args)),
+                    _ => None,
+                })
+            })
+            .collect();
+        match &computes[..] {
+            [(site_id, compute_args)] => {
+                assert!(compute_args.alt_compute_assets.is_empty());
+                let chosen = (*site_id, compute_args.compute_asset);
+                assert!(chosen == (amy, f1) || chosen == (bob, f2), "{:?}", computes);
+            }
+            _ => panic!("expected a single compute, got {:?}", computes),
+        }
+    }
+
+    #[test]
+    fn plan_stats_grow_with_problem_size() {
+        let stats = |num_sites, num_initial_assets, num_computes| {
+            let mut rng = SmallRng::seed_from_u64(0);
+            let site_ids = site_ids(num_sites);
+            let problem =
+                generate::random_problem(&mut rng, &site_ids, num_initial_assets, num_computes);
+            let (result, trace) = plan_traced(&problem, |_| {});
+            result.unwrap();
+            trace.stats
+        };
+        let small = stats(3, 4, 4);
+        let large = stats(30, 60, 100);
+        assert!(small.feasibility_checks > 0);
+        assert!(small.site_selection_scans > 0);
+        assert!(small.having_site_lookups > 0);
+        assert!(large.feasibility_checks > small.feasibility_checks);
+        assert!(large.site_selection_scans > small.site_selection_scans);
+        assert!(large.having_site_lookups > small.having_site_lookups);
+    }
+
+    #[test]
+    fn holder_index_agrees_with_scanning_the_store() {
+        let site_ids = site_ids(10);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let problem = generate::random_problem(&mut rng, &site_ids, 20, 30);
+        // Lookups must find the same holders as the scans they replaced
+        let check = |store: &SymbolicStore| {
+            for asset_id in problem.assets() {
+                let scanned: HashSet<SiteId> = store
+                    .site_has_asset
+                    .iter()
+                    .filter_map(asset_filter_mapper(&asset_id))
+                    .collect();
+                let indexed: HashSet<SiteId> = store.holders(&asset_id).iter().copied().collect();
+                assert_eq!(indexed, scanned);
+                assert_eq!(store.someone_has(&asset_id), !scanned.is_empty());
+            }
+        };
+        let mut store = SymbolicStore::with_assets(&problem.site_has_asset);
+        check(&store);
+        for compute_args in problem.do_compute.iter() {
+            for (&site_id, &asset_id) in site_ids.iter().zip(compute_args.outputs.iter()) {
+                store.insert(site_id, asset_id);
+                store.insert(site_id, asset_id);
+            }
+        }
+        check(&store);
+        store.consume(problem.do_compute[0].inputs[0]);
+        check(&store);
+    }
+
+    #[test]
+    fn consolidate_places_computes_on_one_site() {
+        let ids = site_ids(2);
+        let [amy, bob] = [ids[0], ids[1]];
+        let [x, y, z, w, f] = [0, 1, 2, 3, 4].map(AssetId);
+        // amy holds the input of one compute, and bob that of the other
+        let problem = Problem {
+            may_access: ids
+                .iter()
+                .flat_map(|&site_id| [x, y, z, w, f].map(|asset_id| (site_id, asset_id)))
+                .collect(),
+            may_compute: maplit::hashset! { (amy, f), (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (bob, y), (amy, f), (bob, f) },
+            do_compute: vec![compute(vec![x], z, f), compute(vec![y], w, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let (result, trace) = plan_with_strategy(&problem, PlanStrategy::Consolidate, |_| {});
+        assert_eq!(trace.strategy, PlanStrategy::Consolidate);
+        let compute_sites: HashSet<SiteId> =
+            trace.steps.iter().map(|step| step.compute_site).collect();
+        assert_eq!(compute_sites.len(), 1);
+        result.unwrap();
+        // whereas balancing the load spreads them out
+        let (_, trace) = plan_with_strategy(&problem, PlanStrategy::Balance, |_| {});
+        let compute_sites: HashSet<SiteId> =
+            trace.steps.iter().map(|step| step.compute_site).collect();
+        assert_eq!(compute_sites.len(), 2);
+    }
+
+    #[test]
+    fn input_across_a_partition_is_unreachable() {
+        let ids = site_ids(3);
+        let [amy, bob, cho] = [ids[0], ids[1], ids[2]];
+        let [x, y, f] = [AssetId(0), AssetId(1), AssetId(2)];
+        let problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (bob, y), (bob, f) },
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (cho, x), (amy, f) },
+            do_compute: vec![compute(vec![x], y, f)],
+            // cho is cut off from amy and bob
+            links: Some(maplit::hashset! { (amy, bob), (bob, amy) }),
+            immovable: Default::default(),
+        };
+        match plan(&problem) {
+            Err(PlanError::Unreachable { asset_id, from, to }) => {
+                assert_eq!((asset_id, *from, *to), (x, cho, bob));
+            }
+            other => panic!("expected x to be unreachable, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn replayed_sequence_has_each_compute_input_in_place() {
+        let site_ids = site_ids(5);
+        for seed in 0..10 {
+            let problem =
+                generate::random_problem(&mut SmallRng::seed_from_u64(seed), &site_ids, 8, 12);
+            let sequence = plan_sequence(&problem).unwrap();
+            let mut held = problem.site_has_asset.clone();
+            for (site_id, ins) in sequence.iter() {
+                match ins {
+                    Instruction::SendAssetTo { asset_id, site_id: to, .. } => {
+                        assert!(held.contains(&(*site_id, *asset_id)), "sent before held");
+                        held.insert((*to, *asset_id));
+                    }
+                    Instruction::AcquireAssetFrom { asset_id, site_id: from, .. } => {
+                        assert!(held.contains(&(*from, *asset_id)), "acquired before held");
+                        held.insert((*site_id, *asset_id));
+                    }
+                    Instruction::ComputeAssetData(compute_args) => {
+                        for needed_asset in compute_args.needed_assets() {
+                            assert!(held.contains(&(*site_id, *needed_asset)), "{}", compute_args);
+                        }
+                        held.extend(compute_args.outputs.iter().map(|&output| (*site_id, output)));
+                    }
+                    Instruction::Verify { .. } => {}
                 }
             }
         }
     }
+
+    #[test]
+    fn colocate_keeps_an_intermediate_where_it_was_produced() {
+        let ids = site_ids(2);
+        let [amy, bob] = [ids[0], ids[1]];
+        let [x, m, w, z, f] = [0, 1, 2, 3, 4].map(AssetId);
+        let problem = Problem {
+            may_access: ids
+                .iter()
+                .flat_map(|&site_id| [x, m, w, z, f].map(|asset_id| (site_id, asset_id)))
+                .collect(),
+            may_compute: maplit::hashset! { (amy, f), (bob, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (bob, w), (amy, f), (bob, f) },
+            // m is only consumed by the second compute
+            do_compute: vec![compute(vec![x], m, f), compute(vec![m, w], z, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let transfers_of_m = |instructions: &HashMap<SiteId, Vec<Instruction>>| {
+            instructions
+                .values()
+                .flatten()
+                .filter(|ins| matches!(ins, Instruction::SendAssetTo { asset_id, .. } if *asset_id == m))
+                .count()
+        };
+        let (result, trace) = plan_with_strategy(&problem, PlanStrategy::Colocate, |_| {});
+        let compute_sites: Vec<SiteId> = trace.steps.iter().map(|step| step.compute_site).collect();
+        assert_eq!(compute_sites, vec![amy, amy]);
+        assert_eq!(transfers_of_m(&result.unwrap()), 0);
+
+        // amy may not access w, so the consumer falls back to bob, to which m is sent
+        let mut problem = problem;
+        problem.may_access.remove(&(amy, w));
+        let (result, trace) = plan_with_strategy(&problem, PlanStrategy::Colocate, |_| {});
+        let compute_sites: Vec<SiteId> = trace.steps.iter().map(|step| step.compute_site).collect();
+        assert_eq!(compute_sites, vec![amy, bob]);
+        assert_eq!(transfers_of_m(&result.unwrap()), 1);
+    }
+
+    #[test]
+    fn compute_with_existing_outputs_is_not_planned() {
+        let ids = site_ids(2);
+        let [amy, bob] = [ids[0], ids[1]];
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        // bob holds y already, as if cached from an earlier run
+        let problem = Problem {
+            may_access: ids
+                .iter()
+                .flat_map(|&site_id| [x, y, z, f].map(|asset_id| (site_id, asset_id)))
+                .collect(),
+            may_compute: maplit::hashset! { (amy, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f), (bob, y) },
+            do_compute: vec![compute(vec![x], y, f), compute(vec![y], z, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let (result, trace) = plan_traced(&problem, |_| {});
+        let instructions = result.unwrap();
+        let computes: Vec<&ComputeArgs> = instructions
+            .values()
+            .flatten()
+            .filter_map(|ins| match ins {
+                Instruction::ComputeAssetData(compute_args) => Some(compute_args),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(computes, vec![&problem.do_compute[1]]);
+        assert_eq!(trace.reused_outputs, vec![&problem.do_compute[0]]);
+        // y is sent from bob instead
+        assert!(instructions[&bob].iter().any(|ins| matches!(
+            ins,
+            Instruction::SendAssetTo { asset_id, site_id, .. } if (*asset_id, *site_id) == (y, amy)
+        )));
+    }
+
+    #[test]
+    fn reference_asset_pulls_its_compute_to_its_holder() {
+        let ids = site_ids(2);
+        let [amy, bob] = [ids[0], ids[1]];
+        let [r, x, y, f] = [0, 1, 2, 3].map(AssetId);
+        let problem = |may_compute: HashSet<(SiteId, AssetId)>, immovable: HashSet<AssetId>| {
+            Problem {
+                may_access: ids.iter().flat_map(|&s| [r, x, y, f].map(|a| (s, a))).collect(),
+                may_compute,
+                // bob holds most of what is needed, but amy holds the reference asset r
+                site_has_asset: maplit::hashset! { (amy, r), (bob, x), (bob, f) },
+                do_compute: vec![compute(vec![r, x], y, f)],
+                links: None,
+                immovable,
+            }
+        };
+        let computes_at = |instructions: &HashMap<SiteId, Vec<Instruction>>, site_id| {
+            instructions[&site_id]
+                .iter()
+                .any(|ins| matches!(ins, Instruction::ComputeAssetData(..)))
+        };
+        let movable = problem(maplit::hashset! { (amy, f), (bob, f) }, Default::default());
+        assert!(computes_at(&plan(&movable).unwrap(), bob));
+
+        let reference = problem(maplit::hashset! { (amy, f), (bob, f) }, maplit::hashset! { r });
+        let instructions = plan(&reference).unwrap();
+        assert!(computes_at(&instructions, amy));
+        assert!(!instructions.values().flatten().any(|ins| matches!(
+            ins,
+            Instruction::SendAssetTo { asset_id, .. } | Instruction::AcquireAssetFrom { asset_id, .. }
+                if *asset_id == r
+        )));
+        // only bob may compute, but r may not move there
+        let stuck = problem(maplit::hashset! { (bob, f) }, maplit::hashset! { r });
+        assert!(matches!(plan(&stuck), Err(PlanError::NoSiteForCompute(_))));
+    }
 }
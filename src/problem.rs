@@ -0,0 +1,510 @@
+use super::*;
+
+impl ComputeArgs {
+    /// Check for authoring mistakes which would otherwise confuse planning
+    pub fn validate(&self) -> Result<(), ComputeArgsError> {
+        if self.inputs.is_empty() && self.optional_inputs.is_empty() && self.input_range.is_none() {
+            // A reduction over optional inputs alone is fine
+            return Err(ComputeArgsError::NoInputs);
+        }
+        if self.outputs.contains(&self.compute_asset) {
+            return Err(ComputeArgsError::ComputeAssetIsOutput);
+        }
+        if self.input_range.is_none() && self.optional_inputs.len() < self.min_optional_inputs {
+            return Err(ComputeArgsError::TooFewOptionalInputs);
+        }
+        if self.reducible && self.outputs.len() != 1 {
+            return Err(ComputeArgsError::ReducibleWithoutSingleOutput);
+        }
+        if self.alt_compute_assets.iter().any(|asset_id| self.outputs.contains(asset_id)) {
+            return Err(ComputeArgsError::ComputeAssetIsOutput);
+        }
+        if let Some(&asset_id) = self.inputs.iter().find(|asset_id| self.outputs.contains(asset_id))
+        {
+            return Err(ComputeArgsError::InputIsOutput(asset_id));
+        }
+        if let Some(&asset_id) =
+            self.optional_inputs.iter().find(|asset_id| self.outputs.contains(asset_id))
+        {
+            return Err(ComputeArgsError::InputIsOutput(asset_id));
+        }
+        let needed = self
+            .needed_assets()
+            .chain(self.optional_inputs.iter())
+            .chain(self.alt_compute_assets.iter())
+            .collect::<Vec<_>>();
+        for list in [needed, self.outputs.iter().collect()].iter() {
+            for (i, asset_id) in list.iter().enumerate() {
+                if list[..i].contains(asset_id) {
+                    return Err(ComputeArgsError::DuplicateAsset(**asset_id));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Problem {
+    /// All the assets this problem mentions
+    pub fn assets(&self) -> BTreeSet<AssetId> {
+        let site_assets =
+            self.may_access.iter().chain(self.may_compute.iter()).chain(self.site_has_asset.iter());
+        site_assets
+            .map(|(_site_id, asset_id)| *asset_id)
+            .chain(self.do_compute.iter().flat_map(|compute_args| {
+                let alts = compute_args.alt_compute_assets.iter();
+                let optionals = compute_args.optional_inputs.iter();
+                let outputs = compute_args.outputs.iter();
+                compute_args.needed_assets().chain(optionals).chain(alts).chain(outputs).copied()
+            }))
+            .collect()
+    }
+    /// All the sites this problem mentions, including those only linked or holding results
+    pub fn sites(&self) -> BTreeSet<SiteId> {
+        let site_assets =
+            self.may_access.iter().chain(self.may_compute.iter()).chain(self.site_has_asset.iter());
+        let linked = self
+            .links
+            .iter()
+            .flatten()
+            .flat_map(|&(from, to)| std::iter::once(from).chain(Some(to)));
+        let result_sites =
+            self.do_compute.iter().filter_map(|compute_args| compute_args.result_site);
+        site_assets.map(|(site_id, _asset_id)| *site_id).chain(linked).chain(result_sites).collect()
+    }
+    /// Check that each compute is well-formed, and that each asset has at most one producer
+    pub fn validate(&self) -> Result<(), ProblemError<'_>> {
+        let mut produced = HashSet::<AssetId>::default();
+        for compute_args in self.do_compute.iter() {
+            compute_args.validate().map_err(|e| ProblemError::InvalidCompute(compute_args, e))?;
+            for &asset_id in compute_args.outputs.iter() {
+                if !produced.insert(asset_id) {
+                    return Err(ProblemError::MultipleProducers { asset_id });
+                }
+            }
+        }
+        Ok(())
+    }
+    /// For each compute, in order, whether it can be planned, judged only by permissions and
+    /// which assets are held or producible by feasible computes. Cheaper than `planning::plan`,
+    /// but blind to links and consumed inputs, so feasible computes may yet fail planning.
+    pub fn feasibility_report(&self) -> Vec<ComputeFeasibility<'_>> {
+        // The assets held, and those produced by feasible computes, until no more are
+        let mut available: HashSet<AssetId> =
+            self.site_has_asset.iter().map(|&(_site_id, asset_id)| asset_id).collect();
+        loop {
+            let available_before = available.len();
+            for compute_args in self.do_compute.iter() {
+                let feasibility = self.compute_feasibility(&available, compute_args);
+                if let ComputeFeasibility::Feasible(_) = feasibility {
+                    available.extend(compute_args.outputs.iter().copied());
+                }
+            }
+            if available.len() == available_before {
+                break;
+            }
+        }
+        self.do_compute
+            .iter()
+            .map(|compute_args| self.compute_feasibility(&available, compute_args))
+            .collect()
+    }
+    fn compute_feasibility<'a>(
+        &self,
+        available: &HashSet<AssetId>,
+        compute_args: &'a ComputeArgs,
+    ) -> ComputeFeasibility<'a> {
+        let missing = |asset_id: &&AssetId| !available.contains(asset_id);
+        let optional_candidates = compute_args.optional_candidates(available.iter().copied());
+        let present_optionals =
+            optional_candidates.iter().filter(|a| available.contains(a)).count();
+        if let Err(error) = compute_args.validate() {
+            // e.g., needing more optional inputs than it lists
+            ComputeFeasibility::Invalid { compute_args, error }
+        } else if let Some(&asset_id) = compute_args.inputs.iter().find(missing) {
+            ComputeFeasibility::MissingInput { compute_args, asset_id }
+        } else if present_optionals < compute_args.min_optional_inputs {
+            // Missing from the listed optional inputs, or else from those of `input_range`
+            let asset_id =
+                compute_args.optional_inputs.iter().find(missing).copied().unwrap_or_else(|| {
+                    compute_args
+                        .input_range
+                        .map(|range| range.start)
+                        .unwrap_or(compute_args.compute_asset)
+                });
+            ComputeFeasibility::MissingInput { compute_args, asset_id }
+        } else if compute_args.compute_assets().all(|a| missing(&a)) {
+            let asset_id = compute_args.compute_asset;
+            ComputeFeasibility::MissingInput { compute_args, asset_id }
+        } else if !compute_args
+            .compute_assets()
+            .any(|a| available.contains(a) && self.may_compute_with(compute_args, a))
+        {
+            ComputeFeasibility::NoEligibleSite(compute_args)
+        } else {
+            ComputeFeasibility::Feasible(compute_args)
+        }
+    }
+    /// Whether some site may compute with the given compute asset, and access the inputs,
+    /// holding those which are immovable
+    fn may_compute_with(&self, compute_args: &ComputeArgs, compute_asset: &AssetId) -> bool {
+        self.may_compute.iter().any(|&(site_id, asset_id)| {
+            asset_id == *compute_asset
+                && compute_args.inputs.iter().chain(Some(compute_asset)).all(|needed| {
+                    self.may_access.contains(&(site_id, *needed))
+                        && (!self.immovable.contains(needed)
+                            || self.site_has_asset.contains(&(site_id, *needed)))
+                })
+        })
+    }
+    /// Combine two problems into one, e.g., separately-authored pipelines sharing sites and assets.
+    /// Computes present in both problems are only done once. The merged problem is validated.
+    pub fn merge(mut self, other: Problem) -> Result<Problem, MergeConflict> {
+        let Problem { may_access, may_compute, site_has_asset, do_compute, links, immovable } =
+            other;
+        for compute_args in do_compute {
+            if self.do_compute.contains(&compute_args) {
+                continue;
+            }
+            // Every asset must have one unambiguous producer
+            for existing in self.do_compute.iter() {
+                if let Some(&asset_id) =
+                    compute_args.outputs.iter().find(|asset_id| existing.outputs.contains(asset_id))
+                {
+                    return Err(MergeConflict::ConflictingProducers {
+                        asset_id,
+                        computes: Box::new([existing.clone(), compute_args]),
+                    });
+                }
+            }
+            self.do_compute.push(compute_args);
+        }
+        self.may_access.extend(may_access);
+        self.may_compute.extend(may_compute);
+        self.site_has_asset.extend(site_has_asset);
+        self.immovable.extend(immovable);
+        // Sites linked in either problem are linked. Unrestricted links subsume all others
+        self.links = match (self.links, links) {
+            (Some(mut self_links), Some(links)) => {
+                self_links.extend(links);
+                Some(self_links)
+            }
+            _ => None,
+        };
+        self.validate().map_err(|e| match e {
+            ProblemError::InvalidCompute(compute_args, error) => MergeConflict::InvalidCompute {
+                compute_args: Box::new(compute_args.clone()),
+                error,
+            },
+            ProblemError::MultipleProducers { asset_id } => {
+                MergeConflict::MultipleProducers { asset_id }
+            }
+        })?;
+        Ok(self)
+    }
+
+    /// Report what changed from this problem to `other`, e.g., to find which edit broke planning
+    pub fn diff(&self, other: &Problem) -> ProblemDiff {
+        let compute_diff = |old: &Problem, new: &Problem| -> Vec<ComputeArgs> {
+            new.do_compute.iter().filter(|c| !old.do_compute.contains(c)).cloned().collect()
+        };
+        ProblemDiff {
+            may_access: set_diff(&self.may_access, &other.may_access),
+            may_compute: set_diff(&self.may_compute, &other.may_compute),
+            site_has_asset: set_diff(&self.site_has_asset, &other.site_has_asset),
+            do_compute: SetDiff {
+                added: compute_diff(self, other),
+                removed: compute_diff(other, self),
+            },
+            links: set_diff(
+                self.links.as_ref().unwrap_or(&Default::default()),
+                other.links.as_ref().unwrap_or(&Default::default()),
+            ),
+            links_restricted: match (&self.links, &other.links) {
+                (None, Some(_)) => Some(true),
+                (Some(_), None) => Some(false),
+                _ => None,
+            },
+            immovable: set_diff(&self.immovable, &other.immovable),
+        }
+    }
+}
+
+/// Render the problem's compute dependency graph in the DOT language, e.g., for Graphviz.
+/// Computes are nodes, labeled as by `ComputeArgs`'s `Display`, with an edge to each compute
+/// from the computes producing its needed assets. Initially held assets are boxed source nodes.
+/// Optional inputs are dashed, and cycles show as such.
+pub fn to_dot(problem: &Problem) -> String {
+    use std::fmt::Write as _;
+    let initial: BTreeSet<AssetId> =
+        problem.site_has_asset.iter().map(|&(_, asset_id)| asset_id).collect();
+    let mut dot = String::from("digraph problem {\n");
+    for asset_id in initial.iter() {
+        writeln!(dot, "  a{} [label=\"asset {}\", shape=box];", asset_id.0, asset_id.0).unwrap();
+    }
+    for (i, compute_args) in problem.do_compute.iter().enumerate() {
+        let label = compute_args.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+        writeln!(dot, "  c{} [label=\"{}\"];", i, label).unwrap();
+    }
+    for (i, compute_args) in problem.do_compute.iter().enumerate() {
+        let required = compute_args.inputs.iter().chain(compute_args.compute_assets());
+        let optional_candidates = compute_args.optional_candidates(problem.assets());
+        let needed = required
+            .map(|asset_id| (asset_id, "solid"))
+            .chain(optional_candidates.iter().map(|asset_id| (asset_id, "dashed")));
+        for (asset_id, style) in needed {
+            if initial.contains(asset_id) {
+                writeln!(dot, "  a{} -> c{} [style={}];", asset_id.0, i, style).unwrap();
+            }
+            let producers = problem.do_compute.iter().enumerate();
+            for (j, _) in producers.filter(|(_, producer)| producer.outputs.contains(asset_id)) {
+                let label = asset_id.0;
+                writeln!(dot, "  c{} -> c{} [label=\"{}\", style={}];", j, i, label, style)
+                    .unwrap();
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+impl<T> SetDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn set_diff<T: Copy + Eq + Hash>(old: &HashSet<T>, new: &HashSet<T>) -> SetDiff<T> {
+    SetDiff {
+        added: new.difference(old).copied().collect(),
+        removed: old.difference(new).copied().collect(),
+    }
+}
+
+impl ProblemDiff {
+    pub fn is_empty(&self) -> bool {
+        self.may_access.is_empty()
+            && self.may_compute.is_empty()
+            && self.site_has_asset.is_empty()
+            && self.do_compute.is_empty()
+            && self.links.is_empty()
+            && self.links_restricted.is_none()
+            && self.immovable.is_empty()
+    }
+}
+
+impl std::fmt::Display for ProblemDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pairs = [
+            ("may_access", &self.may_access),
+            ("may_compute", &self.may_compute),
+            ("site_has_asset", &self.site_has_asset),
+        ];
+        for (field, diff) in pairs.iter() {
+            for (sign, pairs) in [('+', &diff.added), ('-', &diff.removed)].iter() {
+                for (site_id, asset_id) in pairs.iter() {
+                    writeln!(f, "{} {} ({}, {:?})", sign, field, site_id.short(), asset_id)?;
+                }
+            }
+        }
+        for (sign, computes) in
+            [('+', &self.do_compute.added), ('-', &self.do_compute.removed)].iter()
+        {
+            for compute_args in computes.iter() {
+                writeln!(f, "{} do_compute {:?}", sign, compute_args)?;
+            }
+        }
+        match self.links_restricted {
+            Some(true) => writeln!(f, "links restricted to those listed")?,
+            Some(false) => writeln!(f, "links unrestricted")?,
+            None => {}
+        }
+        for (sign, links) in [('+', &self.links.added), ('-', &self.links.removed)].iter() {
+            for (from, to) in links.iter() {
+                writeln!(f, "{} links ({}, {})", sign, from.short(), to.short())?;
+            }
+        }
+        for (sign, assets) in [('+', &self.immovable.added), ('-', &self.immovable.removed)].iter()
+        {
+            for asset_id in assets.iter() {
+                writeln!(f, "{} immovable {:?}", sign, asset_id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site_ids(n: usize) -> Vec<SiteId> {
+        (0..n).map(|_| SiteId(Keypair::generate(&mut rand_core::OsRng).public)).collect()
+    }
+
+    fn compute(inputs: Vec<AssetId>, output: AssetId, compute_asset: AssetId) -> ComputeArgs {
+        ComputeArgs {
+            inputs,
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![output],
+            compute_asset,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: None,
+        }
+    }
+
+    #[test]
+    fn merged_plan_satisfies_both_problems() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, f, g] = [0, 1, 2, 3, 4].map(AssetId);
+        let first = Problem {
+            may_access: maplit::hashset! { (amy, x), (amy, y), (amy, f) },
+            may_compute: maplit::hashset! { (amy, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f) },
+            do_compute: vec![compute(vec![x], y, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        // Needs the output of the first problem, so it cannot be planned alone
+        let second = Problem {
+            may_access: maplit::hashset! { (bob, y), (bob, z), (bob, g) },
+            may_compute: maplit::hashset! { (bob, g) },
+            site_has_asset: maplit::hashset! { (bob, g) },
+            do_compute: vec![compute(vec![y], z, g)],
+            links: None,
+            immovable: Default::default(),
+        };
+        assert!(planning::plan(&second).is_err());
+        let computes: Vec<ComputeArgs> =
+            first.do_compute.iter().chain(second.do_compute.iter()).cloned().collect();
+        let merged = first.merge(second).unwrap();
+        let planned = planning::plan(&merged).unwrap();
+        assert!(planning::validate_plan(&planned, &merged).is_ok());
+        for (compute_args, site_id) in computes.iter().zip([amy, bob]) {
+            assert!(
+                planned[&site_id].contains(&Instruction::ComputeAssetData(compute_args.clone()))
+            );
+        }
+    }
+
+    #[test]
+    fn diff_reports_exactly_one_changed_permission() {
+        let (problem, _, _) = crate::scenario::amy_bob_cho(|_| Box::new(NullLogger));
+        let (bob, f) = *problem.may_compute.iter().next().unwrap();
+        let mut other = problem.clone();
+        other.may_compute.remove(&(bob, f));
+        let diff = problem.diff(&other);
+        assert_eq!(diff.to_string(), format!("- may_compute ({}, {:?})\n", bob.short(), f));
+        let unchanged =
+            ProblemDiff { may_compute: SetDiff { added: vec![], removed: vec![] }, ..diff };
+        assert!(unchanged.is_empty());
+        assert!(problem.diff(&problem).is_empty());
+    }
+
+    #[test]
+    fn malformed_computes_are_rejected() {
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        let valid = compute(vec![x], y, f);
+        assert_eq!(valid.validate(), Ok(()));
+        let cases = vec![
+            (ComputeArgs { inputs: vec![], ..valid.clone() }, ComputeArgsError::NoInputs),
+            (compute(vec![x, y], y, f), ComputeArgsError::InputIsOutput(y)),
+            (
+                ComputeArgs { optional_inputs: vec![y], ..valid.clone() },
+                ComputeArgsError::InputIsOutput(y),
+            ),
+            (compute(vec![x], f, f), ComputeArgsError::ComputeAssetIsOutput),
+            (
+                ComputeArgs { alt_compute_assets: vec![y], ..valid.clone() },
+                ComputeArgsError::ComputeAssetIsOutput,
+            ),
+            (compute(vec![x, x], y, f), ComputeArgsError::DuplicateAsset(x)),
+            (compute(vec![f], y, f), ComputeArgsError::DuplicateAsset(f)),
+            (
+                ComputeArgs { outputs: vec![y, y], ..valid.clone() },
+                ComputeArgsError::DuplicateAsset(y),
+            ),
+            (
+                ComputeArgs { reducible: true, outputs: vec![y, z], ..valid.clone() },
+                ComputeArgsError::ReducibleWithoutSingleOutput,
+            ),
+            (
+                ComputeArgs { optional_inputs: vec![z], min_optional_inputs: 2, ..valid.clone() },
+                ComputeArgsError::TooFewOptionalInputs,
+            ),
+        ];
+        for (compute_args, error) in cases {
+            assert_eq!(compute_args.validate(), Err(error), "{:?}", compute_args);
+        }
+    }
+
+    #[test]
+    fn amy_bob_cho_assets_and_sites_are_enumerated() {
+        let (problem, sites, _) = crate::scenario::amy_bob_cho(|_| Box::new(NullLogger));
+        let expected_assets: BTreeSet<AssetId> = (0..4).map(AssetId).collect();
+        assert_eq!(problem.assets(), expected_assets);
+        assert_eq!(problem.sites(), sites.keys().copied().collect());
+    }
+
+    #[test]
+    fn feasibility_report_flags_the_one_infeasible_compute() {
+        let site_ids = site_ids(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, w, f, g] = [0, 1, 2, 3, 4, 5].map(AssetId);
+        let problem = Problem {
+            may_access: maplit::hashset! {
+                (amy, x), (amy, y), (amy, f), (amy, g), (bob, x), (bob, w), (bob, y), (bob, z), (bob, f),
+            },
+            // bob may compute with g, but not access it
+            may_compute: maplit::hashset! { (amy, f), (bob, f), (bob, g) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f), (amy, g) },
+            // the second needs the output of the first
+            do_compute: vec![
+                compute(vec![x], y, f),
+                compute(vec![y], z, f),
+                compute(vec![x], w, g),
+            ],
+            links: None,
+            immovable: Default::default(),
+        };
+        let report = problem.feasibility_report();
+        assert_eq!(
+            report,
+            vec![
+                ComputeFeasibility::Feasible(&problem.do_compute[0]),
+                ComputeFeasibility::Feasible(&problem.do_compute[1]),
+                ComputeFeasibility::NoEligibleSite(&problem.do_compute[2]),
+            ]
+        );
+    }
+
+    #[test]
+    fn dot_of_a_two_stage_pipeline_links_the_stages() {
+        let amy = site_ids(1)[0];
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        let problem = Problem {
+            may_access: [x, y, z, f].iter().map(|&asset_id| (amy, asset_id)).collect(),
+            may_compute: maplit::hashset! { (amy, f) },
+            site_has_asset: maplit::hashset! { (amy, x), (amy, f) },
+            do_compute: vec![compute(vec![x], y, f), compute(vec![y], z, f)],
+            links: None,
+            immovable: Default::default(),
+        };
+        let dot = to_dot(&problem);
+        assert!(dot.starts_with("digraph problem {\n"));
+        // the second stage depends on the first via y
+        assert!(dot.contains("  c0 -> c1 [label=\"1\", style=solid];\n"));
+        assert!(!dot.contains("c1 -> c0"));
+        assert!(dot.contains("  a0 [label=\"asset 0\", shape=box];\n"));
+        assert!(dot.contains("  a0 -> c0 [style=solid];\n"));
+        assert!(dot.contains("  a3 -> c1 [style=solid];\n"));
+        assert!(!dot.contains("a1 ->"));
+    }
+}
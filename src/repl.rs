@@ -0,0 +1,89 @@
+use super::*;
+use std::io::BufRead;
+
+/// Advance the given sites one step at a time, as commanded by lines of `input`:
+/// `step` (each site steps once), `step N` (N times), `state` or `quit`.
+/// Each site's state is written to `output` after each step, and on `state`.
+pub fn run_repl(
+    sites: &mut HashMap<SiteId, Site>,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    let mut site_ids: Vec<SiteId> = sites.keys().copied().collect();
+    site_ids.sort();
+    let mut steps = 0;
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("step"), count) => match count.map(str::parse::<usize>).unwrap_or(Ok(1)) {
+                Ok(count) => {
+                    for _ in 0..count {
+                        steps += 1;
+                        writeln!(output, "step {}", steps)?;
+                        for site_id in site_ids.iter() {
+                            let site = sites.get_mut(site_id).unwrap();
+                            if let Err(e) = site.step() {
+                                writeln!(output, "site {} failed: {:?}", site_id.short(), e)?;
+                            }
+                        }
+                        write_state(sites, &site_ids, &mut output)?;
+                    }
+                }
+                Err(_) => writeln!(output, "usage: step [N]")?,
+            },
+            (Some("state"), None) => write_state(sites, &site_ids, &mut output)?,
+            (Some("quit"), None) => return Ok(()),
+            (None, _) => {}
+            _ => writeln!(output, "unknown command. Try `step`, `step N`, `state` or `quit`")?,
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+fn write_state(
+    sites: &HashMap<SiteId, Site>,
+    site_ids: &[SiteId],
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    for site_id in site_ids {
+        let summary = sites[site_id].pending_summary().replace('\n', "\n    ");
+        writeln!(output, "  site {}: {}", site_id.short(), summary)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted_commands_step_the_scenario_to_completion() {
+        let (problem, sites, initial_data) = crate::scenario::amy_bob_cho(|_| Box::new(NullLogger));
+        let mut prepared = crate::scenario::prepare(&problem, sites, initial_data).unwrap();
+        let script = "state\nstep\nstep 9\nstep x\nfly\nquit\nstep\n";
+        let mut output = vec![];
+        run_repl(&mut prepared.sites, script.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        // Each state lists every site, as the number of instructions it has to do. The first
+        // follows the prompt.
+        let todo_counts: Vec<usize> = output
+            .lines()
+            .filter_map(|line| line.split("  site ").nth(1))
+            .map(|line| line.split(": ").nth(1).unwrap())
+            .map(|line| line.split(' ').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(todo_counts.len(), 3 * 11);
+        assert!(todo_counts[..3].iter().sum::<usize>() > 0);
+        assert_eq!(todo_counts[30..].iter().sum::<usize>(), 0);
+        assert!(output.contains("step 10\n"));
+        assert!(!output.contains("step 11\n"), "steps after `quit` are ignored");
+        assert!(output.contains("usage: step [N]"));
+        assert!(output.contains("unknown command"));
+        assert!(prepared.sites.values().all(|site| site.todo_instructions.is_empty()));
+    }
+}
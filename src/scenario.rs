@@ -1,18 +1,30 @@
+// Scenario errors carry (large) `SiteId`s, but occur at most once per scenario
+#![allow(clippy::result_large_err)]
+
 use super::*;
 
-pub fn scenario_amy_bob_cho() {
+pub fn scenario_amy_bob_cho() -> Result<RunReport, ScenarioError> {
     // Setup the network
-    std::fs::create_dir_all("./logs").expect("creating logging dir");
-    let loggers = vec![
-        FileLogger::new("./logs/amy.txt"),
-        FileLogger::new("./logs/bob.txt"),
-        FileLogger::new("./logs/cho.txt"),
-    ];
-    let (site_ids, mut sites) = crate::site::new_sites(loggers);
-    let [amy, bob, cho]: [SiteId; 3] = std::convert::TryInto::try_into(site_ids).expect("wah");
-
-    // Do the planning
-    println!("Site Ids {:?}", [amy, bob, cho]);
+    std::fs::create_dir_all("./logs").map_err(ScenarioError::Io)?;
+    scenario_amy_bob_cho_with_loggers(|name| FileLogger::new(format!("./logs/{}.txt", name)))
+}
+
+/// As `scenario_amy_bob_cho`, but with loggers given by `make_logger` for each site's name
+pub fn scenario_amy_bob_cho_with_loggers(
+    make_logger: impl FnMut(&str) -> Box<dyn Logger>,
+) -> Result<RunReport, ScenarioError> {
+    let (problem, sites, initial_data) = amy_bob_cho(make_logger);
+    run(&problem, sites, initial_data)
+}
+
+/// The problem of the amy, bob and cho scenario, with its sites and their initial data
+pub fn amy_bob_cho(
+    mut make_logger: impl FnMut(&str) -> Box<dyn Logger>,
+) -> (Problem, HashMap<SiteId, Site>, InitialData) {
+    const NAMES: [&str; 3] = ["amy", "bob", "cho"];
+    let (site_ids, sites) =
+        crate::site::new_sites_with_logger_factory(NAMES.len(), |i| make_logger(NAMES[i]));
+    let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
 
     let x = AssetId(0);
     let y = AssetId(1);
@@ -27,30 +39,466 @@ pub fn scenario_amy_bob_cho() {
         },
         may_compute: maplit::hashset! { (bob, f) },
         site_has_asset: maplit::hashset! { (amy, x), (bob, y) , (cho, f)  },
-        do_compute: vec![ComputeArgs { inputs: vec![x, y], outputs: vec![z], compute_asset: f }],
+        do_compute: vec![ComputeArgs {
+            inputs: vec![x, y],
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![z],
+            compute_asset: f,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: None,
+        }],
+        links: None,
+        immovable: Default::default(),
     };
-    let planned = planning::plan(&problem).unwrap();
-    println!("planned: {:#?}\n------------------", &planned);
+    let initial_data = maplit::hashmap! {
+        (amy, x) => AssetData::from(0xDEADBEEF),
+        (bob, y) => AssetData::from(0xD00DEEDADA),
+        (cho, f) => AssetData::from(0xC0FEFE),
+    };
+    (problem, sites, initial_data)
+}
 
-    // setup the network
-    println!("sites: {:#?}", &sites);
-    println!("--------------------------------------");
+pub fn scenario_diamond() -> Result<RunReport, ScenarioError> {
+    std::fs::create_dir_all("./logs").map_err(ScenarioError::Io)?;
+    scenario_diamond_with_loggers(|name| FileLogger::new(format!("./logs/{}.txt", name)))
+}
 
-    // give the sites their planned instructions
-    for (site_id, instructions) in planned {
-        sites.get_mut(&site_id).unwrap().todo_instructions.extend(instructions)
-    }
+/// As `scenario_diamond`, but with loggers given by `make_logger` for each site's name
+pub fn scenario_diamond_with_loggers(
+    make_logger: impl FnMut(&str) -> Box<dyn Logger>,
+) -> Result<RunReport, ScenarioError> {
+    let (problem, sites, initial_data) = diamond(make_logger);
+    run(&problem, sites, initial_data)
+}
 
-    // give them their initial data
-    sites.get_mut(&amy).unwrap().inner.asset_store.insert(x, AssetData { bits: 0xDEADBEEF });
-    sites.get_mut(&bob).unwrap().inner.asset_store.insert(y, AssetData { bits: 0xD00DEEDADA });
-    sites.get_mut(&cho).unwrap().inner.asset_store.insert(f, AssetData { bits: 0xC0FEFE });
+/// A diamond of computes: `src` holds `a`, from which `left` and `right` each compute an
+/// intermediate in parallel. Both intermediates are sent to `sink`, which computes `d` from them.
+pub fn diamond(
+    mut make_logger: impl FnMut(&str) -> Box<dyn Logger>,
+) -> (Problem, HashMap<SiteId, Site>, InitialData) {
+    const NAMES: [&str; 4] = ["src", "left", "right", "sink"];
+    let (site_ids, sites) =
+        crate::site::new_sites_with_logger_factory(NAMES.len(), |i| make_logger(NAMES[i]));
+    let [src, left, right, sink] = [site_ids[0], site_ids[1], site_ids[2], site_ids[3]];
+
+    let [a, b, c, d] = [AssetId(0), AssetId(1), AssetId(2), AssetId(3)];
+    let [f, g, h] = [AssetId(4), AssetId(5), AssetId(6)];
+    let compute = |inputs: Vec<AssetId>, output: AssetId, compute_asset: AssetId| ComputeArgs {
+        inputs,
+        optional_inputs: vec![],
+        min_optional_inputs: 0,
+        input_range: None,
+        outputs: vec![output],
+        compute_asset,
+        alt_compute_assets: vec![],
+        consume_inputs: false,
+        reducible: false,
+        weight: 1,
+        label: None,
+        result_site: None,
+    };
+    let problem = Problem {
+        may_access: maplit::hashset! {
+            (src, a),
+            (left, a), (left, f), (left, b),
+            (right, a), (right, g), (right, c),
+            (sink, b), (sink, c), (sink, h), (sink, d),
+        },
+        may_compute: maplit::hashset! { (left, f), (right, g), (sink, h) },
+        site_has_asset: maplit::hashset! { (src, a), (left, f), (right, g), (sink, h) },
+        do_compute: vec![
+            compute(vec![a], b, f), // fan out ...
+            compute(vec![a], c, g),
+            compute(vec![b, c], d, h), // ... and back in
+        ],
+        links: None,
+        immovable: Default::default(),
+    };
+    let initial_data = maplit::hashmap! {
+        (src, a) => AssetData::from(0xA),
+        (left, f) => AssetData::from(0xF),
+        (right, g) => AssetData::from(0x6),
+        (sink, h) => AssetData::from(0x4),
+    };
+    (problem, sites, initial_data)
+}
+
+/// Plan the given problem and run it on the given sites until they are done, reporting the outcome.
+/// `initial_data` provides the data of each asset which the problem says a site initially has.
+pub fn run(
+    problem: &Problem,
+    sites: HashMap<SiteId, Site>,
+    initial_data: InitialData,
+) -> Result<RunReport, ScenarioError> {
+    let PreparedRun { mut sites, final_placement, goals, .. } =
+        prepare(problem, sites, initial_data)?;
+    if sites.values().all(|site| site.todo_instructions.is_empty()) {
+        // Every output already exists, so no site has anything to do
+        let sites =
+            sites.iter_mut().map(|(&site_id, site)| (site_id, site_report(site, Ok(())))).collect();
+        let elapsed = Duration::default();
+        return Ok(RunReport {
+            elapsed,
+            sites,
+            goals_met: goals,
+            goals_missed: Default::default(),
+        });
+    }
 
     // run the system
-    crossbeam_utils::thread::scope(|s| {
-        for site in sites.values_mut() {
-            s.spawn(move |_| site.execute());
-        }
+    let start = Instant::now();
+    let mut results: HashMap<SiteId, Result<(), ExecError>> = crossbeam_utils::thread::scope(|s| {
+        let handles: Vec<_> = sites
+            .iter_mut()
+            .map(|(&site_id, site)| (site_id, s.spawn(move |_| site.execute())))
+            .collect();
+        handles.into_iter().map(|(site_id, handle)| (site_id, handle.join().unwrap())).collect()
     })
     .unwrap();
+    let elapsed = start.elapsed();
+
+    // check that the sites ended up where planning predicted
+    for &(site_id, asset_id) in final_placement.iter() {
+        let site = sites.get(&site_id).ok_or(ScenarioError::UnknownSite(site_id))?;
+        if !site.has_asset(&asset_id) {
+            return Err(ScenarioError::MissingFinalAsset { site_id, asset_id });
+        }
+    }
+
+    let (goals_met, goals_missed): (HashSet<_>, HashSet<_>) =
+        goals.into_iter().partition(|(site_id, asset_id)| {
+            sites.get(site_id).map(|site| site.has_asset(asset_id)).unwrap_or(false)
+        });
+    let sites: HashMap<SiteId, SiteReport> = sites
+        .iter_mut()
+        .map(|(&site_id, site)| {
+            (site_id, site_report(site, results.remove(&site_id).unwrap_or(Ok(()))))
+        })
+        .collect();
+    // Once every goal is met without failures, the protocol leaves no traffic behind
+    if goals_missed.is_empty() && sites.values().all(|site| site.result.is_ok()) {
+        for (&site_id, site) in sites.iter() {
+            if site.unhandled_messages > 0 {
+                let count = site.unhandled_messages;
+                return Err(ScenarioError::UnhandledMessages { site_id, count });
+            }
+        }
+    }
+    Ok(RunReport { elapsed, sites, goals_met, goals_missed })
+}
+
+fn site_report(site: &mut Site, result: Result<(), ExecError>) -> SiteReport {
+    SiteReport {
+        result,
+        held_assets: site.held_assets(),
+        remaining_instructions: site.todo_instructions.len(),
+        unhandled_messages: site.drain_inbox().len(),
+        metrics: site.metrics().clone(),
+        access_log: site.take_access_log(),
+    }
+}
+
+impl RunReport {
+    pub fn all_goals_met(&self) -> bool {
+        self.goals_missed.is_empty()
+    }
+    pub fn total_messages_sent(&self) -> u64 {
+        self.sites.values().map(|site| site.metrics.messages_sent).sum()
+    }
+    pub fn total_bytes_sent(&self) -> u64 {
+        self.sites.values().map(|site| site.metrics.bytes_sent).sum()
+    }
+    pub fn total_computes(&self) -> u64 {
+        self.sites.values().map(|site| site.metrics.computes).sum()
+    }
+}
+
+impl std::fmt::Display for RunReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Ran for {:?}: {} messages ({} bytes) sent, {} computes, {}/{} goals met",
+            self.elapsed,
+            self.total_messages_sent(),
+            self.total_bytes_sent(),
+            self.total_computes(),
+            self.goals_met.len(),
+            self.goals_met.len() + self.goals_missed.len(),
+        )?;
+        let mut site_ids: Vec<&SiteId> = self.sites.keys().collect();
+        site_ids.sort();
+        for site_id in site_ids {
+            let site = &self.sites[site_id];
+            let held: Vec<u32> = site.held_assets.iter().map(|asset_id| asset_id.0).collect();
+            write!(
+                f,
+                "Site {} holds assets {:?} with {} instructions left",
+                site_id.short(),
+                held,
+                site.remaining_instructions
+            )?;
+            let metrics = &site.metrics;
+            if metrics.total_re_requests() > 0 || metrics.retransmissions > 0 {
+                write!(
+                    f,
+                    ". Re-requested {} times and retransmitted {} times",
+                    metrics.total_re_requests(),
+                    metrics.retransmissions
+                )?;
+            }
+            if site.unhandled_messages > 0 {
+                write!(f, ". Left {} messages unhandled", site.unhandled_messages)?;
+            }
+            if let Err(e) = &site.result {
+                write!(f, ". Failed: {:?}", e)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plan the given problem, and give the sites their instructions and initial data, ready to run.
+pub fn prepare(
+    problem: &Problem,
+    mut sites: HashMap<SiteId, Site>,
+    mut initial_data: InitialData,
+) -> Result<PreparedRun, ScenarioError> {
+    problem.validate().map_err(|e| ScenarioError::InvalidProblem(format!("{:?}", e)))?;
+    let (planned, final_placement) = planning::plan_with_placement(problem)
+        .map_err(|e| ScenarioError::PlanFailed(format!("{:?}", e)))?;
+    planning::validate_plan(&planned, problem)
+        .map_err(|e| ScenarioError::PlanFailed(format!("{:?}", e)))?;
+    // sites only serve requests for assets the requester may access
+    let mut access_control =
+        AccessControl::from_may_access(&problem.may_access, Arc::new(SystemClock));
+    // ... and for intermediate assets introduced by planning (e.g. partial reductions)
+    let problem_assets = problem.assets();
+    for (site_id, instructions) in planned.iter() {
+        for ins in instructions.iter() {
+            if let Instruction::AcquireAssetFrom { asset_id, .. } = ins {
+                if !problem_assets.contains(asset_id) {
+                    access_control.grant(*site_id, *asset_id, None);
+                }
+            }
+        }
+    }
+    for site in sites.values_mut() {
+        site.set_access_control(Some(access_control.clone()));
+        site.set_may_compute(problem.may_compute.clone());
+    }
+
+    // watch for the sites reaching the goals of the plan
+    let goals = planning::goals(&planned);
+    let completion_watcher = CompletionWatcher::new(goals.iter().copied());
+    for (site_id, site) in sites.iter_mut() {
+        site.set_completion_watcher(completion_watcher.clone());
+        site.set_goals(goals.iter().filter(|(s, _)| s == site_id).map(|&(_, asset_id)| asset_id));
+    }
+
+    // give the sites their planned instructions, signed such that they can check their origin
+    let planner_keypair = Keypair::generate(&mut rand_core::OsRng);
+    for (site_id, instructions) in planned.clone() {
+        let site = sites.get_mut(&site_id).ok_or(ScenarioError::UnknownSite(site_id))?;
+        site.set_planner_key(Some(planner_keypair.public));
+        let signed = SignedInstructions::sign(&planner_keypair, site_id, instructions);
+        site.accept_instructions(signed).map_err(ScenarioError::InstructionsRejected)?;
+    }
+
+    // give them their initial data
+    for &(site_id, asset_id) in problem.site_has_asset.iter() {
+        let asset_data = initial_data
+            .remove(&(site_id, asset_id))
+            .ok_or(ScenarioError::MissingInitialData { site_id, asset_id })?;
+        let site = sites.get_mut(&site_id).ok_or(ScenarioError::UnknownSite(site_id))?;
+        site.insert_asset(asset_id, asset_data);
+    }
+    Ok(PreparedRun { sites, planned, final_placement, goals, completion_watcher })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn missing_initial_data_is_reported() {
+        let (problem, sites, mut initial_data) = amy_bob_cho(|_| Box::new(NullLogger));
+        let x = AssetId(0);
+        let (amy, _) =
+            *problem.site_has_asset.iter().find(|&&(_, asset_id)| asset_id == x).unwrap();
+        initial_data.remove(&(amy, x));
+        match run(&problem, sites, initial_data) {
+            Err(ScenarioError::MissingInitialData { site_id, asset_id }) => {
+                assert_eq!((site_id, asset_id), (amy, x))
+            }
+            other => panic!("expected missing initial data, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn predicted_placement_matches_execution() {
+        let (problem, sites, initial_data) = amy_bob_cho(|_| Box::new(NullLogger));
+        let (_, predicted) = planning::plan_with_placement(&problem).unwrap();
+        let report = run(&problem, sites, initial_data).unwrap();
+        let actual: Placement = report
+            .sites
+            .iter()
+            .flat_map(|(&site_id, site)| site.held_assets.iter().map(move |&a| (site_id, a)))
+            .collect();
+        assert_eq!(predicted, actual);
+    }
+
+    #[test]
+    fn shuffled_scans_reach_the_same_final_state() {
+        let final_states: Vec<BTreeMap<AssetId, BTreeSet<AssetId>>> = (0..5)
+            .map(|seed| {
+                let (problem, mut sites, initial_data) = amy_bob_cho(|_| Box::new(NullLogger));
+                for site in sites.values_mut() {
+                    site.set_scan_shuffle(true);
+                    site.set_seed(seed);
+                }
+                let report = run(&problem, sites, initial_data).unwrap();
+                assert!(report.all_goals_met());
+                // Site IDs differ between runs, so sites are told apart by their initial asset
+                problem
+                    .site_has_asset
+                    .iter()
+                    .map(|(site_id, asset_id)| {
+                        (*asset_id, report.sites[site_id].held_assets.clone())
+                    })
+                    .collect()
+            })
+            .collect();
+        assert!(final_states.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn plan_runs_from_its_json() {
+        let (problem, sites, initial_data) = amy_bob_cho(|_| Box::new(NullLogger));
+        let json = serde_json::to_string(&problem).unwrap();
+        let problem: Problem = serde_json::from_str(&json).unwrap();
+        let PreparedRun { mut sites, planned, goals, .. } =
+            prepare(&problem, sites, initial_data).unwrap();
+        let json = serde_json::to_string(&planned).unwrap();
+        let deserialized: HashMap<SiteId, Vec<Instruction>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, planned);
+        // the sites run the deserialized instructions in place of those they were given
+        for (site_id, site) in sites.iter_mut() {
+            site.todo_instructions = deserialized.get(site_id).cloned().unwrap_or_default();
+        }
+        crossbeam_utils::thread::scope(|s| {
+            for site in sites.values_mut() {
+                s.spawn(move |_| site.execute().unwrap());
+            }
+        })
+        .unwrap();
+        for (site_id, asset_id) in goals {
+            assert!(sites[&site_id].has_asset(&asset_id));
+        }
+    }
+
+    #[test]
+    fn standard_scenario_meets_all_goals() {
+        let report = scenario_amy_bob_cho_with_loggers(|_| Box::new(NullLogger)).unwrap();
+        assert!(report.all_goals_met());
+        assert!(!report.goals_met.is_empty());
+        assert!(report.sites.values().all(|site| site.result.is_ok()));
+        assert_eq!(report.total_computes(), 1);
+        assert!(report.total_messages_sent() > 0);
+    }
+
+    #[test]
+    fn diamond_output_lands_on_the_sink() {
+        let (problem, sites, initial_data) = diamond(|_| Box::new(NullLogger));
+        let [b, c, d, h] = [1, 2, 3, 6].map(AssetId);
+        // sink is the only site holding h, with which d is computed
+        let (sink, _) =
+            *problem.site_has_asset.iter().find(|&&(_, asset_id)| asset_id == h).unwrap();
+        let report = run(&problem, sites, initial_data).unwrap();
+        assert!(report.all_goals_met());
+        assert!(report.goals_met.contains(&(sink, d)));
+        assert!(report.sites[&sink].held_assets.is_superset(&maplit::btreeset! { b, c, d }));
+        assert_eq!(report.total_computes(), 3);
+    }
+
+    #[test]
+    fn completed_scenarios_leave_empty_inboxes() {
+        let report = scenario_amy_bob_cho_with_loggers(|_| Box::new(NullLogger)).unwrap();
+        assert!(report.all_goals_met());
+        assert!(report.sites.values().all(|site| site.unhandled_messages == 0));
+        let (problem, sites, initial_data) = diamond(|_| Box::new(NullLogger));
+        let report = run(&problem, sites, initial_data).unwrap();
+        assert!(report.all_goals_met());
+        assert!(report.sites.values().all(|site| site.unhandled_messages == 0));
+    }
+
+    #[test]
+    fn warm_started_problem_plans_nothing_and_completes_at_once() {
+        let (mut problem, sites, mut initial_data) = amy_bob_cho(|_| Box::new(NullLogger));
+        // every output is held already, by some site which may access it
+        for compute_args in problem.do_compute.clone().iter() {
+            for &output in compute_args.outputs.iter() {
+                let (site_id, _) =
+                    *problem.may_access.iter().find(|&&(_, asset_id)| asset_id == output).unwrap();
+                problem.site_has_asset.insert((site_id, output));
+                initial_data.insert((site_id, output), AssetData::from(0));
+            }
+        }
+        let planned = planning::plan(&problem).unwrap();
+        assert_eq!(planned.values().map(Vec::len).sum::<usize>(), 0);
+        let start = Instant::now();
+        let report = run(&problem, sites, initial_data).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(report.all_goals_met());
+        assert_eq!(report.total_computes(), 0);
+        assert_eq!(report.total_messages_sent(), 0);
+    }
+
+    #[test]
+    fn output_is_kept_at_its_result_site_only() {
+        let (site_ids, sites) =
+            crate::site::new_sites_with_logger_factory(2, |_| Box::new(NullLogger));
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, f] = [0, 1, 2].map(AssetId);
+        let compute_args = ComputeArgs {
+            inputs: vec![x],
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![y],
+            compute_asset: f,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: Some(amy),
+        };
+        // bob works on behalf of amy, who may access only the result
+        let problem = Problem {
+            may_access: maplit::hashset! { (bob, x), (bob, y), (bob, f), (amy, y) },
+            may_compute: maplit::hashset! { (bob, f) },
+            site_has_asset: maplit::hashset! { (bob, x), (bob, f) },
+            do_compute: vec![compute_args],
+            links: None,
+            immovable: Default::default(),
+        };
+        let initial_data: InitialData = maplit::hashmap! {
+            (bob, x) => AssetData::from(1),
+            (bob, f) => AssetData::from(2),
+        };
+        let report = run(&problem, sites, initial_data).unwrap();
+        assert!(report.all_goals_met());
+        assert!(report.goals_met.contains(&(amy, y)));
+        assert_eq!(report.sites[&amy].held_assets, maplit::btreeset! { y });
+        assert_eq!(report.sites[&bob].held_assets, maplit::btreeset! { x, f });
+        assert_eq!(report.sites[&bob].metrics.computes, 1);
+    }
 }
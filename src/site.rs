@@ -1,30 +1,321 @@
 use super::*;
+use rand::{rngs::SmallRng, SeedableRng};
 
 enum InsExecResult {
     Incomplete,
     Complete { added_assets_to_store: bool },
 }
 
+/// What woke a site waiting for messages
+#[allow(clippy::large_enum_variant)] // short-lived, as each is handled right away
+enum Received {
+    Msg(SignedMsg),
+    Command(SiteCommand), // see `SiteControl`
+    Timeout,
+}
+
+struct ScanResult {
+    completed: usize, // instructions completed
+    rescan: bool,     // earlier instructions may have become completable
+}
+
 //////////////////
 
 impl Msg {
+    /// Deterministic byte representation of this message, covered by its signature
+    pub fn to_signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.signing_len());
+        self.write_signing_bytes(&mut bytes);
+        bytes
+    }
+    /// Append the bytes of `to_signing_bytes` to `bytes`, e.g., to reuse its allocation
+    pub fn write_signing_bytes(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Msg::AssetDataRequest { asset_id, priority } => {
+                bytes.push(0);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.push(*priority);
+            }
+            Msg::AssetData { asset_id, asset_data } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.extend_from_slice(&asset_data.bytes);
+            }
+            Msg::Register { public_key } => {
+                bytes.push(2);
+                bytes.extend_from_slice(public_key.as_bytes());
+            }
+            Msg::AssetChunk { asset_id, index, count, chunk } => {
+                bytes.push(3);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.extend_from_slice(&index.to_le_bytes());
+                bytes.extend_from_slice(&count.to_le_bytes());
+                bytes.extend_from_slice(&chunk.bytes);
+            }
+            Msg::Subscribe { asset_id } => {
+                bytes.push(4);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+            }
+            Msg::Nack { asset_id, reason } => {
+                bytes.push(5);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.push(*reason as u8);
+            }
+            Msg::StealRequest => bytes.push(6),
+            Msg::Delegate { compute_args } => {
+                bytes.push(7);
+                compute_args.write_signing_bytes(bytes);
+            }
+            Msg::Heartbeat => bytes.push(8),
+        }
+    }
+    /// Length of `to_signing_bytes`, without building them
+    pub fn signing_len(&self) -> usize {
+        match self {
+            Msg::AssetDataRequest { .. } => 6,
+            Msg::AssetData { asset_data, .. } => {
+                Self::ASSET_DATA_HEADER_LEN + asset_data.bytes.len()
+            }
+            Msg::Register { .. } => 1 + ed25519_dalek::PUBLIC_KEY_LENGTH,
+            Msg::AssetChunk { chunk, .. } => Self::CHUNK_HEADER_LEN + chunk.bytes.len(),
+            Msg::Subscribe { .. } => 5,
+            Msg::Nack { .. } => 6,
+            Msg::StealRequest => 1,
+            Msg::Delegate { compute_args } => {
+                // Rarely sent, so measured by building its bytes
+                let mut bytes = vec![];
+                compute_args.write_signing_bytes(&mut bytes);
+                1 + bytes.len()
+            }
+            Msg::Heartbeat => 1,
+        }
+    }
+    /// Whether dropping this message merely delays progress, as requests are repeated until served
+    pub fn is_droppable(&self) -> bool {
+        matches!(
+            self,
+            Msg::AssetDataRequest { .. } | Msg::Nack { .. } | Msg::StealRequest | Msg::Heartbeat
+        )
+    }
+    const ASSET_DATA_HEADER_LEN: usize = 5;
+    const CHUNK_HEADER_LEN: usize = 13;
     pub fn sign(self, keypair: &Keypair) -> SignedMsg {
-        let signature = keypair.sign(any_as_u8_slice::<Msg>(&self));
-        SignedMsg { sender_public_key: keypair.public, signature, msg: self }
+        let signature = keypair.sign(&self.to_signing_bytes());
+        SignedMsg { sender_public_key: keypair.public, signature: Some(signature), msg: self }
+    }
+    /// As `sign`, but building the signed bytes in `buffer`, reusing its allocation
+    pub fn sign_with_buffer(self, keypair: &Keypair, buffer: &mut Vec<u8>) -> SignedMsg {
+        buffer.clear();
+        self.write_signing_bytes(buffer);
+        let signature = keypair.sign(buffer);
+        SignedMsg { sender_public_key: keypair.public, signature: Some(signature), msg: self }
+    }
+    /// Wrap this message without a signature, for sites in `untrusted_local` mode
+    pub fn unsigned(self, sender_public_key: PublicKey) -> SignedMsg {
+        SignedMsg { sender_public_key, signature: None, msg: self }
     }
 }
 impl SignedMsg {
     pub fn verify(&self) -> Result<(), ed25519::Error> {
-        self.sender_public_key.verify(any_as_u8_slice::<Msg>(&self.msg), &self.signature)
+        match &self.signature {
+            Some(signature) => {
+                self.sender_public_key.verify(&self.msg.to_signing_bytes(), signature)
+            }
+            None => Err(ed25519::Error::new()),
+        }
+    }
+    /// As `verify`, but building the signed bytes in `buffer`, reusing its allocation
+    pub fn verify_into(&self, buffer: &mut Vec<u8>) -> Result<(), ed25519::Error> {
+        match &self.signature {
+            Some(signature) => {
+                buffer.clear();
+                self.msg.write_signing_bytes(buffer);
+                self.sender_public_key.verify(buffer, signature)
+            }
+            None => Err(ed25519::Error::new()),
+        }
+    }
+    /// As `verify`, but also rejecting messages which break the invariants of those sites send
+    pub fn verify_structure(&self) -> Result<(), MsgRejection> {
+        self.verify().map_err(MsgRejection::BadSignature)?;
+        self.check_structure().map_err(MsgRejection::Malformed)
+    }
+    /// Checks the invariants of messages sent by sites, leaving the signature unchecked
+    pub fn check_structure(&self) -> Result<(), MalformedMsg> {
+        match &self.msg {
+            Msg::AssetChunk { index, count, .. } if index >= count => {
+                Err(MalformedMsg::ChunkIndexOutOfRange { index: *index, count: *count })
+            }
+            Msg::AssetChunk { chunk, .. } if chunk.bytes.is_empty() => {
+                Err(MalformedMsg::EmptyChunk)
+            }
+            Msg::Register { public_key } if *public_key != self.sender_public_key => {
+                Err(MalformedMsg::ForeignRegister)
+            }
+            _ => Ok(()),
+        }
     }
     pub fn sender(&self) -> &SiteId {
         SiteId::from_public_key_ref(&self.sender_public_key)
     }
+    /// Size of this message as it would be sent over a network: signed bytes, sender and signature
+    pub fn wire_len(&self) -> usize {
+        Self::OVERHEAD_LEN + self.msg.signing_len()
+    }
+    const OVERHEAD_LEN: usize = ed25519_dalek::PUBLIC_KEY_LENGTH + ed25519_dalek::SIGNATURE_LENGTH;
+}
+impl AssetRange {
+    pub fn contains(&self, asset_id: &AssetId) -> bool {
+        self.start <= *asset_id && *asset_id < self.end
+    }
 }
+
 impl ComputeArgs {
+    /// Append the deterministic byte representation of these arguments to `bytes`, as covered
+    /// by the signatures of plans and of `Msg::Delegate`
+    fn write_signing_bytes(&self, bytes: &mut Vec<u8>) {
+        fn push_ids(bytes: &mut Vec<u8>, ids: &[AssetId]) {
+            bytes.extend_from_slice(&(ids.len() as u32).to_le_bytes());
+            for asset_id in ids {
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+            }
+        }
+        push_ids(bytes, &self.inputs);
+        push_ids(bytes, &self.outputs);
+        bytes.extend_from_slice(&self.compute_asset.0.to_le_bytes());
+        push_ids(bytes, &self.alt_compute_assets);
+        bytes.push(self.consume_inputs as u8);
+        bytes.push(self.reducible as u8);
+        bytes.extend_from_slice(&self.weight.to_le_bytes());
+        push_ids(bytes, &self.optional_inputs);
+        bytes.extend_from_slice(&(self.min_optional_inputs as u64).to_le_bytes());
+        match &self.input_range {
+            Some(AssetRange { start, end }) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&start.0.to_le_bytes());
+                bytes.extend_from_slice(&end.0.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        let label = self.label.as_deref().unwrap_or_default();
+        bytes.extend_from_slice(&(label.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(label.as_bytes());
+        match &self.result_site {
+            Some(site_id) => {
+                bytes.push(1);
+                bytes.extend_from_slice(site_id.0.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+    }
     pub fn needed_assets(&self) -> impl Iterator<Item = &AssetId> + '_ {
         self.inputs.iter().chain(Some(&self.compute_asset))
     }
+    /// These args with only the optional inputs for which `present` holds
+    pub fn with_present_optionals(&self, present: impl Fn(&AssetId) -> bool) -> Self {
+        let optional_inputs = self.optional_inputs.iter().copied().filter(|a| present(a)).collect();
+        Self { optional_inputs, ..self.clone() }
+    }
+    /// The optional inputs, followed by those of the `known` assets which `input_range` adds,
+    /// in ascending order
+    pub fn optional_candidates(&self, known: impl IntoIterator<Item = AssetId>) -> Vec<AssetId> {
+        let mut candidates = self.optional_inputs.clone();
+        if self.input_range.is_some() {
+            let mut discovered: Vec<AssetId> =
+                known.into_iter().filter(|asset_id| self.in_input_range(asset_id)).collect();
+            discovered.sort();
+            discovered.dedup();
+            candidates.extend(discovered);
+        }
+        candidates
+    }
+    /// Whether `input_range` makes the given asset an optional input. Its own assets are excluded.
+    pub fn in_input_range(&self, asset_id: &AssetId) -> bool {
+        self.input_range.is_some_and(|range| range.contains(asset_id))
+            && !self.needed_assets().any(|needed| needed == asset_id)
+            && !self.optional_inputs.contains(asset_id)
+            && !self.alt_compute_assets.contains(asset_id)
+            && !self.outputs.contains(asset_id)
+    }
+    /// Whether the compute may be passed the given asset, as an input, optional input or compute asset
+    pub fn may_take(&self, asset_id: &AssetId) -> bool {
+        self.needed_assets().any(|needed| needed == asset_id)
+            || self.optional_inputs.contains(asset_id)
+            || self.in_input_range(asset_id)
+    }
+    /// The acceptable compute assets: `compute_asset` followed by its alternatives
+    pub fn compute_assets(&self) -> impl Iterator<Item = &AssetId> + '_ {
+        Some(&self.compute_asset).into_iter().chain(self.alt_compute_assets.iter())
+    }
+    /// These args with the given compute asset chosen, and no alternatives
+    pub fn with_compute_asset(&self, compute_asset: AssetId) -> Self {
+        Self { compute_asset, alt_compute_assets: vec![], ..self.clone() }
+    }
+}
+
+impl Instruction {
+    /// Deterministic byte representation of this instruction, covered by plan signatures
+    pub fn to_signing_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        match self {
+            Instruction::SendAssetTo { asset_id, site_id, priority } => {
+                bytes.push(0);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.extend_from_slice(site_id.0.as_bytes());
+                bytes.push(*priority);
+            }
+            Instruction::AcquireAssetFrom { asset_id, site_id, priority, timeout } => {
+                bytes.push(1);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.extend_from_slice(site_id.0.as_bytes());
+                bytes.push(*priority);
+                match timeout {
+                    Some(timeout) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&timeout.as_secs().to_le_bytes());
+                        bytes.extend_from_slice(&timeout.subsec_nanos().to_le_bytes());
+                    }
+                    None => bytes.push(0),
+                }
+            }
+            Instruction::ComputeAssetData(compute_args) => {
+                bytes.push(2);
+                compute_args.write_signing_bytes(&mut bytes);
+            }
+            Instruction::Verify { asset_id, expected_hash } => {
+                bytes.push(3);
+                bytes.extend_from_slice(&asset_id.0.to_le_bytes());
+                bytes.extend_from_slice(&expected_hash.to_le_bytes());
+            }
+        }
+        bytes
+    }
+}
+
+impl SignedInstructions {
+    /// Sign the given site's instructions with the planner's keypair
+    pub fn sign(
+        planner_keypair: &Keypair,
+        site_id: SiteId,
+        instructions: Vec<Instruction>,
+    ) -> Self {
+        let signature = planner_keypair.sign(&Self::signing_bytes(&site_id, &instructions));
+        Self { site_id, instructions, signature: Some(signature) }
+    }
+    pub fn verify(&self, planner_key: &PublicKey) -> Result<(), InstructionsRejected> {
+        let signature = self.signature.as_ref().ok_or(InstructionsRejected::Unsigned)?;
+        planner_key
+            .verify(&Self::signing_bytes(&self.site_id, &self.instructions), signature)
+            .map_err(InstructionsRejected::BadSignature)
+    }
+    fn signing_bytes(site_id: &SiteId, instructions: &[Instruction]) -> Vec<u8> {
+        let mut bytes = site_id.0.as_bytes().to_vec();
+        bytes.extend_from_slice(&(instructions.len() as u32).to_le_bytes());
+        for ins in instructions {
+            bytes.extend(ins.to_signing_bytes());
+        }
+        bytes
+    }
 }
 
 impl std::fmt::Debug for AssetId {
@@ -32,6 +323,124 @@ impl std::fmt::Debug for AssetId {
         f.debug_tuple("AssetId").field(&self.0).finish()
     }
 }
+impl std::fmt::Debug for AssetData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Assets may be large. Only show a short prefix of the data.
+        const PREFIX_LEN: usize = 8;
+        write!(f, "AssetData {{ len: {}, bytes: ", self.bytes.len())?;
+        for byte in self.bytes.iter().take(PREFIX_LEN) {
+            write!(f, "{:02X}", byte)?;
+        }
+        if self.bytes.len() > PREFIX_LEN {
+            write!(f, "..")?;
+        }
+        write!(f, " }}")
+    }
+}
+impl AssetData {
+    /// Hash of the data, identifying its content independently of any asset id
+    pub fn content_hash(&self) -> u64 {
+        use std::hash::Hasher;
+        let mut hasher = fnv::FnvHasher::default();
+        hasher.write(&self.bytes);
+        hasher.finish()
+    }
+    /// Reproducible data derived from the given asset id, e.g., for test fixtures
+    pub fn deterministic(asset_id: AssetId) -> Self {
+        use std::hash::Hasher;
+        let mut hasher = fnv::FnvHasher::default();
+        hasher.write(&asset_id.0.to_le_bytes());
+        Self::from(hasher.finish())
+    }
+}
+impl From<u64> for AssetData {
+    fn from(bits: u64) -> Self {
+        Self { bytes: bits.to_le_bytes().to_vec() }
+    }
+}
+impl std::fmt::Display for Grant {
+    /// E.g., "grant 1A:2B:3C:4D may_access 2"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Grant::MayAccess(site_id, asset_id) => {
+                write!(f, "grant {} may_access {}", site_id.short(), asset_id.0)
+            }
+            Grant::MayCompute(site_id, asset_id) => {
+                write!(f, "grant {} may_compute {}", site_id.short(), asset_id.0)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ComputeArgs {
+    /// E.g., "9(0,1)->2" computes output 2 from inputs 0 and 1 using compute asset 9.
+    /// Alternative compute assets are separated by bars, e.g., "9|8(0,1)->2".
+    /// Optional inputs follow the inputs, marked with question marks, e.g., "9(0,1,?3)->2".
+    /// Labeled computes are prefixed with their label, e.g., "sum: 9(0,1)->2".
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_ids(f: &mut std::fmt::Formatter<'_>, ids: &[AssetId]) -> std::fmt::Result {
+            for (i, asset_id) in ids.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", asset_id.0)?;
+            }
+            Ok(())
+        }
+        if let Some(label) = &self.label {
+            write!(f, "{}: ", label)?;
+        }
+        write!(f, "{}", self.compute_asset.0)?;
+        for alt in self.alt_compute_assets.iter() {
+            write!(f, "|{}", alt.0)?;
+        }
+        write!(f, "(")?;
+        write_ids(f, &self.inputs)?;
+        for asset_id in self.optional_inputs.iter() {
+            write!(f, ",?{}", asset_id.0)?;
+        }
+        if let Some(AssetRange { start, end }) = &self.input_range {
+            write!(f, ",?{}..{}", start.0, end.0)?;
+        }
+        write!(f, ")->")?;
+        write_ids(f, &self.outputs)?;
+        if self.min_optional_inputs > 0 {
+            write!(f, " given {}+ optional inputs", self.min_optional_inputs)?;
+        }
+        if self.consume_inputs {
+            write!(f, " consuming inputs")?;
+        }
+        if let Some(result_site) = &self.result_site {
+            write!(f, " kept at site {}", result_site.short())?;
+        }
+        Ok(())
+    }
+}
+impl std::fmt::Display for Instruction {
+    /// One-line summary for logs. See `Debug` for full detail.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::SendAssetTo { asset_id, site_id, .. } => {
+                write!(f, "SEND asset {} -> site {}", asset_id.0, site_id.short())
+            }
+            Instruction::AcquireAssetFrom { asset_id, site_id, .. } => {
+                write!(f, "ACQUIRE asset {} <- site {}", asset_id.0, site_id.short())
+            }
+            Instruction::ComputeAssetData(compute_args) => write!(f, "COMPUTE {}", compute_args),
+            Instruction::Verify { asset_id, expected_hash } => {
+                write!(f, "VERIFY asset {} has hash {:016X}", asset_id.0, expected_hash)
+            }
+        }
+    }
+}
+impl std::fmt::Display for ComputeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.input {
+            Some(input) => write!(f, "{} (input {})", self.message, input.0),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
 impl std::fmt::Debug for SiteId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for byte in self.0.as_bytes() {
@@ -42,12 +451,47 @@ impl std::fmt::Debug for SiteId {
 }
 
 impl SiteId {
-    pub(crate) fn from_public_key_ref(public_key: &PublicKey) -> &Self {
+    /// RNG seeded by both `seed` and this ID, such that sites sharing `seed` still differ
+    fn derive_seed(&self, seed: u64) -> SmallRng {
+        use std::hash::Hasher;
+        let mut hasher = fnv::FnvHasher::default();
+        hasher.write_u64(seed);
+        hasher.write(self.0.as_bytes());
+        SmallRng::seed_from_u64(hasher.finish())
+    }
+    /// Compact identifier for routine log lines: the first 4 bytes of the public key in hex,
+    /// colon-separated, such that it prefixes `full`
+    pub fn short(&self) -> String {
+        Self::hex(&self.0.as_bytes()[..4])
+    }
+    /// Complete identifier: all bytes of the public key in hex, colon-separated
+    pub fn full(&self) -> String {
+        Self::hex(self.0.as_bytes())
+    }
+    fn hex(bytes: &[u8]) -> String {
+        let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        bytes.join(":")
+    }
+    pub fn from_public_key_ref(public_key: &PublicKey) -> &Self {
         unsafe {
             //safe! SiteId is a transparent newtype for PublicKey
             core::mem::transmute(public_key)
         }
     }
+    /// The bytes of the public key
+    pub fn as_bytes(&self) -> &[u8; ed25519_dalek::PUBLIC_KEY_LENGTH] {
+        self.0.as_bytes()
+    }
+}
+impl From<PublicKey> for SiteId {
+    fn from(public_key: PublicKey) -> Self {
+        Self(public_key)
+    }
+}
+impl From<&Keypair> for SiteId {
+    fn from(keypair: &Keypair) -> Self {
+        Self(keypair.public)
+    }
 }
 impl Hash for SiteId {
     fn hash<H: core::hash::Hasher>(&self, h: &mut H) {
@@ -55,205 +499,4101 @@ impl Hash for SiteId {
     }
 }
 
-pub(crate) fn new_sites(loggers: Vec<Box<dyn Logger>>) -> (Vec<SiteId>, HashMap<SiteId, Site>) {
-    struct Parts {
-        inbox: Receiver<SignedMsg>,
-        logger: Box<dyn Logger>,
-        keypair: Keypair,
+/// Serialized as the hex string of the public key, such that site ids can key maps, e.g., in JSON
+impl Serialize for SiteId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = self.0.as_bytes().iter().map(|byte| format!("{:02X}", byte)).collect();
+        serializer.serialize_str(&hex)
     }
-    let mut outboxes = HashMap::default();
-    let mut parts = vec![];
-    let mut site_ids = vec![];
-    for logger in loggers {
-        let keypair = Keypair::generate(&mut rand_core::OsRng);
-        let (outbox, inbox) = crossbeam_channel::unbounded();
-        let site_id = *SiteId::from_public_key_ref(&keypair.public);
+}
+impl<'de> Deserialize<'de> for SiteId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != 2 * ed25519_dalek::PUBLIC_KEY_LENGTH || !hex.is_ascii() {
+            return Err(D::Error::custom("expected a hex-encoded ed25519 public key"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(D::Error::custom)?;
+        // Not every 32 bytes are a valid public key
+        PublicKey::from_bytes(&bytes).map(SiteId).map_err(D::Error::custom)
+    }
+}
+impl PartialOrd for SiteId {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for SiteId {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.as_bytes().cmp(other.0.as_bytes())
+    }
+}
 
-        outboxes.insert(site_id, outbox);
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout: Duration::from_secs(1),
+            quiescence_timeout: Duration::from_secs(1),
+            untrusted_local: false,
+            channel_capacity: None,
+            watchdog_interval: None,
+            max_msg_bytes: None,
+            goal_grace: None,
+            asset_ttl: None,
+            stream_compute_outputs: false,
+            request_jitter: 0.,
+            seed: None,
+            sender_policy: SenderPolicy::AnySigner,
+            reply_policy: ReplyPolicy::LatestWins,
+            evict_when_inbox_full: false,
+            await_all_goals: false,
+            heaviest_computes_first: false,
+            prefetch_inputs: true,
+            verify_on_thread: false,
+            log_initial_instructions: false,
+            check_msg_structure: false,
+            audit_access: false,
+            work_stealing: false,
+            share_identical_content: false,
+            heartbeat_interval: None,
+        }
+    }
+}
+
+pub fn new_sites(loggers: Vec<Box<dyn Logger>>) -> (Vec<SiteId>, HashMap<SiteId, Site>) {
+    let keypairs = loggers.iter().map(|_| Keypair::generate(&mut rand_core::OsRng)).collect();
+    build_sites(keypairs, loggers, &SiteConfig::default())
+}
+
+/// As `new_sites`, but calls `make_logger` for the logger of each site index in `0..num_sites`
+pub fn new_sites_with_logger_factory(
+    num_sites: usize,
+    make_logger: impl FnMut(usize) -> Box<dyn Logger>,
+) -> (Vec<SiteId>, HashMap<SiteId, Site>) {
+    new_sites((0..num_sites).map(make_logger).collect())
+}
+
+/// As `new_sites`, but configured by `config`, which is validated first
+pub fn new_sites_with_config(
+    loggers: Vec<Box<dyn Logger>>,
+    config: &SiteConfig,
+) -> Result<(Vec<SiteId>, HashMap<SiteId, Site>), NewSitesError> {
+    config.validate().map_err(NewSitesError::InvalidConfig)?;
+    let keypairs = loggers.iter().map(|_| Keypair::generate(&mut rand_core::OsRng)).collect();
+    Ok(build_sites(keypairs, loggers, config))
+}
+
+/// Creates sites with the given identities (e.g. loaded from disk) rather than fresh ones.
+/// The returned `SiteId`s are the keypairs' public keys, in the given order.
+pub fn new_sites_with_keypairs(
+    keypairs: Vec<Keypair>,
+    loggers: Vec<Box<dyn Logger>>,
+) -> Result<(Vec<SiteId>, HashMap<SiteId, Site>), NewSitesError> {
+    if keypairs.len() != loggers.len() {
+        let (keypairs, loggers) = (keypairs.len(), loggers.len());
+        return Err(NewSitesError::KeypairCountMismatch { keypairs, loggers });
+    }
+    Ok(build_sites(keypairs, loggers, &SiteConfig::default()))
+}
+
+fn build_sites(
+    keypairs: Vec<Keypair>,
+    loggers: Vec<Box<dyn Logger>>,
+    config: &SiteConfig,
+) -> (Vec<SiteId>, HashMap<SiteId, Site>) {
+    let coordinator = Coordinator { outboxes: Default::default(), config: config.clone() };
+    let mut site_ids = vec![];
+    let mut sites = HashMap::default();
+    for (keypair, logger) in keypairs.into_iter().zip(loggers) {
+        let site_id = SiteId::from(&keypair);
         site_ids.push(site_id);
-        parts.push(Parts { inbox, logger, keypair });
-    }
-    let outboxes = Arc::new(outboxes);
-    let sites = parts
-        .into_iter()
-        .map(|Parts { inbox, logger, keypair }| {
-            let site_id = *SiteId::from_public_key_ref(&keypair.public);
-            let site = Site {
-                inner: SiteInner {
-                    keypair,
-                    logger,
-                    outboxes: outboxes.clone(),
-                    inbox,
-                    last_requested_at: Default::default(),
-                    asset_store: Default::default(),
-                },
-                todo_instructions: Default::default(), // todo
-            };
-            (site_id, site)
-        })
-        .collect();
+        sites.insert(site_id, coordinator.create_site(keypair, logger));
+    }
     (site_ids, sites)
 }
 
-fn actual_compute(
-    store: &HashMap<AssetId, AssetData>,
-    compute_args: &ComputeArgs,
-) -> Option<HashMap<AssetId, AssetData>> {
-    let mut hasher = fnv::FnvHasher::default();
-    use std::hash::Hasher;
-    for needed_asset in compute_args.needed_assets() {
-        hasher.write_u64(store.get(needed_asset)?.bits);
+impl SiteConfig {
+    /// Check for settings with which sites could not work
+    pub fn validate(&self) -> Result<(), SiteConfigError> {
+        if let Some(max_msg_bytes) = self.max_msg_bytes {
+            // Every chunk carries at least one byte, and sites must announce themselves
+            let min = SignedMsg::OVERHEAD_LEN
+                + (Msg::CHUNK_HEADER_LEN + 1).max(1 + ed25519_dalek::PUBLIC_KEY_LENGTH);
+            if max_msg_bytes < min {
+                return Err(SiteConfigError::MaxMsgBytesTooSmall { max_msg_bytes, min });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SiteMetrics {
+    pub fn total_re_requests(&self) -> u64 {
+        self.re_requests.values().sum()
+    }
+}
+
+impl Coordinator {
+    /// Creates a site with the given identity, reachable by (and able to reach) all others
+    fn create_site(&self, keypair: Keypair, logger: Box<dyn Logger>) -> Site {
+        let (outbox, inbox) = match self.config.channel_capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+        let site_id = SiteId::from(&keypair);
+        self.outboxes.write().unwrap().insert(site_id, outbox);
+        let mut seeder = match self.config.seed {
+            Some(seed) => site_id.derive_seed(seed),
+            None => SmallRng::from_entropy(),
+        };
+        let (command_sender, commands) = crossbeam_channel::unbounded();
+        let asset_store: Box<dyn AssetStore> = if self.config.share_identical_content {
+            Box::new(SharedContentStore::default())
+        } else {
+            Box::new(HashMap::<AssetId, AssetData>::default())
+        };
+        Site {
+            inner: SiteInner {
+                keypair,
+                logger,
+                config: self.config.clone(),
+                outboxes: self.outboxes.clone(),
+                inbox,
+                inbox_buffer: Default::default(),
+                inbox_verified: false,
+                commands,
+                command_sender,
+                signing_buffer: Default::default(),
+                compute_fn: Some(Arc::new(<HashCompute>::default())),
+                compute_fns: Default::default(),
+                rate_limiter: None,
+                completion_watcher: None,
+                progress: Default::default(),
+                pause: Default::default(),
+                access_control: None,
+                request_again_at: Default::default(),
+                request_jitter_rng: SmallRng::from_rng(&mut seeder).unwrap(),
+                seeder,
+                nacked: Default::default(),
+                full_outboxes: Default::default(),
+                scan_shuffle: None,
+                sent_assets: Default::default(),
+                partial_assets: Default::default(),
+                chunks_sent: Default::default(),
+                metrics: Default::default(),
+                subscribers: Default::default(),
+                stale_subscribers: Default::default(),
+                goals: Default::default(),
+                planner_key: None,
+                last_accessed_at: Default::default(),
+                acquired_from: Default::default(),
+                acquiring_since: Default::default(),
+                access_log: Default::default(),
+                may_compute: Default::default(),
+                steal_asked: Default::default(),
+                steal_asked_at: None,
+                heartbeat_sent_at: None,
+                last_heard: Default::default(),
+                suspected: Default::default(),
+                failure_watcher: None,
+                results_elsewhere: Default::default(),
+                asset_store,
+                #[cfg(feature = "tracing")]
+                span: tracing::info_span!("site", id = %site_id.short()),
+            },
+            todo_instructions: Default::default(), // todo
+        }
+    }
+    /// Adds a new site to the network, whose sites may already be executing.
+    /// Each existing site is sent a `Msg::Register` from the new site announcing it.
+    pub fn join(&self, keypair: Keypair, logger: Box<dyn Logger>) -> (SiteId, Site) {
+        let site_id = SiteId::from(&keypair);
+        let site = self.create_site(keypair, logger);
+        for (dest_id, outbox) in self.outboxes.read().unwrap().iter() {
+            if *dest_id == site_id {
+                continue;
+            }
+            let msg = Msg::Register { public_key: site.inner.keypair.public };
+            let signed_msg = if self.config.untrusted_local {
+                msg.unsigned(site.inner.keypair.public)
+            } else {
+                msg.sign(&site.inner.keypair)
+            };
+            // Existing sites never drop their inboxes
+            outbox.send(signed_msg).unwrap();
+        }
+        (site_id, site)
+    }
+    /// All the sites in the network so far
+    pub fn site_ids(&self) -> Vec<SiteId> {
+        self.outboxes.read().unwrap().keys().copied().collect()
+    }
+}
+
+impl<H> Default for HashCompute<H> {
+    fn default() -> Self {
+        Self { sort_inputs: false, phantom: PhantomData }
+    }
+}
+impl<H> HashCompute<H> {
+    /// As `default`, but hashing (optional) inputs in `AssetId` order, such that computes
+    /// listing the same inputs in any order have the same outputs.
+    pub fn with_sorted_inputs() -> Self {
+        Self { sort_inputs: true, phantom: PhantomData }
+    }
+}
+impl<H> std::fmt::Debug for HashCompute<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HashCompute<{}>", std::any::type_name::<H>())?;
+        if self.sort_inputs {
+            write!(f, " with sorted inputs")?;
+        }
+        Ok(())
     }
-    Some(
-        compute_args
+}
+impl<H: std::hash::Hasher + Default> ComputeFn for HashCompute<H> {
+    fn compute(
+        &self,
+        store: &dyn AssetStore,
+        compute_args: &ComputeArgs,
+    ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+        let mut hasher = H::default();
+        let mut inputs = compute_args.inputs.iter().collect::<Vec<_>>();
+        let mut optional_inputs = compute_args.optional_inputs.iter().collect::<Vec<_>>();
+        if self.sort_inputs {
+            inputs.sort();
+            optional_inputs.sort();
+        }
+        for needed_asset in inputs.into_iter().chain(Some(&compute_args.compute_asset)) {
+            let asset_data = store.get(needed_asset).ok_or_else(|| ComputeError {
+                message: "needed asset is missing from the store".to_owned(),
+                input: Some(*needed_asset),
+            })?;
+            hasher.write(&asset_data.bytes);
+        }
+        // Which optional inputs were present is hashed too, distinguishing their absence
+        for optional_input in optional_inputs {
+            let asset_data = store.get(optional_input).ok_or_else(|| ComputeError {
+                message: "optional input passed to the compute is missing from the store"
+                    .to_owned(),
+                input: Some(*optional_input),
+            })?;
+            hasher.write_u32(optional_input.0);
+            hasher.write(&asset_data.bytes);
+        }
+        Ok(compute_args
             .outputs
             .iter()
             .map(|&output_asset_id| {
-                let data = AssetData { bits: hasher.finish() };
-                hasher.write_u64(data.bits);
+                let data = AssetData::from(hasher.finish());
+                hasher.write(&data.bytes);
                 (output_asset_id, data)
             })
-            .collect(),
-    )
+            .collect())
+    }
+}
+
+impl RateLimiter {
+    /// Permits a sustained `tokens_per_sec` actions per second, with bursts of up to `capacity`
+    pub fn new(tokens_per_sec: f64, capacity: f64) -> Self {
+        assert!(tokens_per_sec > 0.);
+        Self {
+            tokens_per_sec,
+            capacity,
+            tokens: capacity,
+            refilled_at: Instant::now(),
+            starved: false,
+        }
+    }
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.refilled_at;
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.tokens_per_sec).min(self.capacity);
+        self.refilled_at = now;
+    }
+    /// Consume one token if available
+    fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            true
+        } else {
+            self.starved = true;
+            false
+        }
+    }
+    fn until_next_token(&self) -> Duration {
+        Duration::from_secs_f64((1. - self.tokens).max(0.) / self.tokens_per_sec)
+    }
+}
+
+impl CompletionWatcher {
+    pub fn new(goals: impl IntoIterator<Item = (SiteId, AssetId)>) -> Self {
+        let remaining_goals = goals.into_iter().collect();
+        Self {
+            remaining_goals: Arc::new(Mutex::new(remaining_goals)),
+            all_reached: Arc::new(Condvar::new()),
+        }
+    }
+    fn goal_reached(&self, site_id: SiteId, asset_id: AssetId) {
+        let mut remaining_goals = self.remaining_goals.lock().unwrap();
+        if remaining_goals.remove(&(site_id, asset_id)) && remaining_goals.is_empty() {
+            self.all_reached.notify_all();
+        }
+    }
+    pub fn is_complete(&self) -> bool {
+        self.remaining_goals.lock().unwrap().is_empty()
+    }
+    /// Block until all goals are reached, or the timeout elapses. Returns whether they were reached.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let remaining_goals = self.remaining_goals.lock().unwrap();
+        let (remaining_goals, _) = self
+            .all_reached
+            .wait_timeout_while(remaining_goals, timeout, |remaining_goals| {
+                !remaining_goals.is_empty()
+            })
+            .unwrap();
+        remaining_goals.is_empty()
+    }
+}
+
+impl SiteProgress {
+    /// Number of assets the site holds
+    pub fn asset_count(&self) -> usize {
+        self.asset_count.load(Ordering::Relaxed)
+    }
+    /// Number of the site's instructions yet to be completed
+    pub fn pending_count(&self) -> usize {
+        self.pending_count.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "test-hooks")]
+impl AssetInjector {
+    /// Returns false if the site's inbox is full or gone, in which case nothing is delivered
+    pub fn inject_asset(&self, asset_id: AssetId, asset_data: AssetData) -> bool {
+        let signed_msg = Msg::AssetData { asset_id, asset_data }.sign(&self.keypair);
+        self.inbox.try_send(signed_msg).is_ok()
+    }
+}
+
+impl SiteControl {
+    /// Cancel the given compute, as by `Site::cancel_compute`, once the site next checks for
+    /// commands, i.e., between computes, or as soon as it waits for messages
+    pub fn cancel_compute(&self, compute_args: ComputeArgs) {
+        self.send(SiteCommand::CancelCompute(compute_args));
+    }
+    /// Add instructions for the site to do, as by `Site::accept_instructions`, e.g., to reassign
+    /// computes while it executes
+    pub fn add_instructions(&self, signed: SignedInstructions) {
+        self.send(SiteCommand::AddInstructions(signed));
+    }
+    fn send(&self, command: SiteCommand) {
+        // Only fails once the site is dropped, when there is nothing left to command
+        let _ = self.commands.send(command);
+    }
+}
+
+impl SitePause {
+    /// The site stops the next time it would wait for a message, e.g., after its current compute
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.resumed.notify_all();
+    }
+    pub fn is_paused(&self) -> bool {
+        *self.paused.lock().unwrap()
+    }
+    /// Blocks while paused. Returns whether it was paused.
+    fn wait_while_paused(&self) -> bool {
+        let paused = self.paused.lock().unwrap();
+        if !*paused {
+            return false;
+        }
+        drop(self.resumed.wait_while(paused, |paused| *paused).unwrap());
+        true
+    }
 }
 
 impl SiteInner {
     const REQUEST_PERIOD: Duration = Duration::from_millis(300);
+    const FULL_OUTBOX_RETRY_PERIOD: Duration = Duration::from_millis(5);
+    const MISSED_HEARTBEATS: u32 = 3; // before a peer is suspected to have failed
 
-    fn send_to(&mut self, dest_id: &SiteId, msg: Msg) {
-        log!(self.logger, "Sending to {:?} msg {:?}", dest_id, &msg);
-        let signed_msg = msg.sign(&self.keypair);
+    /// Returns false if the destination's channel is full, in which case the message is dropped.
+    /// Once a send to some destination fails, subsequent sends to it fail until `full_outboxes`
+    /// is cleared, such that deferred sends retain their order.
+    #[must_use]
+    fn send_to(&mut self, dest_id: &SiteId, msg: Msg) -> bool {
+        if self.full_outboxes.contains(dest_id) {
+            return false;
+        }
+        log!(self.logger, "Sending to {} msg {:?}", dest_id.short(), &msg);
+        #[cfg(feature = "tracing")]
+        tracing::info!(name: "send", parent: &self.span, to = %dest_id.short(), msg = ?msg);
+        let sent_asset = match &msg {
+            Msg::AssetData { asset_id, .. } | Msg::AssetChunk { asset_id, index: 0, .. } => {
+                Some(*asset_id)
+            }
+            _ => None,
+        };
+        let signed_msg = if self.config.untrusted_local {
+            msg.unsigned(self.keypair.public)
+        } else {
+            msg.sign_with_buffer(&self.keypair, &mut self.signing_buffer)
+        };
         // let mut signed_msg = msg.sign(&self.keypair);
         // let mut sig = signed_msg.signature.to_bytes();
         // sig[2] ^= !0;
         // signed_msg.signature = Signature::new(sig);
-        self.outboxes.get(dest_id).unwrap().send(signed_msg).unwrap();
-    }
-    fn try_complete(&mut self, instruction: &mut Instruction) -> InsExecResult {
-        match instruction {
-            Instruction::AcquireAssetFrom { asset_id, site_id } => {
-                if self.asset_store.contains_key(asset_id) {
-                    return InsExecResult::Complete { added_assets_to_store: false };
-                }
-                let now = Instant::now();
-                let recent_request = self
-                    .last_requested_at
-                    .get(asset_id)
-                    .map(|&at| now - at < Self::REQUEST_PERIOD)
-                    .unwrap_or(false);
-                if !recent_request {
-                    // Did not recently request this asset! Do so!
-                    self.last_requested_at.insert(*asset_id, now);
-                    let msg = Msg::AssetDataRequest { asset_id: *asset_id };
-                    self.send_to(site_id, msg);
+        let wire_len = signed_msg.wire_len() as u64;
+        match self.outboxes.read().unwrap().get(dest_id).unwrap().try_send(signed_msg) {
+            Ok(()) => {
+                self.metrics.messages_sent += 1;
+                self.metrics.bytes_sent += wire_len;
+                if let Some(asset_id) = sent_asset {
+                    if !self.sent_assets.insert((*dest_id, asset_id)) {
+                        self.metrics.retransmissions += 1;
+                    }
                 }
-                InsExecResult::Incomplete
+                true
             }
-            Instruction::SendAssetTo { asset_id, site_id } => {
-                if let Some(asset_data) = self.asset_store.get(&asset_id) {
-                    let msg =
-                        Msg::AssetData { asset_id: *asset_id, asset_data: asset_data.clone() };
-                    self.send_to(site_id, msg);
-                    InsExecResult::Complete { added_assets_to_store: false }
-                } else {
-                    InsExecResult::Incomplete
-                }
+            Err(crossbeam_channel::TrySendError::Full(_)) => {
+                log!(self.logger, "Outbox to {} is full. Deferring", dest_id.short());
+                self.full_outboxes.insert(*dest_id);
+                false
             }
-            Instruction::ComputeAssetData(compute_args) => {
-                if compute_args
-                    .needed_assets()
-                    .all(|asset_id| self.asset_store.contains_key(&asset_id))
-                {
-                    log!(self.logger, "Did a computation with {:?} ", &compute_args);
-                    self.asset_store.extend(
-                        actual_compute(&self.asset_store, compute_args).expect("compute failed!"),
-                    );
-                    InsExecResult::Complete { added_assets_to_store: true }
-                } else {
-                    InsExecResult::Incomplete
-                }
+            Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
+                // The destination is gone, e.g., it crashed. Its messages are lost, as on a network.
+                log!(self.logger, "Outbox to {} is disconnected. Dropping", dest_id.short());
+                true
             }
         }
     }
-}
-
-impl Site {
-    /// Consumes the calling thread
-    pub fn execute(&mut self) {
-        let start = Instant::now();
-        log!(
-            self.inner.logger,
-            "Started executing at {:?}. My site_id is {:?}",
-            &start,
-            SiteId::from_public_key_ref(&self.inner.keypair.public),
-        );
-        'execute_loop: loop {
-            // Any instruction might be completable!
-
-            let mut i = 0;
-            // loop invariant: todo instructions with indices in [0..i)] would return InsExecResult::Incomplete if checked with `try_complete`.
-            while i < self.todo_instructions.len() {
-                let result = self.inner.try_complete(&mut self.todo_instructions[i]);
-                match result {
-                    InsExecResult::Incomplete => {
-                        // retain this instruction, consider the next
-                        i += 1;
-                    }
-                    InsExecResult::Complete { added_assets_to_store: false } => {
-                        // remove this instruction, consider all subsequent instructions
-                        self.todo_instructions.swap_remove(i);
-                    }
-                    InsExecResult::Complete { added_assets_to_store: true } => {
-                        // remove this instruction, consider all instructions
-                        self.todo_instructions.swap_remove(i);
-                        continue 'execute_loop;
-                    }
-                }
+    /// Send the given asset's data, in chunks if it exceeds the maximum message size.
+    /// Returns false if (any chunk of) the data could not be sent, as `send_to`. Sending the same
+    /// data again resumes from the first chunk not sent, such that slow receivers catch up.
+    #[must_use]
+    fn send_asset(&mut self, dest_id: &SiteId, asset_id: AssetId, asset_data: AssetData) -> bool {
+        let whole_len =
+            SignedMsg::OVERHEAD_LEN + Msg::ASSET_DATA_HEADER_LEN + asset_data.bytes.len();
+        let max_msg_bytes = match self.config.max_msg_bytes {
+            Some(max_msg_bytes) if whole_len > max_msg_bytes => max_msg_bytes,
+            _ => return self.send_to(dest_id, Msg::AssetData { asset_id, asset_data }),
+        };
+        let chunk_len =
+            max_msg_bytes.saturating_sub(SignedMsg::OVERHEAD_LEN + Msg::CHUNK_HEADER_LEN).max(1);
+        let chunks = asset_data.bytes.chunks(chunk_len);
+        let count = chunks.len() as u32;
+        let content_hash = asset_data.content_hash();
+        // Other data, e.g., a newer version, is sent from its first chunk
+        let first = match self.chunks_sent.remove(&(*dest_id, asset_id)) {
+            Some((sent_hash, next)) if sent_hash == content_hash => next,
+            _ => 0,
+        };
+        for (index, chunk) in chunks.enumerate().skip(first as usize) {
+            let chunk = AssetData { bytes: chunk.to_vec() };
+            let msg = Msg::AssetChunk { asset_id, index: index as u32, count, chunk };
+            if !self.send_to(dest_id, msg) {
+                self.chunks_sent.insert((*dest_id, asset_id), (content_hash, index as u32));
+                return false;
             }
-            // No instructions are completable.
-
-            if self.todo_instructions.is_empty() {
-                log!(self.inner.logger, "Ran out of TODO instructions after {:?}", start.elapsed());
+        }
+        true
+    }
+    /// Buffers the given chunk. Returns the asset's data once all of its chunks have arrived from
+    /// the sender. Chunks of the same asset from different senders are reassembled separately.
+    fn receive_chunk(
+        &mut self,
+        sender: SiteId,
+        asset_id: AssetId,
+        index: u32,
+        count: u32,
+        chunk: AssetData,
+    ) -> Option<AssetData> {
+        if index >= count {
+            log!(self.logger, "Dropped inconsistent chunk {}/{} of {:?}", index, count, asset_id);
+            return None;
+        }
+        let (expected_count, chunks) = self
+            .partial_assets
+            .entry((sender, asset_id))
+            .or_insert_with(|| (count, Default::default()));
+        if count != *expected_count {
+            // e.g., the asset was sent again in other chunks. Those buffered so far are discarded
+            log!(self.logger, "Restarting reassembly of {:?} in {} chunks", asset_id, count);
+            *expected_count = count;
+            chunks.clear();
+        }
+        chunks.insert(index, chunk.bytes);
+        if chunks.len() < count as usize {
+            return None;
+        }
+        let (_count, mut chunks) = self.partial_assets.remove(&(sender, asset_id))?;
+        let bytes = (0..count).flat_map(|index| chunks.remove(&index).unwrap()).collect();
+        Some(AssetData { bytes })
+    }
+    fn store_asset(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        if let Some(subscribers) = self.subscribers.get(&asset_id) {
+            let changed = self.asset_store.get(&asset_id).map(|old| *old != asset_data);
+            if changed.unwrap_or(true) {
+                self.stale_subscribers.extend(subscribers.iter().map(|&s| (s, asset_id)));
             }
-
-            // receive 1+ messages until we have further populated the asset store
-            loop {
-                let signed_msg = match self.inner.inbox.recv_timeout(Duration::from_secs(1)) {
-                    Ok(signed_msg) => signed_msg,
-                    Err(_) => {
+        }
+        self.asset_store.insert(asset_id, asset_data);
+        self.touch(asset_id);
+        if let Some(completion_watcher) = &self.completion_watcher {
+            let site_id = SiteId::from_public_key_ref(&self.keypair.public);
+            completion_watcher.goal_reached(*site_id, asset_id);
+        }
+    }
+    /// Push the latest version of each changed asset to its subscribers.
+    /// Those whose outboxes are full remain stale, to be retried.
+    fn push_to_subscribers(&mut self) {
+        let stale: Vec<_> = self.stale_subscribers.iter().copied().collect();
+        for (subscriber, asset_id) in stale {
+            let asset_data = match self.asset_store.get(&asset_id) {
+                Some(asset_data) => asset_data.into_owned(),
+                None => continue,
+            };
+            self.touch(asset_id);
+            if self.send_asset(&subscriber, asset_id, asset_data) {
+                self.stale_subscribers.remove(&(subscriber, asset_id));
+            }
+        }
+    }
+    /// The function computing with the given compute asset: the one registered for it, if any,
+    /// or else the default
+    fn compute_fn_for(&self, compute_asset: &AssetId) -> Result<Arc<dyn ComputeFn>, ComputeError> {
+        let compute_fn = self.compute_fns.get(compute_asset).or(self.compute_fn.as_ref());
+        compute_fn.cloned().ok_or_else(|| ComputeError {
+            message: format!(
+                "no compute function is registered for compute asset {}",
+                compute_asset.0
+            ),
+            input: Some(*compute_asset),
+        })
+    }
+    /// Runs the compute on another thread, storing each output and pushing it to subscribers as
+    /// it is streamed. Returns whether any outputs were stored.
+    fn compute_streaming(&mut self, compute_args: &ComputeArgs) -> Result<bool, ComputeError> {
+        // The compute reads copies of its needed assets, as the store is updated meanwhile
+        let needed: HashMap<AssetId, AssetData> = compute_args
+            .needed_assets()
+            .chain(compute_args.optional_inputs.iter())
+            .filter_map(|asset_id| Some((*asset_id, self.asset_store.get(asset_id)?.into_owned())))
+            .collect();
+        let compute_fn = self.compute_fn_for(&compute_args.compute_asset)?;
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        crossbeam_utils::thread::scope(|s| {
+            let computing =
+                s.spawn(move |_| compute_fn.compute_streaming(&needed, compute_args, &sender));
+            let mut added_assets_to_store = false;
+            for (asset_id, asset_data) in receiver.iter() {
+                log!(self.logger, "Streamed output {:?} of {}", asset_id, compute_args);
+                self.store_asset(asset_id, asset_data);
+                self.push_to_subscribers();
+                added_assets_to_store = true;
+            }
+            computing.join().unwrap().map(|()| added_assets_to_store)
+        })
+        .unwrap()
+    }
+    /// Whether this site must keep serving until the goals of all sites are reached.
+    /// See `SiteConfig::await_all_goals`.
+    fn awaits_goals(&self) -> bool {
+        self.config.await_all_goals
+            && self.completion_watcher.as_ref().is_some_and(|watcher| !watcher.is_complete())
+    }
+    /// Whether this site holds all of its goal assets. Trivially true without goals.
+    fn has_all_goals(&self) -> bool {
+        self.goals.iter().all(|asset_id| self.asset_store.contains_key(asset_id))
+    }
+    /// Record that the given asset was used, postponing its eviction
+    fn touch(&mut self, asset_id: AssetId) {
+        if self.config.asset_ttl.is_some() {
+            self.last_accessed_at.insert(asset_id, Instant::now());
+        }
+    }
+    /// Evict acquired assets unused for longer than `SiteConfig::asset_ttl`, except those in `keep`.
+    /// Returns how long until the next asset may expire, if any.
+    fn evict_expired(&mut self, keep: &HashSet<AssetId>) -> Option<Duration> {
+        let ttl = self.config.asset_ttl?;
+        let mut until_next_expiry = None;
+        let mut expired = vec![];
+        for (asset_id, at) in self.last_accessed_at.iter() {
+            if keep.contains(asset_id) || !self.acquired_from.contains_key(asset_id) {
+                continue;
+            }
+            match ttl.checked_sub(at.elapsed()) {
+                Some(remaining) if remaining > Duration::default() => {
+                    until_next_expiry =
+                        Some(until_next_expiry.map_or(remaining, |u| remaining.min(u)))
+                }
+                _ => expired.push(*asset_id),
+            }
+        }
+        for asset_id in expired {
+            log!(self.logger, "Evicting {:?}, unused for over {:?}", asset_id, ttl);
+            self.asset_store.remove(&asset_id);
+            self.last_accessed_at.remove(&asset_id);
+        }
+        until_next_expiry
+    }
+    /// Request the given asset from its source if it was evicted
+    fn refetch_if_evicted(&mut self, asset_id: AssetId) {
+        if self.asset_store.contains_key(&asset_id) {
+            return;
+        }
+        if let Some(source) = self.acquired_from.get(&asset_id).copied() {
+            self.request_asset(asset_id, &source, 0);
+        }
+    }
+    /// Move messages from the inbox into the buffer, dropping the oldest droppable messages to make
+    /// room. Once full of undroppable messages, the rest are left in the inbox.
+    fn buffer_inbox(&mut self) {
+        let capacity = match self.config.channel_capacity {
+            Some(capacity) if self.config.evict_when_inbox_full => capacity,
+            _ => return,
+        };
+        while !self.inbox.is_empty() {
+            if self.inbox_buffer.len() >= capacity {
+                let droppable = self.inbox_buffer.iter().position(|m| m.msg.is_droppable());
+                match droppable.and_then(|i| self.inbox_buffer.remove(i)) {
+                    Some(evicted) => {
+                        log!(
+                            self.logger,
+                            "Evicted msg from {} {:?} from the full inbox",
+                            evicted.sender().short(),
+                            &evicted.msg
+                        );
+                        self.metrics.messages_evicted += 1;
+                    }
+                    None => return,
+                }
+            }
+            match self.inbox.try_recv() {
+                Ok(signed_msg) => self.inbox_buffer.push_back(signed_msg),
+                Err(_) => return,
+            }
+        }
+    }
+    /// The next received message, if any is waiting
+    fn try_receive(&mut self) -> Option<SignedMsg> {
+        self.buffer_inbox();
+        self.inbox_buffer.pop_front().or_else(|| self.inbox.try_recv().ok())
+    }
+    /// The next received message, waiting up to `timeout` for one, unless a command arrives first
+    fn receive(&mut self, timeout: Duration) -> Received {
+        self.buffer_inbox();
+        if let Some(signed_msg) = self.inbox_buffer.pop_front() {
+            return Received::Msg(signed_msg);
+        }
+        crossbeam_channel::select! {
+            recv(self.inbox) -> signed_msg => match signed_msg {
+                Ok(signed_msg) => Received::Msg(signed_msg),
+                // Unreachable, as the site keeps its own outbox
+                Err(_) => Received::Timeout,
+            },
+            recv(self.commands) -> command => match command {
+                Ok(command) => Received::Command(command),
+                // Unreachable, as the site keeps a sender of its own
+                Err(_) => Received::Timeout,
+            },
+            default(timeout) => Received::Timeout,
+        }
+    }
+    /// The other sites in this site's network
+    fn peers(&self) -> Vec<SiteId> {
+        let me = SiteId::from(&self.keypair);
+        self.outboxes.read().unwrap().keys().filter(|&&id| id != me).copied().collect()
+    }
+    /// Stop pushing assets to subscribers, and taking over delegated computes, e.g., once
+    /// execution has completed
+    fn stop_serving(&mut self) {
+        self.subscribers.clear();
+        self.stale_subscribers.clear();
+        self.steal_asked.clear();
+    }
+    /// Store an asset received from the given site, which it can be re-fetched from if evicted
+    fn store_received(&mut self, source: SiteId, asset_id: AssetId, asset_data: AssetData) {
+        self.request_again_at.remove(&asset_id);
+        self.nacked.retain(|&(_, nacked_asset_id), _| nacked_asset_id != asset_id);
+        if self.config.asset_ttl.is_some() {
+            self.acquired_from.insert(asset_id, source);
+        }
+        match self.asset_store.get(&asset_id).map(|held| *held == asset_data) {
+            Some(true) => {
+                // Re-delivered, e.g., a retransmission. Storing it again would change nothing.
+                log!(
+                    self.logger,
+                    "Already holding {:?} with this content. Not storing it",
+                    asset_id
+                );
+                self.metrics.duplicates_received += 1;
+                self.touch(asset_id);
+                return;
+            }
+            Some(false) if self.config.reply_policy == ReplyPolicy::FirstWins => return,
+            Some(false) if self.config.reply_policy == ReplyPolicy::FlagConflicts => {
+                log!(
+                    self.logger,
+                    "Conflict! {} sent {:?} with other content than held. Keeping the held",
+                    source.short(),
+                    asset_id
+                );
+                self.metrics.conflicting_replies += 1;
+                return;
+            }
+            _ => {}
+        }
+        self.store_asset(asset_id, asset_data);
+    }
+    fn try_take_rate_token(&mut self) -> bool {
+        self.rate_limiter.as_mut().map(RateLimiter::try_take).unwrap_or(true)
+    }
+    /// How long to wait for messages before concluding that the site is idle, if at all
+    fn recv_timeout(&self) -> Option<Duration> {
+        if !self.full_outboxes.is_empty() {
+            // Deferred sends become completable when the receivers catch up
+            return Some(Self::FULL_OUTBOX_RETRY_PERIOD);
+        }
+        match &self.rate_limiter {
+            // Deferred instructions become completable when tokens are refilled
+            Some(rate_limiter) if rate_limiter.starved => Some(rate_limiter.until_next_token()),
+            _ => None,
+        }
+    }
+    /// Request the given asset from the given site, unless it was requested recently,
+    /// or the site already declined to serve it
+    fn request_asset(&mut self, asset_id: AssetId, site_id: &SiteId, priority: u8) {
+        if self.is_nacked(site_id, asset_id) {
+            return;
+        }
+        let now = Instant::now();
+        let recent_request =
+            self.request_again_at.get(&asset_id).map(|&at| now < at).unwrap_or(false);
+        if !recent_request {
+            // Did not recently request this asset! Do so!
+            let msg = Msg::AssetDataRequest { asset_id, priority };
+            if self.send_to(site_id, msg) {
+                let again_at = now + self.request_period();
+                if self.request_again_at.insert(asset_id, again_at).is_some() {
+                    *self.metrics.re_requests.entry(asset_id).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    /// Whether the given site declined to serve the given asset. Sites which lack the asset are
+    /// asked again after a request period, in case the send they queued never arrives.
+    fn is_nacked(&self, site_id: &SiteId, asset_id: AssetId) -> bool {
+        let nacked = self.nacked.get(&(*site_id, asset_id));
+        nacked.is_some_and(|(_, until)| until.is_none_or(|until| Instant::now() < until))
+    }
+    fn nack(&mut self, site_id: SiteId, asset_id: AssetId, reason: NackReason) {
+        let until = match reason {
+            NackReason::Missing => Some(Instant::now() + self.request_period()),
+            NackReason::Denied => None,
+        };
+        self.nacked.insert((site_id, asset_id), (reason, until));
+    }
+    /// Forget the expired nacks, returning how long until the next one expires, if any
+    fn until_nack_expiry(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        self.nacked.retain(|_, (_, until)| until.is_none_or(|until| now < until));
+        self.nacked.values().filter_map(|&(_, until)| until).min().map(|until| until - now)
+    }
+    /// How long to wait before requesting an asset again, varied by `SiteConfig::request_jitter`
+    fn request_period(&mut self) -> Duration {
+        use rand::Rng;
+        let jitter = self.config.request_jitter;
+        if jitter <= 0. {
+            return Self::REQUEST_PERIOD;
+        }
+        let factor = 1. + self.request_jitter_rng.gen_range(-jitter, jitter);
+        Self::REQUEST_PERIOD.mul_f64(factor.max(0.))
+    }
+    /// Describes why the given instruction cannot be completed yet, for diagnostics
+    fn waiting_for(&self, instruction: &Instruction) -> String {
+        let missing = |asset_id: &AssetId| !self.asset_store.contains_key(asset_id);
+        match instruction {
+            Instruction::AcquireAssetFrom { asset_id, site_id, .. }
+                if matches!(
+                    self.nacked.get(&(*site_id, *asset_id)),
+                    Some((NackReason::Denied, _))
+                ) =>
+            {
+                format!("denied asset {} by {}", asset_id.0, site_id.short())
+            }
+            Instruction::AcquireAssetFrom { asset_id, site_id, .. } => {
+                format!("waiting for asset {} from {}", asset_id.0, site_id.short())
+            }
+            Instruction::SendAssetTo { asset_id, .. } if missing(asset_id) => {
+                format!("waiting for asset {} to send", asset_id.0)
+            }
+            Instruction::SendAssetTo { site_id, .. } if self.full_outboxes.contains(site_id) => {
+                format!("waiting for the outbox to {} to drain", site_id.short())
+            }
+            Instruction::ComputeAssetData(compute_args) => {
+                let missing: Vec<u32> =
+                    compute_args.needed_assets().filter(|a| missing(a)).map(|a| a.0).collect();
+                let present_optionals = self.present_optional_inputs(compute_args);
+                if !missing.is_empty() {
+                    format!("waiting for assets {:?}", missing)
+                } else if present_optionals < compute_args.min_optional_inputs {
+                    format!(
+                        "waiting for {} more optional inputs",
+                        compute_args.min_optional_inputs - present_optionals
+                    )
+                } else {
+                    "waiting for a rate limit token".to_owned()
+                }
+            }
+            Instruction::Verify { asset_id, .. } => format!("waiting for asset {}", asset_id.0),
+            Instruction::SendAssetTo { .. } => "waiting for a rate limit token".to_owned(),
+        }
+    }
+    /// How many of the given compute's optional inputs are present
+    fn present_optional_inputs(&self, compute_args: &ComputeArgs) -> usize {
+        let optional_inputs = self.optional_candidates(compute_args).into_iter();
+        optional_inputs.filter(|asset_id| self.asset_store.contains_key(asset_id)).count()
+    }
+    /// The given compute's optional inputs, including those of `input_range` held here
+    fn optional_candidates(&self, compute_args: &ComputeArgs) -> Vec<AssetId> {
+        if compute_args.input_range.is_none() {
+            return compute_args.optional_inputs.clone();
+        }
+        compute_args.optional_candidates(self.asset_store.asset_ids())
+    }
+    fn try_complete(&mut self, instruction: &mut Instruction) -> Result<InsExecResult, ExecError> {
+        Ok(match instruction {
+            Instruction::AcquireAssetFrom { asset_id, site_id, priority, timeout } => {
+                if self.asset_store.contains_key(asset_id) {
+                    self.acquiring_since.remove(asset_id);
+                    return Ok(InsExecResult::Complete { added_assets_to_store: false });
+                }
+                if let Some(timeout) = *timeout {
+                    let since = *self.acquiring_since.entry(*asset_id).or_insert_with(Instant::now);
+                    if since.elapsed() >= timeout {
                         log!(
-                            self.inner.logger,
-                            "RECV timeout with todo instructions {:#?} assets {:?}",
-                            &self.todo_instructions,
-                            &self.inner.asset_store
+                            self.logger,
+                            "Acquisition of {:?} timed out after {:?}",
+                            asset_id,
+                            timeout
                         );
-                        return;
+                        return Err(ExecError::AcquireTimedOut { asset_id: *asset_id, timeout });
+                    }
+                }
+                self.request_asset(*asset_id, site_id, *priority);
+                InsExecResult::Incomplete
+            }
+            Instruction::SendAssetTo { asset_id, site_id, .. } => {
+                self.refetch_if_evicted(*asset_id);
+                if !self.asset_store.contains_key(asset_id) || !self.try_take_rate_token() {
+                    return Ok(InsExecResult::Incomplete);
+                }
+                if let Some(asset_data) = self.asset_store.get(asset_id) {
+                    let asset_data = asset_data.into_owned();
+                    self.touch(*asset_id);
+                    if self.send_asset(site_id, *asset_id, asset_data) {
+                        InsExecResult::Complete { added_assets_to_store: false }
+                    } else {
+                        InsExecResult::Incomplete
+                    }
+                } else {
+                    InsExecResult::Incomplete
+                }
+            }
+            Instruction::ComputeAssetData(compute_args) => {
+                if !compute_args.consume_inputs
+                    && !compute_args.outputs.is_empty()
+                    && compute_args
+                        .outputs
+                        .iter()
+                        .all(|asset_id| self.asset_store.contains_key(asset_id))
+                {
+                    // Satisfied another way, e.g., the outputs were sent by another site
+                    log!(self.logger, "Skipping computation {}. Outputs are present", compute_args);
+                    return Ok(InsExecResult::Complete { added_assets_to_store: false });
+                }
+                for asset_id in compute_args.needed_assets().copied().collect::<Vec<_>>() {
+                    self.refetch_if_evicted(asset_id);
+                }
+                if compute_args
+                    .needed_assets()
+                    .all(|asset_id| self.asset_store.contains_key(asset_id))
+                    && self.present_optional_inputs(compute_args)
+                        >= compute_args.min_optional_inputs
+                    && self.try_take_rate_token()
+                {
+                    // Pass whichever optional inputs have arrived, including those discovered in
+                    // `input_range`, which are listed such that outputs depend on the set used
+                    let optional_inputs = self
+                        .optional_candidates(compute_args)
+                        .into_iter()
+                        .filter(|asset_id| self.asset_store.contains_key(asset_id))
+                        .collect();
+                    let compute_args =
+                        &ComputeArgs { optional_inputs, input_range: None, ..compute_args.clone() };
+                    let result = if self.config.stream_compute_outputs {
+                        self.compute_streaming(compute_args)
+                    } else {
+                        let compute_fn = self.compute_fn_for(&compute_args.compute_asset);
+                        compute_fn.and_then(|compute_fn| {
+                            let outputs = compute_fn.compute(&*self.asset_store, compute_args)?;
+                            let added_assets_to_store = !outputs.is_empty();
+                            for (asset_id, asset_data) in outputs {
+                                self.store_asset(asset_id, asset_data);
+                            }
+                            Ok(added_assets_to_store)
+                        })
+                    };
+                    let added_assets_to_store = match result {
+                        Ok(added_assets_to_store) => added_assets_to_store,
+                        Err(error) => {
+                            log!(self.logger, "Computation {} failed: {}", compute_args, error);
+                            let compute_args = Box::new(compute_args.clone());
+                            return Err(ExecError::ComputeFailed { compute_args, error });
+                        }
+                    };
+                    log!(self.logger, "Did a computation: {}", compute_args);
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(name: "compute", parent: &self.span, compute = %compute_args);
+                    self.metrics.computes += 1;
+                    let used =
+                        compute_args.needed_assets().chain(compute_args.optional_inputs.iter());
+                    for asset_id in used.copied().collect::<Vec<_>>() {
+                        self.touch(asset_id);
+                    }
+                    if compute_args.consume_inputs {
+                        for asset_id in compute_args.inputs.iter() {
+                            self.asset_store.remove(asset_id);
+                            self.last_accessed_at.remove(asset_id);
+                            self.acquired_from.remove(asset_id);
+                        }
+                    }
+                    match compute_args.result_site {
+                        Some(result_site) if result_site != SiteId::from(&self.keypair) => {
+                            self.results_elsewhere.extend(compute_args.outputs.iter().copied());
+                        }
+                        _ => {}
                     }
+                    InsExecResult::Complete { added_assets_to_store }
+                } else {
+                    InsExecResult::Incomplete
+                }
+            }
+            Instruction::Verify { asset_id, expected_hash } => {
+                self.refetch_if_evicted(*asset_id);
+                let actual_hash = match self.asset_store.get(asset_id) {
+                    Some(asset_data) => asset_data.content_hash(),
+                    None => return Ok(InsExecResult::Incomplete),
                 };
-                if let Err(e) = signed_msg.verify() {
-                    log!(self.inner.logger, "Msg verification failed {:?} {:?}", &signed_msg, e);
-                    continue;
-                }
-                log!(self.inner.logger, "Received verfied msg {:?}", &signed_msg.msg);
-                match signed_msg.msg {
-                    Msg::AssetDataRequest { asset_id } => {
-                        if let Some(asset_data) = self.inner.asset_store.get(&asset_id) {
-                            let msg = Msg::AssetData { asset_id, asset_data: asset_data.clone() };
-                            self.inner.send_to(signed_msg.sender(), msg);
-                        } else {
-                            self.todo_instructions.push(Instruction::SendAssetTo {
-                                asset_id,
-                                site_id: *signed_msg.sender(),
-                            });
+                if actual_hash != *expected_hash {
+                    log!(self.logger, "Verification of {:?} failed", asset_id);
+                    return Err(ExecError::VerifyFailed {
+                        asset_id: *asset_id,
+                        expected_hash: *expected_hash,
+                        actual_hash,
+                    });
+                }
+                InsExecResult::Complete { added_assets_to_store: false }
+            }
+        })
+    }
+}
+
+impl Site {
+    /// Provide this site with initial asset data
+    pub fn insert_asset(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        self.inner.store_asset(asset_id, asset_data);
+    }
+    /// Store the given asset as if received from this site itself, e.g., to simulate an external
+    /// delivery between `step`s. As for received assets, the `ReplyPolicy` applies.
+    #[cfg(feature = "test-hooks")]
+    pub fn inject_asset(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        let site_id = SiteId::from(&self.inner.keypair);
+        self.inner.store_received(site_id, asset_id, asset_data);
+    }
+    /// Handle delivering assets to this site while it executes, via its inbox, such that it
+    /// re-checks its instructions as on receiving them from a peer
+    #[cfg(feature = "test-hooks")]
+    pub fn asset_injector(&self) -> AssetInjector {
+        let keypair = Keypair::from_bytes(&self.inner.keypair.to_bytes()).unwrap();
+        let site_id = SiteId::from(&keypair);
+        let inbox = self.inner.outboxes.read().unwrap()[&site_id].clone();
+        AssetInjector { keypair, inbox }
+    }
+    /// Whether this site currently stores the given asset
+    pub fn has_asset(&self, asset_id: &AssetId) -> bool {
+        self.inner.asset_store.contains_key(asset_id)
+    }
+    /// Only accept instructions signed by the planner with the given key. See `accept_instructions`.
+    pub fn set_planner_key(&mut self, planner_key: Option<PublicKey>) {
+        self.inner.planner_key = planner_key;
+    }
+    /// Add the given instructions to do, after checking they were signed for this site by the
+    /// planner, if its key is set. Rejected instructions are not added. Instructions equal to
+    /// one already to do are skipped, such that re-delivered instructions are done once.
+    pub fn accept_instructions(
+        &mut self,
+        signed: SignedInstructions,
+    ) -> Result<(), InstructionsRejected> {
+        if let Some(planner_key) = &self.inner.planner_key {
+            let site_id = SiteId::from(&self.inner.keypair);
+            if signed.site_id != site_id {
+                return Err(InstructionsRejected::WrongSite);
+            }
+            signed.verify(planner_key)?;
+        }
+        for ins in signed.instructions {
+            if !self.todo_instructions.contains(&ins) {
+                self.todo_instructions.push(ins);
+            }
+        }
+        Ok(())
+    }
+    /// Set the assets this site must end up with. See `SiteConfig::goal_grace`.
+    pub fn set_goals(&mut self, goals: impl IntoIterator<Item = AssetId>) {
+        self.inner.goals = goals.into_iter().collect();
+    }
+    /// Whether this site holds all of its goal assets, i.e., its own work is done
+    pub fn has_all_goals(&self) -> bool {
+        self.inner.has_all_goals()
+    }
+    /// The assets this site currently stores
+    pub fn held_assets(&self) -> BTreeSet<AssetId> {
+        self.inner.asset_store.asset_ids().into_iter().collect()
+    }
+    /// Replace the function this site uses to compute with compute assets which have no function
+    /// registered. If None, computes with such compute assets fail.
+    pub fn set_compute_fn(&mut self, compute_fn: Option<Arc<dyn ComputeFn>>) {
+        self.inner.compute_fn = compute_fn;
+    }
+    /// Compute with the given function whenever the compute asset is `compute_asset`,
+    /// as if the compute asset were the function's executable logic
+    pub fn register_compute_fn(&mut self, compute_asset: AssetId, compute_fn: Arc<dyn ComputeFn>) {
+        self.inner.compute_fns.insert(compute_asset, compute_fn);
+    }
+    /// Replace the store this site keeps its assets in
+    pub fn set_asset_store(&mut self, asset_store: Box<dyn AssetStore>) {
+        self.inner.asset_store = asset_store;
+    }
+    /// Limit the rate of this site's computes and sends. Unlimited by default.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<RateLimiter>) {
+        self.inner.rate_limiter = rate_limiter;
+    }
+    /// Request all the missing inputs of pending computes up front, so their transfers overlap,
+    /// rather than waiting for their acquisitions to be reached in the instruction scan.
+    fn prefetch_compute_inputs(&mut self) {
+        let asset_store = &self.inner.asset_store;
+        let missing_inputs: HashSet<AssetId> = self
+            .todo_instructions
+            .iter()
+            .filter_map(|ins| match ins {
+                Instruction::ComputeAssetData(compute_args) => Some(compute_args),
+                _ => None,
+            })
+            .flat_map(ComputeArgs::needed_assets)
+            .filter(|asset_id| !asset_store.contains_key(asset_id))
+            .copied()
+            .collect();
+        for ins in self.todo_instructions.iter() {
+            if let Instruction::AcquireAssetFrom { asset_id, site_id, priority, .. } = ins {
+                if missing_inputs.contains(asset_id) {
+                    self.inner.request_asset(*asset_id, site_id, *priority);
+                }
+            }
+        }
+    }
+    /// Ask the given site to push the given asset's data to this site whenever it changes.
+    /// Returns false if the request could not be sent, as the site's outbox is full.
+    pub fn subscribe(&mut self, asset_id: AssetId, site_id: &SiteId) -> bool {
+        self.inner.send_to(site_id, Msg::Subscribe { asset_id })
+    }
+    /// Report the peers this site suspects failed to the given channel, each once until heard
+    /// from again. See `SiteConfig::heartbeat_interval`.
+    pub fn set_failure_watcher(&mut self, failure_watcher: Sender<SiteId>) {
+        self.inner.failure_watcher = Some(failure_watcher);
+    }
+    /// Notify the given watcher of the assets this site stores from now on
+    pub fn set_completion_watcher(&mut self, completion_watcher: CompletionWatcher) {
+        self.inner.completion_watcher = Some(completion_watcher);
+    }
+    /// Whether the given site may obtain the given asset, logging denials
+    fn access_granted(&mut self, requester: SiteId, asset_id: AssetId) -> bool {
+        if let Some(access_control) = &self.inner.access_control {
+            match access_control.check(requester, asset_id) {
+                AccessCheck::Granted => {}
+                AccessCheck::Denied => {
+                    log!(
+                        self.inner.logger,
+                        "Denied request from {} for {:?}",
+                        requester.short(),
+                        asset_id
+                    );
+                    return false;
+                }
+                AccessCheck::Expired { at } => {
+                    log!(
+                        self.inner.logger,
+                        "Denied request from {} for {:?}. Access expired at {:?}",
+                        requester.short(),
+                        asset_id,
+                        at
+                    );
+                    return false;
+                }
+            }
+        }
+        true
+    }
+    /// Request an asset still to be acquired from the next source which did not decline it, i.e.,
+    /// another source it is to be acquired from, or the site it was acquired from before
+    fn request_from_next_source(&mut self, asset_id: AssetId) {
+        let acquires = self.todo_instructions.iter().filter_map(|ins| match ins {
+            Instruction::AcquireAssetFrom { asset_id: a, site_id, priority, .. }
+                if *a == asset_id =>
+            {
+                Some((*site_id, *priority))
+            }
+            _ => None,
+        });
+        let mut sources: Vec<(SiteId, u8)> = acquires.collect();
+        if sources.is_empty() || self.inner.asset_store.contains_key(&asset_id) {
+            // Not needed (anymore)
+            return;
+        }
+        sources.extend(self.inner.acquired_from.get(&asset_id).map(|&site_id| (site_id, 0)));
+        let inner = &self.inner;
+        match sources.into_iter().find(|(site_id, _)| !inner.is_nacked(site_id, asset_id)) {
+            Some((site_id, priority)) => {
+                log!(self.inner.logger, "Falling back to {} for {:?}", site_id.short(), asset_id);
+                self.inner.request_asset(asset_id, &site_id, priority);
+            }
+            None => log!(self.inner.logger, "No other source of {:?} to fall back to", asset_id),
+        }
+    }
+    fn handle_asset_request(&mut self, requester: SiteId, asset_id: AssetId, priority: u8) {
+        let granted = self.access_granted(requester, asset_id);
+        if self.inner.config.audit_access {
+            if granted {
+                // Denials are logged regardless
+                log!(
+                    self.inner.logger,
+                    "Granted request from {} for {:?}",
+                    requester.short(),
+                    asset_id
+                );
+            }
+            let record = AccessRecord { requester, asset_id, granted, at: Instant::now() };
+            self.inner.access_log.push(record);
+        }
+        if !granted {
+            let nack = Msg::Nack { asset_id, reason: NackReason::Denied };
+            let _ = self.inner.send_to(&requester, nack);
+            return;
+        }
+        if !self.inner.asset_store.contains_key(&asset_id) {
+            // The requester needn't ask again, as the send is queued below
+            let nack = Msg::Nack { asset_id, reason: NackReason::Missing };
+            let _ = self.inner.send_to(&requester, nack);
+        }
+        let can_send =
+            self.inner.asset_store.contains_key(&asset_id) && self.inner.try_take_rate_token();
+        let sent = match self.inner.asset_store.get(&asset_id).filter(|_| can_send) {
+            Some(asset_data) => {
+                let asset_data = asset_data.into_owned();
+                self.inner.touch(asset_id);
+                self.inner.send_asset(&requester, asset_id, asset_data)
+            }
+            None => false,
+        };
+        let send = Instruction::SendAssetTo { asset_id, site_id: requester, priority };
+        if !sent && !self.todo_instructions.contains(&send) {
+            // Queue the send ahead of less urgent sends, unless a repeated request queued it already
+            let at = self
+                .todo_instructions
+                .iter()
+                .position(|ins| match ins {
+                    Instruction::SendAssetTo { priority: p, .. } => *p < priority,
+                    _ => false,
+                })
+                .unwrap_or(self.todo_instructions.len());
+            self.todo_instructions.insert(at, send);
+        }
+    }
+    /// Shuffle the instructions before each scan, to expose instructions which only complete in a
+    /// particular order. For testing. Off by default. The shuffles are determined by the site's seed.
+    pub fn set_scan_shuffle(&mut self, enabled: bool) {
+        self.inner.scan_shuffle =
+            if enabled { Some(SmallRng::from_rng(&mut self.inner.seeder).unwrap()) } else { None };
+    }
+    /// Reseed all of this site's random choices, e.g., request jitter and scan shuffles, overriding
+    /// `SiteConfig::seed`. Sites with identical seeds, keypairs, and inputs make identical choices,
+    /// so runs are reproducible up to the timing of their threads.
+    pub fn set_seed(&mut self, seed: u64) {
+        let inner = &mut self.inner;
+        inner.seeder = SmallRng::seed_from_u64(seed);
+        inner.request_jitter_rng = SmallRng::from_rng(&mut inner.seeder).unwrap();
+        if inner.scan_shuffle.is_some() {
+            inner.scan_shuffle = Some(SmallRng::from_rng(&mut inner.seeder).unwrap());
+        }
+    }
+    /// Number of assets this site holds
+    pub fn asset_count(&self) -> usize {
+        self.inner.asset_store.len()
+    }
+    /// Number of this site's instructions yet to be completed
+    pub fn pending_count(&self) -> usize {
+        self.todo_instructions.len()
+    }
+    /// Handle to poll this site's asset and pending counts from other threads, e.g., while it
+    /// executes. These are cheap to read, but only as recent as the site's last loop iteration.
+    pub fn progress(&self) -> SiteProgress {
+        self.update_progress();
+        self.inner.progress.clone()
+    }
+    /// Remove and return the received messages not yet handled, without waiting for more,
+    /// e.g., to inspect leftover traffic once execution has ended
+    pub fn drain_inbox(&mut self) -> Vec<SignedMsg> {
+        let mut unhandled: Vec<SignedMsg> = self.inner.inbox_buffer.drain(..).collect();
+        unhandled.extend(self.inner.inbox.try_iter());
+        log!(self.inner.logger, "Drained {} unhandled msgs from the inbox", unhandled.len());
+        unhandled
+    }
+    /// Handle to pause and resume this site's `execute` from other threads
+    pub fn pause_handle(&self) -> SitePause {
+        self.inner.pause.clone()
+    }
+    /// The requests for assets this site handled so far, in order. Empty unless
+    /// `SiteConfig::audit_access` is set.
+    pub fn access_log(&self) -> &[AccessRecord] {
+        &self.inner.access_log
+    }
+    /// Remove and return the records of `access_log`, e.g., to persist them elsewhere
+    pub fn take_access_log(&mut self) -> Vec<AccessRecord> {
+        std::mem::take(&mut self.inner.access_log)
+    }
+    /// Counts of this site's repeated messages so far
+    pub fn metrics(&self) -> &SiteMetrics {
+        &self.inner.metrics
+    }
+    /// Handle to command this site from other threads, e.g., to cancel computes while it executes
+    pub fn control_handle(&self) -> SiteControl {
+        SiteControl { commands: self.inner.command_sender.clone() }
+    }
+    /// Carry out the commands received from `SiteControl`s so far. Returns whether there were any.
+    fn carry_out_commands(&mut self) -> bool {
+        let mut any = false;
+        while let Ok(command) = self.inner.commands.try_recv() {
+            self.carry_out(command);
+            any = true;
+        }
+        any
+    }
+    fn carry_out(&mut self, command: SiteCommand) {
+        match command {
+            SiteCommand::CancelCompute(compute_args) => {
+                if self.cancel_compute(&compute_args) {
+                    log!(self.inner.logger, "Cancelled computation {}", compute_args);
+                } else {
+                    log!(self.inner.logger, "No pending computation {} to cancel", compute_args);
+                }
+            }
+            SiteCommand::AddInstructions(signed) => {
+                let count = signed.instructions.len();
+                match self.accept_instructions(signed) {
+                    Ok(()) => log!(self.inner.logger, "Accepted {} more instructions", count),
+                    Err(e) => log!(self.inner.logger, "Rejected instructions: {:?}", e),
+                }
+            }
+        }
+    }
+    /// Remove the given pending compute, along with its no-longer-needed acquisitions.
+    /// Returns whether the compute was pending. See `SiteControl::cancel_compute` to cancel
+    /// computes while the site executes.
+    pub fn cancel_compute(&mut self, compute_args: &ComputeArgs) -> bool {
+        let before = self.todo_instructions.len();
+        self.todo_instructions.retain(|ins| match ins {
+            Instruction::ComputeAssetData(pending) => pending != compute_args,
+            _ => true,
+        });
+        let cancelled = self.todo_instructions.len() < before;
+        if cancelled {
+            self.cancel_unneeded_acquires(compute_args);
+        }
+        cancelled
+    }
+    /// Remove acquisitions of the given compute's needed assets which no remaining instruction
+    /// needs, and stop re-requesting them. Returns whether any acquisitions were removed.
+    fn cancel_unneeded_acquires(&mut self, compute_args: &ComputeArgs) -> bool {
+        let still_needed = |asset_id: &AssetId| {
+            self.todo_instructions.iter().any(|ins| match ins {
+                Instruction::ComputeAssetData(compute_args) => compute_args.may_take(asset_id),
+                Instruction::SendAssetTo { asset_id: sent, .. }
+                | Instruction::Verify { asset_id: sent, .. } => sent == asset_id,
+                Instruction::AcquireAssetFrom { .. } => false,
+            })
+        };
+        let unneeded: HashSet<AssetId> = self
+            .todo_instructions
+            .iter()
+            .filter_map(|ins| match ins {
+                Instruction::AcquireAssetFrom { asset_id, .. } => Some(asset_id),
+                _ => None,
+            })
+            .filter(|asset_id| {
+                compute_args.may_take(asset_id)
+                    && !still_needed(asset_id)
+                    && !self.inner.asset_store.contains_key(asset_id)
+            })
+            .copied()
+            .collect();
+        let before = self.todo_instructions.len();
+        self.todo_instructions.retain(|ins| match ins {
+            Instruction::AcquireAssetFrom { asset_id, .. } => !unneeded.contains(asset_id),
+            _ => true,
+        });
+        for asset_id in unneeded.iter() {
+            self.inner.request_again_at.remove(asset_id);
+        }
+        let cancelled = self.todo_instructions.len() < before;
+        if cancelled {
+            log!(self.inner.logger, "Cancelled acquisitions of no-longer-needed {:?}", unneeded);
+        }
+        cancelled
+    }
+    /// A few lines summarizing this site's remaining instructions and assets
+    pub fn pending_summary(&self) -> String {
+        let (mut sends, mut acquires, mut computes, mut verifies) = (0, 0, 0, 0);
+        let mut awaited = BTreeSet::<AssetId>::default();
+        let asset_store = &self.inner.asset_store;
+        let mut await_asset = |asset_id: &AssetId| {
+            if !asset_store.contains_key(asset_id) {
+                awaited.insert(*asset_id);
+            }
+        };
+        for ins in self.todo_instructions.iter() {
+            match ins {
+                Instruction::SendAssetTo { .. } => sends += 1,
+                Instruction::AcquireAssetFrom { asset_id, .. } => {
+                    acquires += 1;
+                    await_asset(asset_id);
+                }
+                Instruction::ComputeAssetData(compute_args) => {
+                    computes += 1;
+                    compute_args.needed_assets().for_each(&mut await_asset);
+                }
+                Instruction::Verify { asset_id, .. } => {
+                    verifies += 1;
+                    await_asset(asset_id);
+                }
+            }
+        }
+        let mut held: Vec<u32> =
+            asset_store.asset_ids().iter().map(|asset_id| asset_id.0).collect();
+        held.sort_unstable();
+        let awaited: Vec<u32> = awaited.iter().map(|asset_id| asset_id.0).collect();
+        format!(
+            "{} todo: {} send, {} acquire, {} compute, {} verify\nholding assets {:?}\nawaiting assets {:?}",
+            self.todo_instructions.len(),
+            sends,
+            acquires,
+            computes,
+            verifies,
+            held,
+            awaited,
+        )
+    }
+    /// Log what each remaining instruction is waiting for
+    fn log_blocked(&mut self, blocked_for: Duration) {
+        let summary = self.pending_summary();
+        log!(self.inner.logger, "No progress for {:?} with {}", blocked_for, summary);
+        for ins in self.todo_instructions.iter() {
+            let waiting_for = self.inner.waiting_for(ins);
+            log!(self.inner.logger, "Blocked on {} for {:?}: {}", ins, blocked_for, waiting_for);
+        }
+    }
+    /// Evict expired assets, except those in use. Returns how long until the next asset may
+    /// expire, if any.
+    fn evict_expired_assets(&mut self) -> Option<Duration> {
+        self.inner.config.asset_ttl?;
+        let keep = self.assets_in_use();
+        self.inner.evict_expired(&keep)
+    }
+    /// Assets which are never evicted: goals, those which instructions to do refer to, and those
+    /// yet to be pushed to subscribers. Evicting these would only have them re-fetched straight
+    /// away, and evicted again once expired, over and over.
+    fn assets_in_use(&self) -> HashSet<AssetId> {
+        let mut keep = self.inner.goals.clone();
+        keep.extend(self.inner.stale_subscribers.iter().map(|&(_, asset_id)| asset_id));
+        for ins in self.todo_instructions.iter() {
+            match ins {
+                Instruction::SendAssetTo { asset_id, .. }
+                | Instruction::AcquireAssetFrom { asset_id, .. }
+                | Instruction::Verify { asset_id, .. } => {
+                    keep.insert(*asset_id);
+                }
+                Instruction::ComputeAssetData(compute_args) => {
+                    keep.extend(compute_args.needed_assets().copied());
+                    keep.extend(self.inner.optional_candidates(compute_args));
+                }
+            }
+        }
+        keep
+    }
+    /// Drop the outputs computed here for another result site, once no longer in use,
+    /// e.g., once they have been sent on.
+    fn drop_unused_results(&mut self) {
+        if self.inner.results_elsewhere.is_empty() {
+            return;
+        }
+        let keep = self.assets_in_use();
+        let inner = &mut self.inner;
+        let unused = inner
+            .results_elsewhere
+            .iter()
+            .filter(|asset_id| !keep.contains(asset_id))
+            .copied()
+            .collect::<Vec<_>>();
+        for asset_id in unused {
+            inner.results_elsewhere.remove(&asset_id);
+            inner.asset_store.remove(&asset_id);
+            inner.last_accessed_at.remove(&asset_id);
+            inner.acquired_from.remove(&asset_id);
+            log!(inner.logger, "Dropped asset {}, which is kept at its result site", asset_id.0);
+        }
+    }
+    /// How long until the next acquisition with a timeout times out, if any
+    fn until_acquire_timeout(&self) -> Option<Duration> {
+        self.todo_instructions
+            .iter()
+            .filter_map(|ins| match ins {
+                Instruction::AcquireAssetFrom { asset_id, timeout: Some(timeout), .. } => {
+                    let since = self.inner.acquiring_since.get(asset_id)?;
+                    Some(timeout.checked_sub(since.elapsed()).unwrap_or_default())
+                }
+                _ => None,
+            })
+            .min()
+    }
+    /// Handle for adding further sites to this site's network
+    pub fn coordinator(&self) -> Coordinator {
+        Coordinator { outboxes: self.inner.outboxes.clone(), config: self.inner.config.clone() }
+    }
+    /// Restrict which sites may request which of this site's assets. Unrestricted by default.
+    pub fn set_access_control(&mut self, access_control: Option<AccessControl>) {
+        self.inner.access_control = access_control;
+    }
+    /// Which sites may compute with which compute assets, e.g., `Problem::may_compute`. Given
+    /// `SiteConfig::work_stealing`, computes are only handed over to sites permitted to do them.
+    pub fn set_may_compute(&mut self, may_compute: HashSet<(SiteId, AssetId)>) {
+        self.inner.may_compute = may_compute;
+    }
+    /// Reorder the computes among the instructions, heaviest first, leaving the other
+    /// instructions in place. Computes of equal weight keep their order.
+    fn order_computes_by_weight(&mut self) {
+        fn weight(ins: &Instruction) -> u64 {
+            match ins {
+                Instruction::ComputeAssetData(compute_args) => compute_args.weight,
+                _ => 0,
+            }
+        }
+        let slots: Vec<usize> = (0..self.todo_instructions.len())
+            .filter(|&i| matches!(self.todo_instructions[i], Instruction::ComputeAssetData(_)))
+            .collect();
+        // Insertion sort, as there are few computes, and swaps leave the other slots alone
+        for k in 1..slots.len() {
+            let mut j = k;
+            while j > 0
+                && weight(&self.todo_instructions[slots[j - 1]])
+                    < weight(&self.todo_instructions[slots[j]])
+            {
+                self.todo_instructions.swap(slots[j - 1], slots[j]);
+                j -= 1;
+            }
+        }
+    }
+    /// Scan the instructions once, completing those which are completable
+    fn scan_instructions(&mut self) -> Result<ScanResult, ExecError> {
+        // Any instruction might be completable!
+        if let Some(rate_limiter) = &mut self.inner.rate_limiter {
+            rate_limiter.starved = false;
+        }
+        self.inner.full_outboxes.clear();
+        self.inner.push_to_subscribers();
+        if let Some(rng) = &mut self.inner.scan_shuffle {
+            use rand::seq::SliceRandom;
+            self.todo_instructions.shuffle(rng);
+        }
+        if self.inner.config.heaviest_computes_first {
+            self.order_computes_by_weight();
+        }
+        if self.inner.config.prefetch_inputs {
+            self.prefetch_compute_inputs();
+        }
+
+        let mut completed = 0;
+        let mut i = 0;
+        // loop invariant: todo instructions with indices in [0..i)] would return InsExecResult::Incomplete if checked with `try_complete`.
+        while i < self.todo_instructions.len() {
+            let result = self.inner.try_complete(&mut self.todo_instructions[i])?;
+            match result {
+                InsExecResult::Incomplete => {
+                    // retain this instruction, consider the next
+                    i += 1;
+                }
+                InsExecResult::Complete { added_assets_to_store } => {
+                    // remove this instruction, and ...
+                    let done = self.todo_instructions.remove(i);
+                    completed += 1;
+                    let cancelled_acquires = match &done {
+                        Instruction::ComputeAssetData(compute_args) => {
+                            self.cancel_unneeded_acquires(compute_args)
                         }
+                        _ => false,
+                    };
+                    if added_assets_to_store || cancelled_acquires {
+                        // ... consider all instructions
+                        return Ok(ScanResult { completed, rescan: true });
                     }
-                    Msg::AssetData { asset_id, asset_data } => {
-                        self.inner.last_requested_at.remove(&asset_id);
-                        self.inner.asset_store.insert(asset_id, asset_data);
-                        continue 'execute_loop;
+                    // ... consider all subsequent instructions
+                }
+            }
+        }
+        if completed > 0 {
+            self.drop_unused_results();
+        }
+        Ok(ScanResult { completed, rescan: false })
+    }
+    /// Handle a received message. Returns whether instructions may have become completable,
+    /// e.g., as it added an asset to the store.
+    fn handle_msg(&mut self, signed_msg: SignedMsg) -> bool {
+        if let Some(max_msg_bytes) = self.inner.config.max_msg_bytes {
+            // Checked first, as it is cheaper than verification
+            if signed_msg.wire_len() > max_msg_bytes {
+                log!(
+                    self.inner.logger,
+                    "Dropped msg from {} of {} bytes, exceeding the maximum of {}",
+                    signed_msg.sender().short(),
+                    signed_msg.wire_len(),
+                    max_msg_bytes
+                );
+                return false;
+            }
+        }
+        if self.inner.config.sender_policy == SenderPolicy::KnownPeers
+            && !self.inner.outboxes.read().unwrap().contains_key(signed_msg.sender())
+        {
+            log!(self.inner.logger, "Dropped msg from unknown {}", signed_msg.sender().full());
+            return false;
+        }
+        let verified = if self.inner.config.untrusted_local || self.inner.inbox_verified {
+            Ok(())
+        } else {
+            signed_msg.verify_into(&mut self.inner.signing_buffer)
+        };
+        if let Err(e) = verified {
+            log!(
+                self.inner.logger,
+                "Msg verification failed from {} {:?} {:?}",
+                signed_msg.sender().full(),
+                &signed_msg.msg,
+                e
+            );
+            self.inner.metrics.verification_failures += 1;
+            return false;
+        }
+        if self.inner.config.check_msg_structure {
+            if let Err(e) = signed_msg.check_structure() {
+                log!(
+                    self.inner.logger,
+                    "Dropped malformed msg from {} {:?} {:?}",
+                    signed_msg.sender().full(),
+                    &signed_msg.msg,
+                    e
+                );
+                self.inner.metrics.malformed_msgs += 1;
+                return false;
+            }
+        }
+        log!(
+            self.inner.logger,
+            "Received verified msg from {} {:?}",
+            signed_msg.sender().short(),
+            &signed_msg.msg
+        );
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            name: "recv",
+            parent: &self.inner.span,
+            from = %signed_msg.sender().short(),
+            msg = ?signed_msg.msg
+        );
+        let sender = *signed_msg.sender();
+        if self.inner.last_heard.contains_key(&sender) {
+            self.inner.last_heard.insert(sender, Instant::now());
+        }
+        if self.inner.suspected.remove(&sender) {
+            log!(self.inner.logger, "Heard from suspected {} again", sender.short());
+        }
+        match signed_msg.msg {
+            Msg::AssetDataRequest { asset_id, priority } => {
+                self.handle_asset_request(sender, asset_id, priority);
+                false
+            }
+            Msg::AssetData { asset_id, asset_data } => {
+                self.inner.store_received(sender, asset_id, asset_data);
+                true
+            }
+            Msg::AssetChunk { asset_id, index, count, chunk } => {
+                match self.inner.receive_chunk(sender, asset_id, index, count, chunk) {
+                    Some(asset_data) => {
+                        self.inner.store_received(sender, asset_id, asset_data);
+                        true
                     }
+                    None => false,
+                }
+            }
+            Msg::Subscribe { asset_id } => {
+                let subscriber = *signed_msg.sender();
+                if self.access_granted(subscriber, asset_id) {
+                    log!(self.inner.logger, "{} subscribed to {:?}", subscriber.short(), asset_id);
+                    self.inner.subscribers.entry(asset_id).or_default().insert(subscriber);
+                }
+                false
+            }
+            Msg::Nack { asset_id, reason } => {
+                log!(
+                    self.inner.logger,
+                    "{} will not serve {:?}: {:?}",
+                    sender.short(),
+                    asset_id,
+                    reason
+                );
+                self.inner.nack(sender, asset_id, reason);
+                // Other sources may be requested right away
+                self.inner.request_again_at.remove(&asset_id);
+                self.request_from_next_source(asset_id);
+                true
+            }
+            Msg::Register { public_key } => {
+                let joined = SiteId::from_public_key_ref(&public_key);
+                if joined == signed_msg.sender() {
+                    log!(self.inner.logger, "Site {} joined", joined.full());
+                } else {
+                    log!(
+                        self.inner.logger,
+                        "Ignoring registration of {} sent by {}",
+                        joined.full(),
+                        signed_msg.sender().full()
+                    );
+                }
+                false
+            }
+            Msg::StealRequest => {
+                if self.inner.config.work_stealing {
+                    self.delegate_compute(sender);
                 }
+                false
             }
+            Msg::Delegate { compute_args } => self.take_over_compute(sender, compute_args),
+            Msg::Heartbeat => false,
+        }
+    }
+    /// Hand over one of this site's ready computes to `thief`, which asked for work, keeping at
+    /// least one for itself. Only computes `thief` may do, with access to their needed assets,
+    /// are handed over. Their outputs are acquired from `thief` instead.
+    fn delegate_compute(&mut self, thief: SiteId) {
+        let ready: Vec<usize> = (0..self.todo_instructions.len())
+            .filter(|&i| match &self.todo_instructions[i] {
+                Instruction::ComputeAssetData(compute_args) => compute_args
+                    .needed_assets()
+                    .all(|asset_id| self.inner.asset_store.contains_key(asset_id)),
+                _ => false,
+            })
+            .collect();
+        if ready.len() < 2 {
+            return;
         }
+        // Hand over the last in order, i.e., the least urgent
+        let delegable = ready.into_iter().rev().find_map(|i| match &self.todo_instructions[i] {
+            Instruction::ComputeAssetData(compute_args)
+                if self.may_delegate(thief, compute_args) =>
+            {
+                Some((i, compute_args.clone()))
+            }
+            _ => None,
+        });
+        let (i, compute_args) = match delegable {
+            Some(delegable) => delegable,
+            None => return,
+        };
+        for asset_id in compute_args.needed_assets().copied().collect::<Vec<_>>() {
+            if self.inner.sent_assets.contains(&(thief, asset_id)) {
+                // e.g., for a compute delegated before
+                continue;
+            }
+            let asset_data = match self.inner.asset_store.get(&asset_id) {
+                Some(asset_data) => asset_data.into_owned(),
+                None => return,
+            };
+            if !self.inner.send_asset(&thief, asset_id, asset_data) {
+                return;
+            }
+        }
+        if !self.inner.send_to(&thief, Msg::Delegate { compute_args: compute_args.clone() }) {
+            return;
+        }
+        log!(self.inner.logger, "Delegated computation {} to {}", compute_args, thief.short());
+        self.inner.metrics.computes_delegated += 1;
+        let acquires = compute_args.outputs.iter().map(|&asset_id| Instruction::AcquireAssetFrom {
+            asset_id,
+            site_id: thief,
+            priority: 0,
+            timeout: None,
+        });
+        self.todo_instructions.splice(i..=i, acquires);
+    }
+    /// Whether the given compute may be handed over to `thief`
+    fn may_delegate(&self, thief: SiteId, compute_args: &ComputeArgs) -> bool {
+        // Consumed and optional inputs would have their fate decided elsewhere.
+        // Outputs for a result site are sent on by the planned compute site only.
+        !compute_args.consume_inputs
+            && compute_args.result_site.is_none()
+            && compute_args.optional_inputs.is_empty()
+            && compute_args.input_range.is_none()
+            && !compute_args.outputs.is_empty()
+            && self.inner.may_compute.contains(&(thief, compute_args.compute_asset))
+            && compute_args.needed_assets().all(|&asset_id| match &self.inner.access_control {
+                Some(access_control) => {
+                    access_control.check(thief, asset_id) == AccessCheck::Granted
+                }
+                None => true,
+            })
+    }
+    /// Take over the compute delegated by `delegator`, if it was asked for work, and has not
+    /// answered since. Returns whether it was taken over.
+    fn take_over_compute(&mut self, delegator: SiteId, compute_args: ComputeArgs) -> bool {
+        // Each ask is answered with one compute at most
+        if !self.inner.steal_asked.remove(&delegator) {
+            log!(
+                self.inner.logger,
+                "Ignoring unrequested delegation of {} by {}",
+                compute_args,
+                delegator.short()
+            );
+            return false;
+        }
+        log!(
+            self.inner.logger,
+            "Took over computation {} from {}",
+            compute_args,
+            delegator.short()
+        );
+        self.inner.metrics.computes_taken_over += 1;
+        // The delegator acquires the outputs from this site
+        if let Some(access_control) = &mut self.inner.access_control {
+            for &asset_id in compute_args.outputs.iter() {
+                access_control.grant(delegator, asset_id, None);
+            }
+        }
+        let ins = Instruction::ComputeAssetData(compute_args);
+        if !self.todo_instructions.contains(&ins) {
+            self.todo_instructions.push(ins);
+        }
+        true
+    }
+    /// Ask all peers for work, at most once per `SiteConfig::idle_timeout`
+    fn ask_for_work(&mut self) {
+        let idle_timeout = self.inner.config.idle_timeout;
+        if self.inner.steal_asked_at.is_some_and(|at| at.elapsed() < idle_timeout) {
+            return;
+        }
+        self.inner.steal_asked_at = Some(Instant::now());
+        // Unanswered asks expire, such that late delegations are ignored
+        self.inner.steal_asked.clear();
+        for peer in self.inner.peers() {
+            if self.inner.send_to(&peer, Msg::StealRequest) {
+                self.inner.steal_asked.insert(peer);
+            }
+        }
+    }
+    /// Send peers a heartbeat if one is due, and report the peers this site awaits assets from
+    /// which missed several heartbeats. Returns how long until the next heartbeat is due.
+    fn keep_heartbeat(&mut self) -> Option<Duration> {
+        let interval = self.inner.config.heartbeat_interval?;
+        let now = Instant::now();
+        let sent_at = match self.inner.heartbeat_sent_at {
+            Some(sent_at) if now - sent_at < interval => sent_at,
+            _ => {
+                for peer in self.inner.peers() {
+                    // Dropped if the outbox is full, as another heartbeat follows
+                    let _ = self.inner.send_to(&peer, Msg::Heartbeat);
+                }
+                *self.inner.heartbeat_sent_at.insert(now)
+            }
+        };
+        let awaited: HashSet<SiteId> = self
+            .todo_instructions
+            .iter()
+            .filter_map(|ins| match ins {
+                Instruction::AcquireAssetFrom { asset_id, site_id, .. }
+                    if !self.inner.asset_store.contains_key(asset_id) =>
+                {
+                    Some(*site_id)
+                }
+                _ => None,
+            })
+            .collect();
+        self.inner.last_heard.retain(|peer, _| awaited.contains(peer));
+        for peer in awaited {
+            // Peers are given the full period from when they are first awaited
+            let heard_at = *self.inner.last_heard.entry(peer).or_insert(now);
+            let silent_for = now - heard_at;
+            if silent_for >= interval * SiteInner::MISSED_HEARTBEATS
+                && self.inner.suspected.insert(peer)
+            {
+                log!(
+                    self.inner.logger,
+                    "Suspecting {} failed, silent for {:?}",
+                    peer.short(),
+                    silent_for
+                );
+                if let Some(failure_watcher) = &self.inner.failure_watcher {
+                    let _ = failure_watcher.send(peer);
+                }
+            }
+        }
+        Some(interval.saturating_sub(now - sent_at))
+    }
+    fn update_progress(&self) {
+        let SiteProgress { asset_count, pending_count } = &self.inner.progress;
+        asset_count.store(self.inner.asset_store.len(), Ordering::Relaxed);
+        pending_count.store(self.todo_instructions.len(), Ordering::Relaxed);
+    }
+    /// Do the work `execute` would, without waiting: complete instructions until none are
+    /// completable, then handle the messages already received. Returns whether anything happened.
+    pub fn step(&mut self) -> Result<bool, ExecError> {
+        let mut progressed = self.carry_out_commands();
+        loop {
+            let scanned = self.scan_instructions()?;
+            progressed |= scanned.completed > 0;
+            if !scanned.rescan {
+                break;
+            }
+        }
+        self.evict_expired_assets();
+        self.keep_heartbeat();
+        while let Some(signed_msg) = self.inner.try_receive() {
+            self.handle_msg(signed_msg);
+            progressed = true;
+        }
+        self.update_progress();
+        Ok(progressed)
+    }
+    /// Consumes the calling thread
+    pub fn execute(&mut self) -> Result<(), ExecError> {
+        if !self.inner.config.verify_on_thread || self.inner.config.untrusted_local {
+            return self.execute_loop();
+        }
+        // Messages are verified on another thread, which feeds a channel replacing the inbox
+        let (verified_sender, verified_inbox) = match self.inner.config.channel_capacity {
+            Some(capacity) => crossbeam_channel::bounded(capacity),
+            None => crossbeam_channel::unbounded(),
+        };
+        let (stop_sender, stop) = crossbeam_channel::bounded::<()>(0);
+        let inbox = std::mem::replace(&mut self.inner.inbox, verified_inbox);
+        let unverified = std::mem::take(&mut self.inner.inbox_buffer);
+        let verifier =
+            std::thread::spawn(move || forward_verified(unverified, inbox, verified_sender, stop));
+        self.inner.inbox_verified = true;
+        let result = self.execute_loop();
+        drop(stop_sender);
+        let (inbox, unforwarded, failures) = verifier.join().unwrap();
+        self.inner.inbox_verified = false;
+        // Verified but not yet handled. These are verified again once handled.
+        let verified_inbox = std::mem::replace(&mut self.inner.inbox, inbox);
+        self.inner.inbox_buffer.extend(verified_inbox.try_iter());
+        self.inner.inbox_buffer.extend(unforwarded);
+        self.inner.metrics.verification_failures += failures;
+        result
+    }
+    fn execute_loop(&mut self) -> Result<(), ExecError> {
+        let start = Instant::now();
+        log!(
+            self.inner.logger,
+            "Started executing at {:?}. My site_id is {}",
+            &start,
+            SiteId::from_public_key_ref(&self.inner.keypair.public).full(),
+        );
+        if self.inner.config.log_initial_instructions {
+            let count = self.todo_instructions.len();
+            log!(self.inner.logger, "Starting with {} instructions:", count);
+            for ins in self.todo_instructions.iter() {
+                log!(self.inner.logger, "  {}", ins);
+            }
+        }
+        let mut last_progress = start; // when an instruction was last completed
+        let mut last_report = start; // when the watchdog last reported blocked instructions
+        let mut finished_at = None; // when this site first held all its goals
+        'execute_loop: loop {
+            self.carry_out_commands();
+            let scanned = self.scan_instructions()?;
+            self.update_progress();
+            if scanned.completed > 0 {
+                last_progress = Instant::now();
+            }
+            if scanned.rescan {
+                if self.inner.config.work_stealing {
+                    // Hear out peers asking for work between computes, not only once stuck
+                    while let Some(signed_msg) = self.inner.try_receive() {
+                        self.handle_msg(signed_msg);
+                    }
+                }
+                continue 'execute_loop;
+            }
+            // No instructions are completable.
+
+            if finished_at.is_none() && !self.inner.goals.is_empty() && self.inner.has_all_goals() {
+                log!(self.inner.logger, "Holding all goals after {:?}", start.elapsed());
+                finished_at = Some(Instant::now());
+            }
+
+            if self.todo_instructions.is_empty() {
+                log!(self.inner.logger, "Ran out of TODO instructions after {:?}", start.elapsed());
+            }
+
+            // receive 1+ messages until we have further populated the asset store
+            // ... unless this site's work is done, and it only serves its peers until its grace is over
+            let serving_only = finished_at.is_some() && self.inner.config.goal_grace.is_some();
+            let mut silent_since = Instant::now();
+            loop {
+                let paused_at = Instant::now();
+                if self.inner.pause.wait_while_paused() {
+                    // Time spent paused does not count towards timeouts
+                    log!(self.inner.logger, "Resumed after pausing for {:?}", paused_at.elapsed());
+                    silent_since = Instant::now();
+                    last_progress = Instant::now();
+                    continue 'execute_loop;
+                }
+                let until_expiry = self.evict_expired_assets();
+                self.update_progress();
+                let until_report = match self.inner.config.watchdog_interval {
+                    Some(interval) if !self.todo_instructions.is_empty() => {
+                        let quiet_for = last_progress.max(last_report).elapsed();
+                        if quiet_for >= interval {
+                            self.log_blocked(last_progress.elapsed());
+                            last_report = Instant::now();
+                            Some(interval)
+                        } else {
+                            Some(interval - quiet_for)
+                        }
+                    }
+                    _ => None,
+                };
+                // Woken for these, the instructions are scanned again
+                let retry_timeout = self
+                    .inner
+                    .recv_timeout()
+                    .into_iter()
+                    .chain(self.until_acquire_timeout())
+                    .chain(self.inner.until_nack_expiry())
+                    .min();
+                // An idle site awaiting the other sites' goals keeps serving them, checking each
+                // `idle_timeout` whether it may exit yet
+                let awaiting_goals = self.todo_instructions.is_empty() && self.inner.awaits_goals();
+                if awaiting_goals && self.inner.config.work_stealing {
+                    self.ask_for_work();
+                }
+                let until_poll =
+                    if awaiting_goals { Some(self.inner.config.idle_timeout) } else { None };
+                let until_heartbeat = self.keep_heartbeat();
+                let exit_timeout = if self.todo_instructions.is_empty() && !awaiting_goals {
+                    self.inner.config.idle_timeout
+                } else {
+                    self.inner.config.quiescence_timeout
+                };
+                let until_exit =
+                    exit_timeout.checked_sub(silent_since.elapsed()).unwrap_or_default();
+                let until_grace_over = match (finished_at, self.inner.config.goal_grace) {
+                    (Some(finished_at), Some(grace)) => {
+                        Some(grace.checked_sub(finished_at.elapsed()).unwrap_or_default())
+                    }
+                    _ => None,
+                };
+                if until_grace_over == Some(Duration::default()) {
+                    let summary = self.pending_summary();
+                    log!(self.inner.logger, "Grace period over. Exiting with {}", summary);
+                    self.inner.stop_serving();
+                    return Ok(());
+                }
+                let timeout = retry_timeout
+                    .into_iter()
+                    .chain(until_report)
+                    .chain(until_grace_over)
+                    .chain(until_expiry)
+                    .chain(until_poll)
+                    .chain(until_heartbeat)
+                    .fold(until_exit, Duration::min);
+                let signed_msg = match self.inner.receive(timeout) {
+                    Received::Msg(signed_msg) => {
+                        // Heartbeats keep peers from being suspected, but not this site from exiting
+                        if !matches!(signed_msg.msg, Msg::Heartbeat) {
+                            silent_since = Instant::now();
+                        }
+                        signed_msg
+                    }
+                    Received::Command(command) => {
+                        self.carry_out(command);
+                        self.carry_out_commands();
+                        continue 'execute_loop;
+                    }
+                    Received::Timeout if retry_timeout.is_some() => continue 'execute_loop,
+                    // woken up for the watchdog, the end of the grace period, or to poll the goals
+                    Received::Timeout if silent_since.elapsed() < exit_timeout => continue,
+                    Received::Timeout if self.todo_instructions.is_empty() => {
+                        log!(self.inner.logger, "Idle for {:?}. Exiting", exit_timeout);
+                        self.inner.stop_serving();
+                        return Ok(());
+                    }
+                    Received::Timeout => {
+                        let summary = self.pending_summary();
+                        log!(self.inner.logger, "RECV timeout. Exiting with {}", summary);
+                        self.inner.stop_serving();
+                        return Ok(());
+                    }
+                };
+                if self.handle_msg(signed_msg) && !serving_only {
+                    continue 'execute_loop;
+                }
+            }
+        }
+    }
+}
+
+/// Passes on the messages of `unverified` and then `inbox` which pass verification to `verified`,
+/// in order, until `stop` is disconnected. Returns the inbox, the messages not yet passed on,
+/// and how many failed verification.
+fn forward_verified(
+    mut unverified: VecDeque<SignedMsg>,
+    inbox: Receiver<SignedMsg>,
+    verified: Sender<SignedMsg>,
+    stop: Receiver<()>,
+) -> (Receiver<SignedMsg>, VecDeque<SignedMsg>, u64) {
+    let mut buffer = vec![];
+    let mut failures = 0;
+    loop {
+        while let Some(mut signed_msg) = unverified.pop_front() {
+            if signed_msg.verify_into(&mut buffer).is_err() {
+                failures += 1;
+                continue;
+            }
+            // Wait for room in `verified`, unless stopped in the meantime
+            loop {
+                match verified.try_send(signed_msg) {
+                    Ok(()) => break,
+                    Err(crossbeam_channel::TrySendError::Full(unsent)) => signed_msg = unsent,
+                    Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
+                        return (inbox, unverified, failures)
+                    }
+                }
+                let waited = stop.recv_timeout(SiteInner::FULL_OUTBOX_RETRY_PERIOD);
+                if waited == Err(crossbeam_channel::RecvTimeoutError::Disconnected) {
+                    unverified.push_front(signed_msg);
+                    return (inbox, unverified, failures);
+                }
+            }
+        }
+        crossbeam_channel::select! {
+            recv(inbox) -> signed_msg => unverified.extend(signed_msg),
+            recv(stop) -> _ => return (inbox, unverified, failures),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sites(n: usize) -> (Vec<SiteId>, HashMap<SiteId, Site>) {
+        new_sites_with_logger_factory(n, |_| Box::new(NullLogger))
+    }
+
+    fn acquire(asset_id: AssetId, site_id: SiteId) -> Instruction {
+        Instruction::AcquireAssetFrom { asset_id, site_id, priority: 0, timeout: None }
+    }
+
+    fn compute(inputs: Vec<AssetId>, output: AssetId, compute_asset: AssetId) -> ComputeArgs {
+        ComputeArgs {
+            inputs,
+            optional_inputs: vec![],
+            min_optional_inputs: 0,
+            input_range: None,
+            outputs: vec![output],
+            compute_asset,
+            alt_compute_assets: vec![],
+            consume_inputs: false,
+            reducible: false,
+            weight: 1,
+            label: None,
+            result_site: None,
+        }
+    }
+
+    /// Sites with work stealing, where `bob` may delegate computes with asset 0 to `amy`, which
+    /// asked for work. `bob` is to do the given computes, with assets 0 and 1 at hand.
+    fn stealing_sites(computes: Vec<ComputeArgs>) -> (SiteId, SiteId, HashMap<SiteId, Site>) {
+        let config = SiteConfig { work_stealing: true, ..Default::default() };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.set_may_compute(maplit::hashset! { (amy, AssetId(0)), (bob, AssetId(0)) });
+        bob_site.insert_asset(AssetId(0), AssetData::from(0));
+        bob_site.insert_asset(AssetId(1), AssetData::from(1));
+        bob_site.todo_instructions.extend(computes.into_iter().map(Instruction::ComputeAssetData));
+        sites.get_mut(&amy).unwrap().ask_for_work();
+        (amy, bob, sites)
+    }
+
+    /// Collects the lines written to it
+    #[derive(Debug)]
+    struct LineLogger {
+        line: Vec<u8>,
+        lines: Arc<Mutex<Vec<String>>>,
+    }
+    impl Logger for LineLogger {
+        fn line_writer(&mut self) -> Option<&mut dyn Write> {
+            self.line.clear();
+            Some(&mut self.line)
+        }
+        fn line_done(&mut self) {
+            let line = String::from_utf8_lossy(&self.line).trim_end().to_owned();
+            self.lines.lock().unwrap().push(line);
+        }
+    }
+
+    fn delegated(site: &mut Site) -> Vec<ComputeArgs> {
+        let inbox = site.drain_inbox().into_iter();
+        inbox
+            .filter_map(|signed_msg| match signed_msg.msg {
+                Msg::Delegate { compute_args } => Some(compute_args),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn computes_for_result_sites_are_not_delegated() {
+        let [f, x] = [AssetId(0), AssetId(1)];
+        let for_result_site = |output, result_site| ComputeArgs {
+            result_site: Some(result_site),
+            ..compute(vec![x], AssetId(output), f)
+        };
+        let (amy, bob, mut sites) = stealing_sites(vec![]);
+        let computes = vec![for_result_site(2, bob), for_result_site(3, bob)];
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.todo_instructions.extend(computes.into_iter().map(Instruction::ComputeAssetData));
+        for signed_msg in bob_site.drain_inbox() {
+            bob_site.handle_msg(signed_msg);
+        }
+        assert_eq!(delegated(sites.get_mut(&amy).unwrap()), vec![]);
+        assert_eq!(sites[&bob].metrics().computes_delegated, 0);
+    }
+
+    #[test]
+    fn one_delegation_is_taken_over_per_ask() {
+        let [f, x] = [AssetId(0), AssetId(1)];
+        let computes = (2..5).map(|output| compute(vec![x], AssetId(output), f)).collect();
+        let (amy, bob, mut sites) = stealing_sites(computes);
+        let bob_site = sites.get_mut(&bob).unwrap();
+        for signed_msg in bob_site.drain_inbox() {
+            bob_site.handle_msg(signed_msg);
+        }
+        // bob delegates once more, unasked
+        bob_site.delegate_compute(amy);
+        let amy_site = sites.get_mut(&amy).unwrap();
+        let inbox = amy_site.drain_inbox();
+        assert_eq!(inbox.iter().filter(|m| matches!(m.msg, Msg::Delegate { .. })).count(), 2);
+        for signed_msg in inbox {
+            amy_site.handle_msg(signed_msg);
+        }
+        assert_eq!(amy_site.metrics().computes_taken_over, 1);
+        assert_eq!(
+            amy_site.todo_instructions,
+            vec![Instruction::ComputeAssetData(compute(vec![x], AssetId(4), f))]
+        );
+    }
+
+    #[test]
+    fn compressed_and_uncompressed_copies_compute_alike() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        sites.get_mut(&amy).unwrap().set_asset_store(Box::new(GzipAssetStore::default()));
+        sites.get_mut(&bob).unwrap().insert_asset(x, AssetData::from(1));
+        // amy stores its copy of x compressed, as received from bob
+        sites.get_mut(&amy).unwrap().todo_instructions.push(acquire(x, bob));
+        for site_id in &[amy, bob] {
+            let site = sites.get_mut(site_id).unwrap();
+            site.insert_asset(f, AssetData::from(0));
+            site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![x], y, f)));
+        }
+        for _ in 0..3 {
+            for site_id in &[amy, bob] {
+                sites.get_mut(site_id).unwrap().step().unwrap();
+            }
+        }
+        let output = |site_id| sites[&site_id].inner.asset_store.get(&y).map(Cow::into_owned);
+        assert!(output(amy).is_some());
+        assert_eq!(output(amy), output(bob));
+    }
+
+    #[test]
+    fn identical_content_received_under_two_ids_is_shared() {
+        let config = SiteConfig { share_identical_content: true, ..Default::default() };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(7));
+        bob_site.insert_asset(y, AssetData::from(7));
+        sites
+            .get_mut(&amy)
+            .unwrap()
+            .todo_instructions
+            .extend(vec![acquire(x, bob), acquire(y, bob)]);
+        for _ in 0..3 {
+            for site_id in &[amy, bob] {
+                sites.get_mut(site_id).unwrap().step().unwrap();
+            }
+        }
+        let asset_store = &sites[&amy].inner.asset_store;
+        match (asset_store.get(&x), asset_store.get(&y)) {
+            (Some(Cow::Borrowed(x)), Some(Cow::Borrowed(y))) => assert!(std::ptr::eq(x, y)),
+            held => panic!("expected borrowed data for both, got {:?}", held),
+        }
+    }
+
+    #[test]
+    fn compute_cancelled_while_executing() {
+        let config = SiteConfig {
+            idle_timeout: Duration::from_millis(50),
+            quiescence_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.insert_asset(f, AssetData::from(0));
+        // bob never sends x, so the compute waits until cancelled
+        amy_site.todo_instructions.push(acquire(x, bob));
+        amy_site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![x], y, f)));
+        let control = amy_site.control_handle();
+        let started = Instant::now();
+        let executing = std::thread::spawn(move || {
+            amy_site.execute().unwrap();
+            amy_site
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        control.cancel_compute(compute(vec![x], y, f));
+        let amy_site = executing.join().unwrap();
+        // Exited for idling, long before giving up on bob
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(amy_site.todo_instructions.is_empty());
+        assert!(!amy_site.has_asset(&y));
+    }
+
+    #[test]
+    fn denied_request_falls_back_to_next_source() {
+        let (site_ids, mut sites) = sites(3);
+        let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
+        let x = AssetId(0);
+        for &site_id in &[bob, cho] {
+            sites.get_mut(&site_id).unwrap().insert_asset(x, AssetData::from(7));
+        }
+        // bob grants nobody access to x
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.set_access_control(Some(AccessControl::new(Arc::new(SystemClock))));
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.todo_instructions.extend(vec![acquire(x, bob), acquire(x, cho)]);
+        // amy requests x of bob only, as it was requested recently when the second acquire is reached
+        amy_site.step().unwrap();
+        let requests = |site: &mut Site| {
+            site.drain_inbox()
+                .into_iter()
+                .filter(|signed_msg| matches!(signed_msg.msg, Msg::AssetDataRequest { .. }))
+                .count()
+        };
+        assert_eq!(requests(sites.get_mut(&cho).unwrap()), 0);
+        // bob declines, and amy requests x of cho upon the Nack, without waiting to scan again
+        sites.get_mut(&bob).unwrap().step().unwrap();
+        let amy_site = sites.get_mut(&amy).unwrap();
+        let mut inbox = amy_site.drain_inbox();
+        assert!(matches!(
+            inbox[..],
+            [SignedMsg { msg: Msg::Nack { asset_id, reason: NackReason::Denied }, .. }] if asset_id == x
+        ));
+        amy_site.handle_msg(inbox.pop().unwrap());
+        assert_eq!(requests(sites.get_mut(&cho).unwrap()), 1);
+    }
+
+    #[test]
+    fn denied_acquire_completes_from_fallback() {
+        let (site_ids, mut sites) = sites(3);
+        let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
+        let x = AssetId(0);
+        for &site_id in &[bob, cho] {
+            sites.get_mut(&site_id).unwrap().insert_asset(x, AssetData::from(7));
+        }
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.set_access_control(Some(AccessControl::new(Arc::new(SystemClock))));
+        sites.get_mut(&amy).unwrap().todo_instructions.push(acquire(x, bob));
+        // amy acquired x from cho before, e.g., before evicting it
+        sites.get_mut(&amy).unwrap().inner.acquired_from.insert(x, cho);
+        for _ in 0..3 {
+            for site_id in &[amy, bob, cho] {
+                sites.get_mut(site_id).unwrap().step().unwrap();
+            }
+        }
+        let amy_site = sites.get_mut(&amy).unwrap();
+        assert!(amy_site.has_asset(&x));
+        amy_site.step().unwrap();
+        assert!(amy_site.todo_instructions.is_empty());
+    }
+
+    /// An event's name, the fields of its parent span, and its own fields
+    #[cfg(feature = "tracing")]
+    type TracedEvent = (&'static str, String, String);
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct EventCollector {
+        spans: std::sync::Mutex<Vec<String>>,
+        events: Arc<std::sync::Mutex<Vec<TracedEvent>>>,
+    }
+    #[cfg(feature = "tracing")]
+    struct FieldsVisitor(String);
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for FieldsVisitor {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0 += &format!("{}={:?} ", field.name(), value);
+        }
+    }
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for EventCollector {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut visitor = FieldsVisitor(String::new());
+            span.record(&mut visitor);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(visitor.0);
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut visitor = FieldsVisitor(String::new());
+            event.record(&mut visitor);
+            let span = event
+                .parent()
+                .map(|id| self.spans.lock().unwrap()[id.into_u64() as usize - 1].clone());
+            let record = (event.metadata().name(), span.unwrap_or_default(), visitor.0);
+            self.events.lock().unwrap().push(record);
+        }
+        fn enter(&self, _: &tracing::span::Id) {}
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn sends_receives_and_computes_are_traced() {
+        let collector = EventCollector::default();
+        let events = collector.events.clone();
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        let (amy, bob) = tracing::subscriber::with_default(collector, || {
+            let (site_ids, mut sites) = sites(2);
+            let [amy, bob] = [site_ids[0], site_ids[1]];
+            sites.get_mut(&bob).unwrap().insert_asset(x, AssetData::from(1));
+            let amy_site = sites.get_mut(&amy).unwrap();
+            amy_site.insert_asset(f, AssetData::from(0));
+            amy_site.todo_instructions.extend(vec![
+                acquire(x, bob),
+                Instruction::ComputeAssetData(compute(vec![x], y, f)),
+            ]);
+            for _ in 0..3 {
+                for site_id in &[amy, bob] {
+                    sites.get_mut(site_id).unwrap().step().unwrap();
+                }
+            }
+            assert!(sites[&amy].has_asset(&y));
+            (amy, bob)
+        });
+        let events = events.lock().unwrap();
+        let span_of = |site_id: SiteId| format!("id={} ", site_id.short());
+        let traced = |name: &str, site_id: SiteId, field: String| {
+            events.iter().any(|(n, span, fields)| {
+                *n == name && *span == span_of(site_id) && fields.contains(&field)
+            })
+        };
+        assert!(traced("send", amy, format!("to={}", bob.short())));
+        assert!(traced("recv", bob, format!("from={}", amy.short())));
+        assert!(traced("send", bob, format!("to={}", amy.short())));
+        assert!(traced("recv", amy, format!("from={}", bob.short())));
+        assert!(traced("compute", amy, "compute=".to_owned()));
+        assert!(!traced("compute", bob, "compute=".to_owned()));
+    }
+
+    /// Checks that its one input is even, computing nothing
+    #[derive(Debug)]
+    struct EvenCheck;
+    impl ComputeFn for EvenCheck {
+        fn compute(
+            &self,
+            store: &dyn AssetStore,
+            compute_args: &ComputeArgs,
+        ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+            let input = compute_args.inputs[0];
+            if store.get(&input).unwrap().bytes[0].is_multiple_of(2) {
+                Ok(HashMap::default())
+            } else {
+                Err(ComputeError { message: "odd".to_owned(), input: Some(input) })
+            }
+        }
+    }
+
+    #[test]
+    fn failed_check_without_outputs_is_an_exec_error() {
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let [f, even, odd] = [AssetId(0), AssetId(1), AssetId(2)];
+        site.set_compute_fn(Some(Arc::new(EvenCheck)));
+        site.insert_asset(f, AssetData::from(0));
+        site.insert_asset(even, AssetData::from(2));
+        site.insert_asset(odd, AssetData::from(3));
+        let check = |input| ComputeArgs { outputs: vec![], ..compute(vec![input], AssetId(9), f) };
+        site.todo_instructions.push(Instruction::ComputeAssetData(check(even)));
+        assert!(site.step().is_ok());
+        assert_eq!((site.todo_instructions.len(), site.asset_count()), (0, 3));
+
+        site.todo_instructions.push(Instruction::ComputeAssetData(check(odd)));
+        match site.step() {
+            Err(ExecError::ComputeFailed { compute_args, error }) => {
+                assert_eq!(*compute_args, check(odd));
+                assert_eq!(error.input, Some(odd));
+            }
+            other => panic!("expected the check to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rate_limited_computes_are_spaced_out() {
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        site.set_rate_limiter(Some(RateLimiter::new(20., 1.)));
+        let [f, x] = [AssetId(0), AssetId(1)];
+        site.insert_asset(f, AssetData::from(0));
+        site.insert_asset(x, AssetData::from(1));
+        let computes = (2..5).map(|output| compute(vec![x], AssetId(output), f));
+        site.todo_instructions.extend(computes.map(Instruction::ComputeAssetData));
+        let start = Instant::now();
+        let mut computed_at = vec![];
+        while computed_at.len() < 3 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            site.step().unwrap();
+            if computed_at.len() < site.metrics().computes as usize {
+                computed_at.push(Instant::now());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // One token every 50ms
+        for pair in computed_at.windows(2) {
+            assert!(pair[1] - pair[0] >= Duration::from_millis(40));
+        }
+    }
+
+    #[test]
+    fn short_site_id_prefixes_full() {
+        for site_id in sites(3).0 {
+            let (short, full) = (site_id.short(), site_id.full());
+            assert!(short.len() < full.len());
+            assert!(full.starts_with(&short));
+        }
+    }
+
+    #[test]
+    fn missing_compute_inputs_are_requested_up_front() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y, a, b, z] = [0, 1, 2, 3, 4, 5].map(AssetId);
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.insert_asset(f, AssetData::from(0));
+        amy_site.insert_asset(x, AssetData::from(1));
+        amy_site.todo_instructions.extend(vec![
+            // Completing this first cuts the scan short, before it reaches the acquisitions
+            Instruction::ComputeAssetData(compute(vec![x], y, f)),
+            acquire(a, bob),
+            acquire(b, bob),
+            Instruction::ComputeAssetData(compute(vec![a, b], z, f)),
+        ]);
+        let scanned = amy_site.scan_instructions().unwrap();
+        assert_eq!((scanned.completed, scanned.rescan), (1, true));
+        // bob is yet to answer either request, so both transfers are in flight at once
+        let requested: HashSet<AssetId> = sites
+            .get_mut(&bob)
+            .unwrap()
+            .drain_inbox()
+            .into_iter()
+            .filter_map(|signed_msg| match signed_msg.msg {
+                Msg::AssetDataRequest { asset_id, .. } => Some(asset_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(requested, maplit::hashset! { a, b });
+    }
+
+    #[test]
+    fn idle_site_exits_promptly_after_a_tiny_timeout() {
+        let idle_timeout = Duration::from_millis(10);
+        let config = SiteConfig { idle_timeout, ..Default::default() };
+        let (site_ids, mut sites) =
+            new_sites_with_config(vec![Box::new(NullLogger)], &config).unwrap();
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let start = Instant::now();
+        site.execute().unwrap();
+        assert!(start.elapsed() >= idle_timeout);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn urgent_request_is_served_first() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [low, high] = [AssetId(0), AssetId(1)];
+        let acquire_with = |asset_id, priority| Instruction::AcquireAssetFrom {
+            asset_id,
+            site_id: bob,
+            priority,
+            timeout: None,
+        };
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.todo_instructions.extend(vec![acquire_with(low, 0), acquire_with(high, 5)]);
+        amy_site.step().unwrap();
+        // bob queues both sends, as it is yet to have either asset
+        let bob_site = sites.get_mut(&bob).unwrap();
+        for signed_msg in bob_site.drain_inbox() {
+            bob_site.handle_msg(signed_msg);
+        }
+        bob_site.insert_asset(low, AssetData::from(0));
+        bob_site.insert_asset(high, AssetData::from(1));
+        bob_site.step().unwrap();
+        let delivered: Vec<AssetId> = sites
+            .get_mut(&amy)
+            .unwrap()
+            .drain_inbox()
+            .into_iter()
+            .filter_map(|signed_msg| match signed_msg.msg {
+                Msg::AssetData { asset_id, .. } => Some(asset_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivered, vec![high, low]);
+    }
+
+    /// The first 8 bytes of the SHA-256 digest, as a little-endian `u64`
+    #[derive(Default)]
+    struct Sha256Hasher(sha2::Sha256);
+    impl std::hash::Hasher for Sha256Hasher {
+        fn write(&mut self, bytes: &[u8]) {
+            sha2::Digest::update(&mut self.0, bytes);
+        }
+        fn finish(&self) -> u64 {
+            let digest = sha2::Digest::finalize(self.0.clone());
+            let mut first = [0; 8];
+            first.copy_from_slice(&digest[..8]);
+            u64::from_le_bytes(first)
+        }
+    }
+
+    #[test]
+    fn compute_hashes_with_the_configured_hasher() {
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        let mut store = HashMap::<AssetId, AssetData>::default();
+        store.insert(x, AssetData { bytes: b"ab".to_vec() });
+        store.insert(f, AssetData { bytes: b"c".to_vec() });
+        let outputs = HashCompute::<Sha256Hasher>::default()
+            .compute(&store, &compute(vec![x], y, f))
+            .unwrap();
+        // `printf abc | sha256sum` starts with ba7816bf8f01cfea
+        let expected = AssetData { bytes: vec![0xBA, 0x78, 0x16, 0xBF, 0x8F, 0x01, 0xCF, 0xEA] };
+        assert_eq!(outputs, maplit::hashmap! { y => expected });
+    }
+
+    #[test]
+    fn completion_fires_once_the_last_goal_lands() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z] = [AssetId(0), AssetId(1), AssetId(2)];
+        let watcher = CompletionWatcher::new(vec![(amy, x), (bob, y)]);
+        for site in sites.values_mut() {
+            site.set_completion_watcher(watcher.clone());
+        }
+        let waiter = {
+            let watcher = watcher.clone();
+            std::thread::spawn(move || watcher.wait_timeout(Duration::from_secs(5)))
+        };
+        sites.get_mut(&amy).unwrap().insert_asset(x, AssetData::from(0));
+        // Neither the goal of another site, nor an asset which is no goal, completes it
+        sites.get_mut(&amy).unwrap().insert_asset(y, AssetData::from(1));
+        sites.get_mut(&bob).unwrap().insert_asset(z, AssetData::from(2));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!watcher.is_complete());
+        assert!(!waiter.is_finished());
+        sites.get_mut(&bob).unwrap().insert_asset(y, AssetData::from(1));
+        assert!(watcher.is_complete());
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn verify_checks_the_content_hash() {
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let x = AssetId(0);
+        let asset_data = AssetData::from(7);
+        let expected_hash = asset_data.content_hash();
+        site.insert_asset(x, asset_data);
+        site.todo_instructions.push(Instruction::Verify { asset_id: x, expected_hash });
+        site.step().unwrap();
+        assert!(site.todo_instructions.is_empty());
+
+        let wrong_hash = expected_hash.wrapping_add(1);
+        site.todo_instructions.push(Instruction::Verify { asset_id: x, expected_hash: wrong_hash });
+        match site.step() {
+            Err(ExecError::VerifyFailed { asset_id, expected_hash: e, actual_hash }) => {
+                assert_eq!((asset_id, e, actual_hash), (x, wrong_hash, expected_hash))
+            }
+            other => panic!("expected verification to fail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sites_are_identified_by_the_given_keypairs() {
+        let generate = || (0..3).map(|_| Keypair::generate(&mut rand_core::OsRng)).collect();
+        let loggers = |n| (0..n).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let keypairs: Vec<Keypair> = generate();
+        let public_keys: Vec<PublicKey> = keypairs.iter().map(|keypair| keypair.public).collect();
+        let (site_ids, sites) = new_sites_with_keypairs(keypairs, loggers(3)).unwrap();
+        let site_keys: Vec<PublicKey> = site_ids.iter().map(|site_id| site_id.0).collect();
+        assert_eq!(site_keys, public_keys);
+        assert!(site_ids.iter().all(|site_id| sites.contains_key(site_id)));
+        assert!(matches!(
+            new_sites_with_keypairs(generate(), loggers(2)),
+            Err(NewSitesError::KeypairCountMismatch { keypairs: 3, loggers: 2 })
+        ));
+    }
+
+    #[test]
+    fn full_outbox_defers_sends_without_blocking_the_inbox() {
+        let config = SiteConfig { channel_capacity: Some(1), ..Default::default() };
+        let loggers = (0..3).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
+        let [x, y, z] = [AssetId(0), AssetId(1), AssetId(2)];
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.insert_asset(x, AssetData::from(0));
+        amy_site.insert_asset(y, AssetData::from(1));
+        amy_site.todo_instructions.extend(vec![
+            Instruction::SendAssetTo { asset_id: x, site_id: bob, priority: 0 },
+            Instruction::SendAssetTo { asset_id: y, site_id: bob, priority: 0 },
+        ]);
+        let cho_site = sites.get_mut(&cho).unwrap();
+        cho_site.insert_asset(z, AssetData::from(2));
+        cho_site.todo_instructions.push(Instruction::SendAssetTo {
+            asset_id: z,
+            site_id: amy,
+            priority: 0,
+        });
+
+        // bob's inbox fills up with x, so the send of y is deferred
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        assert_eq!(sites[&amy].todo_instructions.len(), 1);
+        // amy still handles its inbox while the send is pending
+        sites.get_mut(&cho).unwrap().step().unwrap();
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        assert!(sites[&amy].has_asset(&z));
+        assert_eq!(sites[&amy].todo_instructions.len(), 1);
+
+        // once bob catches up, the deferred send completes in order
+        let mut received = vec![];
+        let mut drain_bob = |sites: &mut HashMap<SiteId, Site>| {
+            let inbox = sites.get_mut(&bob).unwrap().drain_inbox();
+            received.extend(inbox.into_iter().filter_map(|signed_msg| match signed_msg.msg {
+                Msg::AssetData { asset_id, .. } => Some(asset_id),
+                _ => None,
+            }));
+        };
+        drain_bob(&mut sites);
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        assert!(sites[&amy].todo_instructions.is_empty());
+        drain_bob(&mut sites);
+        assert_eq!(received, vec![x, y]);
+    }
+
+    #[test]
+    fn instructions_display_concisely() {
+        let (site_ids, _sites) = sites(1);
+        let amy = site_ids[0];
+        let [x, y, z, f] = [0, 1, 2, 9].map(AssetId);
+        let short = amy.short();
+        let cases = vec![
+            (
+                Instruction::SendAssetTo { asset_id: z, site_id: amy, priority: 3 },
+                format!("SEND asset 2 -> site {}", short),
+            ),
+            (acquire(x, amy), format!("ACQUIRE asset 0 <- site {}", short)),
+            (
+                Instruction::ComputeAssetData(compute(vec![x, y], z, f)),
+                "COMPUTE 9(0,1)->2".to_owned(),
+            ),
+            (
+                Instruction::Verify { asset_id: x, expected_hash: 0xAB },
+                "VERIFY asset 0 has hash 00000000000000AB".to_owned(),
+            ),
+        ];
+        for (instruction, expected) in cases {
+            assert_eq!(instruction.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn site_joining_mid_run_acquires_an_asset() {
+        let (site_ids, mut sites) = sites(3);
+        let amy = site_ids[0];
+        let x = AssetId(0);
+        sites.get_mut(&amy).unwrap().insert_asset(x, AssetData::from(0xBEEF));
+        let coordinator = sites[&amy].coordinator();
+        let dan = crossbeam_utils::thread::scope(|s| {
+            for site in sites.values_mut() {
+                s.spawn(move |_| site.execute().unwrap());
+            }
+            // the others are executing by the time dan joins
+            let keypair = Keypair::generate(&mut rand_core::OsRng);
+            let (dan_id, mut dan) = coordinator.join(keypair, Box::new(NullLogger));
+            assert!(coordinator.site_ids().contains(&dan_id));
+            dan.todo_instructions.push(acquire(x, amy));
+            dan.execute().unwrap();
+            dan
+        })
+        .unwrap();
+        assert_eq!(dan.inner.asset_store.get(&x).unwrap().into_owned(), AssetData::from(0xBEEF));
+        assert!(dan.todo_instructions.is_empty());
+    }
+
+    #[test]
+    fn lost_asset_data_is_counted_as_repeats() {
+        // amy acquires x from bob, but loses all messages sent to it for `lossy_for`
+        fn repeats(lossy_for: Duration) -> (u64, u64) {
+            let (site_ids, mut sites) = sites(2);
+            let [amy, bob] = [site_ids[0], site_ids[1]];
+            let x = AssetId(0);
+            sites.get_mut(&bob).unwrap().insert_asset(x, AssetData::from(0));
+            sites.get_mut(&amy).unwrap().todo_instructions.push(acquire(x, bob));
+            let lossy_until = Instant::now() + lossy_for;
+            while !sites[&amy].todo_instructions.is_empty() {
+                sites.get_mut(&amy).unwrap().step().unwrap();
+                sites.get_mut(&bob).unwrap().step().unwrap();
+                if Instant::now() < lossy_until {
+                    sites.get_mut(&amy).unwrap().drain_inbox();
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let re_requests = sites[&amy].metrics().re_requests.get(&x).copied().unwrap_or(0);
+            (re_requests, sites[&bob].metrics().retransmissions)
+        }
+        assert_eq!(repeats(Duration::default()), (0, 0));
+        let (re_requests, retransmissions) = repeats(Duration::from_secs(1));
+        assert!(re_requests > 0);
+        assert!(retransmissions > 0);
+    }
+
+    #[test]
+    fn logger_factory_gives_each_site_its_own_logger() {
+        /// Records which site index wrote each line
+        #[derive(Debug)]
+        struct IndexedLogger {
+            index: usize,
+            line: Vec<u8>,
+            writers: Arc<Mutex<Vec<usize>>>,
+        }
+        impl Logger for IndexedLogger {
+            fn line_writer(&mut self) -> Option<&mut dyn Write> {
+                self.line.clear();
+                Some(&mut self.line)
+            }
+            fn line_done(&mut self) {
+                self.writers.lock().unwrap().push(self.index);
+            }
+        }
+        let writers = Arc::new(Mutex::new(vec![]));
+        let mut made = vec![];
+        let (site_ids, mut sites) = new_sites_with_logger_factory(3, |index| {
+            made.push(index);
+            Box::new(IndexedLogger { index, line: vec![], writers: writers.clone() })
+        });
+        assert_eq!(made, vec![0, 1, 2]);
+        for (index, site_id) in site_ids.iter().enumerate() {
+            sites.get_mut(site_id).unwrap().drain_inbox();
+            let written: Vec<usize> = writers.lock().unwrap().drain(..).collect();
+            assert_eq!(written, vec![index]);
+        }
+    }
+
+    #[test]
+    fn deterministic_data_depends_only_on_the_asset_id() {
+        let [x, y] = [AssetId(0), AssetId(1)];
+        assert_eq!(AssetData::deterministic(x), AssetData::deterministic(x));
+        assert_ne!(AssetData::deterministic(x), AssetData::deterministic(y));
+    }
+
+    #[test]
+    fn watchdog_logs_stuck_instructions_before_timing_out() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let config = SiteConfig {
+            watchdog_interval: Some(Duration::from_millis(100)),
+            quiescence_timeout: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let loggers = vec![
+            Box::new(LineLogger { line: vec![], lines: lines.clone() }) as Box<dyn Logger>,
+            Box::new(NullLogger),
+        ];
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        // bob never has asset 2, so amy never acquires it
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.todo_instructions.push(acquire(AssetId(2), bob));
+        amy_site.execute().unwrap();
+        let lines = lines.lock().unwrap();
+        let position = |prefix: &str| lines.iter().position(|line| line.starts_with(prefix));
+        let blocked = position(&format!("Blocked on ACQUIRE asset 2 <- site {}", bob.short()));
+        let timed_out = position("RECV timeout");
+        assert!(blocked.unwrap() < timed_out.unwrap(), "{:#?}", lines);
+    }
+
+    #[test]
+    fn pending_summary_counts_each_kind_of_instruction() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y, z, f] = [0, 1, 2, 3].map(AssetId);
+        let site = sites.get_mut(&amy).unwrap();
+        site.insert_asset(f, AssetData::from(3));
+        site.insert_asset(x, AssetData::from(0));
+        site.todo_instructions.extend(vec![
+            Instruction::SendAssetTo { asset_id: x, site_id: bob, priority: 0 },
+            Instruction::SendAssetTo { asset_id: f, site_id: bob, priority: 0 },
+            acquire(y, bob),
+            Instruction::ComputeAssetData(compute(vec![x, y], z, f)),
+            Instruction::Verify { asset_id: z, expected_hash: 0 },
+        ]);
+        assert_eq!(
+            site.pending_summary(),
+            "5 todo: 2 send, 1 acquire, 1 compute, 1 verify\n\
+             holding assets [0, 3]\n\
+             awaiting assets [1, 2]"
+        );
+    }
+
+    #[test]
+    fn oversized_msg_is_dropped_with_a_logged_reason() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let config = SiteConfig { max_msg_bytes: Some(1024), ..Default::default() };
+        let loggers = vec![
+            Box::new(LineLogger { line: vec![], lines: lines.clone() }) as Box<dyn Logger>,
+            Box::new(NullLogger),
+        ];
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let asset_data = AssetData { bytes: vec![7; 2048] };
+        let signed_msg =
+            Msg::AssetData { asset_id: x, asset_data }.sign(&sites[&bob].inner.keypair);
+        let wire_len = signed_msg.wire_len();
+        let amy_site = sites.get_mut(&amy).unwrap();
+        assert!(!amy_site.handle_msg(signed_msg));
+        assert!(!amy_site.has_asset(&x));
+        let expected = format!(
+            "Dropped msg from {} of {} bytes, exceeding the maximum of 1024",
+            bob.short(),
+            wire_len
+        );
+        assert!(lines.lock().unwrap().contains(&expected));
+    }
+
+    #[test]
+    fn compute_error_is_logged_and_returned() {
+        #[derive(Debug)]
+        struct Utf8Only;
+        impl ComputeFn for Utf8Only {
+            fn compute(
+                &self,
+                store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+            ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+                let input = compute_args.inputs[0];
+                let bytes = store.get(&input).unwrap().into_owned().bytes;
+                let text = String::from_utf8(bytes).map_err(|e| ComputeError {
+                    message: format!("input is not valid UTF-8: {}", e),
+                    input: Some(input),
+                })?;
+                let output = AssetData { bytes: text.to_uppercase().into_bytes() };
+                Ok(maplit::hashmap! { compute_args.outputs[0] => output })
+            }
+        }
+        let lines = Arc::new(Mutex::new(vec![]));
+        let logger = Box::new(LineLogger { line: vec![], lines: lines.clone() });
+        let (site_ids, mut sites) = new_sites(vec![logger]);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        site.set_compute_fn(Some(Arc::new(Utf8Only)));
+        site.insert_asset(f, AssetData::from(0));
+        site.insert_asset(x, AssetData { bytes: vec![0xFF] });
+        let compute_args = compute(vec![x], y, f);
+        site.todo_instructions.push(Instruction::ComputeAssetData(compute_args.clone()));
+        let error = match site.step() {
+            Err(ExecError::ComputeFailed { error, .. }) => error,
+            other => panic!("expected the compute to fail, got {:?}", other),
+        };
+        assert!(error.message.starts_with("input is not valid UTF-8"), "{}", error.message);
+        assert_eq!(error.input, Some(x));
+        let expected = format!("Computation {} failed: {}", compute_args, error);
+        assert!(lines.lock().unwrap().contains(&expected));
+    }
+
+    #[test]
+    fn subscriber_receives_recomputed_asset() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        sites.get_mut(&amy).unwrap().insert_asset(f, AssetData::from(0));
+        assert!(sites.get_mut(&bob).unwrap().subscribe(y, &amy));
+        // amy handles the subscription before it has any y
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        // amy consumes each new version of x into a new version of y
+        let consume = ComputeArgs { consume_inputs: true, ..compute(vec![x], y, f) };
+        let mut versions = vec![];
+        for x_version in [1, 2].iter() {
+            let amy_site = sites.get_mut(&amy).unwrap();
+            amy_site.insert_asset(x, AssetData::from(*x_version));
+            amy_site.todo_instructions.push(Instruction::ComputeAssetData(consume.clone()));
+            amy_site.step().unwrap();
+            let y_data = amy_site.inner.asset_store.get(&y).unwrap().into_owned();
+            let bob_site = sites.get_mut(&bob).unwrap();
+            bob_site.step().unwrap();
+            assert_eq!(bob_site.inner.asset_store.get(&y).unwrap().into_owned(), y_data);
+            versions.push(y_data);
+        }
+        assert_ne!(versions[0], versions[1]);
+    }
+
+    #[test]
+    fn finished_site_serves_requests_until_its_grace_is_over() {
+        let config = SiteConfig {
+            goal_grace: Some(Duration::from_millis(500)),
+            idle_timeout: Duration::from_secs(10),
+            quiescence_timeout: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.insert_asset(x, AssetData::from(0));
+        amy_site.set_goals(Some(x));
+        let bob_site = sites.get_mut(&bob).unwrap();
+        // bob keeps working, as nobody has y
+        bob_site.todo_instructions.push(acquire(y, amy));
+        crossbeam_utils::thread::scope(|s| {
+            let amy_thread = s.spawn(move |_| {
+                let start = Instant::now();
+                amy_site.execute().unwrap();
+                start.elapsed()
+            });
+            // bob requests x within amy's grace window
+            std::thread::sleep(Duration::from_millis(100));
+            bob_site.todo_instructions.push(acquire(x, amy));
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while !bob_site.has_asset(&x) && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert!(bob_site.has_asset(&x));
+            assert!(!bob_site.todo_instructions.is_empty());
+            let amy_ran_for = amy_thread.join().unwrap();
+            assert!(amy_ran_for < Duration::from_secs(2), "{:?}", amy_ran_for);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn tampered_instructions_are_rejected() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let planner = Keypair::generate(&mut rand_core::OsRng);
+        let site = sites.get_mut(&amy).unwrap();
+        site.set_planner_key(Some(planner.public));
+        let instructions = vec![acquire(AssetId(0), bob)];
+        let signed = SignedInstructions::sign(&planner, amy, instructions.clone());
+
+        let mut tampered = signed.clone();
+        tampered.instructions.push(Instruction::SendAssetTo {
+            asset_id: AssetId(1),
+            site_id: bob,
+            priority: 0,
+        });
+        assert!(matches!(
+            site.accept_instructions(tampered),
+            Err(InstructionsRejected::BadSignature(_))
+        ));
+        let unsigned = SignedInstructions { signature: None, ..signed.clone() };
+        assert!(matches!(site.accept_instructions(unsigned), Err(InstructionsRejected::Unsigned)));
+        let redirected = SignedInstructions { site_id: bob, ..signed.clone() };
+        assert!(matches!(
+            site.accept_instructions(redirected),
+            Err(InstructionsRejected::WrongSite)
+        ));
+        assert!(site.todo_instructions.is_empty());
+
+        site.accept_instructions(signed).unwrap();
+        assert_eq!(site.todo_instructions, instructions);
+    }
+
+    #[test]
+    fn only_assets_unused_past_the_ttl_are_evicted() {
+        let config =
+            SiteConfig { asset_ttl: Some(Duration::from_millis(200)), ..Default::default() };
+        let loggers = (0..3).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob, cho] = [site_ids[0], site_ids[1], site_ids[2]];
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let step = |sites: &mut HashMap<SiteId, Site>, site_ids: &[SiteId]| {
+            for site_id in site_ids {
+                sites.get_mut(site_id).unwrap().step().unwrap();
+            }
+        };
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(0));
+        bob_site.insert_asset(y, AssetData::from(1));
+        sites
+            .get_mut(&amy)
+            .unwrap()
+            .todo_instructions
+            .extend(vec![acquire(x, bob), acquire(y, bob)]);
+        step(&mut sites, &[amy, bob, amy]);
+        assert!(sites[&amy].has_asset(&x) && sites[&amy].has_asset(&y));
+
+        // amy uses y, serving it to cho, but leaves x unused
+        std::thread::sleep(Duration::from_millis(120));
+        sites.get_mut(&cho).unwrap().todo_instructions.push(acquire(y, amy));
+        step(&mut sites, &[cho, amy, cho]);
+        assert!(sites[&cho].has_asset(&y));
+        std::thread::sleep(Duration::from_millis(120));
+        step(&mut sites, &[amy]);
+        assert!(!sites[&amy].has_asset(&x));
+        assert!(sites[&amy].has_asset(&y));
+    }
+
+    #[test]
+    fn streamed_output_is_used_before_the_next_exists() {
+        /// Streams its first output, and its second only once the gate opens
+        #[derive(Debug)]
+        struct Gated(crossbeam_channel::Receiver<()>);
+        impl ComputeFn for Gated {
+            fn compute(
+                &self,
+                _store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+            ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+                let outputs = compute_args.outputs.iter();
+                Ok(outputs
+                    .map(|&asset_id| (asset_id, AssetData::from(asset_id.0 as u64)))
+                    .collect())
+            }
+            fn compute_streaming(
+                &self,
+                _store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+                outputs: &Sender<(AssetId, AssetData)>,
+            ) -> Result<(), ComputeError> {
+                let [first, second] = [compute_args.outputs[0], compute_args.outputs[1]];
+                outputs.send((first, AssetData::from(first.0 as u64))).unwrap();
+                self.0.recv().unwrap();
+                outputs.send((second, AssetData::from(second.0 as u64))).unwrap();
+                Ok(())
+            }
+        }
+        let config = SiteConfig { stream_compute_outputs: true, ..Default::default() };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y1, y2, g, z] = [0, 1, 2, 3, 4, 5].map(AssetId);
+        let (open_gate, gate) = crossbeam_channel::bounded(1);
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.register_compute_fn(f, Arc::new(Gated(gate)));
+        amy_site.insert_asset(f, AssetData::from(0));
+        amy_site.insert_asset(x, AssetData::from(1));
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(g, AssetData::from(4));
+        assert!(bob_site.subscribe(y1, &amy));
+        // amy handles the subscription before computing
+        amy_site.step().unwrap();
+        let produce = ComputeArgs { outputs: vec![y1, y2], ..compute(vec![x], y1, f) };
+        amy_site.todo_instructions.push(Instruction::ComputeAssetData(produce));
+        crossbeam_utils::thread::scope(|s| {
+            let amy_thread = s.spawn(move |_| {
+                amy_site.execute().unwrap();
+                amy_site
+            });
+            // bob consumes y1 while y2 is held up behind the gate
+            bob_site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![y1], z, g)));
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while !bob_site.has_asset(&z) && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert!(bob_site.has_asset(&z));
+            open_gate.send(()).unwrap();
+            let amy_site = amy_thread.join().unwrap();
+            assert!(amy_site.has_asset(&y2));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn jittered_request_periods_vary_reproducibly() {
+        let config = SiteConfig { request_jitter: 0.2, ..Default::default() };
+        let periods = || {
+            let loggers = vec![Box::new(NullLogger) as Box<dyn Logger>];
+            let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            site.set_seed(7);
+            (0..20).map(|_| site.inner.request_period()).collect::<Vec<Duration>>()
+        };
+        let burst = periods();
+        assert!(burst.iter().any(|&period| period != burst[0]));
+        let period = SiteInner::REQUEST_PERIOD;
+        let (min, max) = (period.mul_f64(0.8), period.mul_f64(1.2));
+        assert!(burst.iter().all(|&period| min <= period && period <= max));
+        // identically seeded sites vary identically
+        assert_eq!(periods(), burst);
+    }
+
+    #[test]
+    fn seeded_runs_log_identically() {
+        let run = || {
+            // deterministic keypairs, from which the problem is derived
+            let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+            let keypairs = (0..4).map(|_| Keypair::generate(&mut rng)).collect();
+            let logs: Vec<_> = (0..4).map(|_| Arc::new(Mutex::new(vec![]))).collect();
+            let loggers = logs
+                .iter()
+                .map(|lines| Box::new(LineLogger { line: vec![], lines: lines.clone() }) as _)
+                .collect();
+            let (site_ids, sites) = new_sites_with_keypairs(keypairs, loggers).unwrap();
+            let mut rng = SmallRng::seed_from_u64(0);
+            let problem = generate::random_problem(&mut rng, &site_ids, 6, 6);
+            let initial_data = problem
+                .site_has_asset
+                .iter()
+                .map(|&(site_id, asset_id)| {
+                    ((site_id, asset_id), AssetData::deterministic(asset_id))
+                })
+                .collect();
+            let mut sites = crate::scenario::prepare(&problem, sites, initial_data).unwrap().sites;
+            for site in sites.values_mut() {
+                site.set_scan_shuffle(true);
+                site.set_seed(1);
+            }
+            for _ in 0..20 {
+                for site_id in site_ids.iter() {
+                    sites.get_mut(site_id).unwrap().step().unwrap();
+                }
+            }
+            assert!(sites.values().all(|site| site.todo_instructions.is_empty()));
+            drop(sites);
+            logs.iter().map(|lines| lines.lock().unwrap().clone()).collect::<Vec<_>>()
+        };
+        let logs = run();
+        assert!(logs.iter().all(|lines| !lines.is_empty()));
+        assert_eq!(run(), logs);
+    }
+
+    #[test]
+    fn progress_is_polled_while_executing() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.todo_instructions.push(acquire(x, bob));
+        let progress = amy_site.progress();
+        assert_eq!((progress.asset_count(), progress.pending_count()), (0, 1));
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(0));
+        crossbeam_utils::thread::scope(|s| {
+            s.spawn(move |_| amy_site.execute().unwrap());
+            // amy awaits x until bob serves it
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!((progress.asset_count(), progress.pending_count()), (0, 1));
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while progress.pending_count() > 0 && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!((progress.asset_count(), progress.pending_count()), (1, 0));
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn optional_input_changes_the_output_deterministically() {
+        let [f, x, o, y] = [0, 1, 2, 3].map(AssetId);
+        let output = |with_optional: bool| {
+            let (site_ids, mut sites) = sites(1);
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            site.insert_asset(f, AssetData::from(0));
+            site.insert_asset(x, AssetData::from(1));
+            if with_optional {
+                site.insert_asset(o, AssetData::from(2));
+            }
+            let compute_args = ComputeArgs { optional_inputs: vec![o], ..compute(vec![x], y, f) };
+            site.todo_instructions.push(Instruction::ComputeAssetData(compute_args));
+            site.step().unwrap();
+            assert!(site.todo_instructions.is_empty());
+            site.inner.asset_store.get(&y).unwrap().into_owned()
+        };
+        assert_eq!(output(false), output(false));
+        assert_eq!(output(true), output(true));
+        assert_ne!(output(false), output(true));
+    }
+
+    #[test]
+    fn input_of_a_pending_compute_outlives_the_ttl() {
+        let config =
+            SiteConfig { asset_ttl: Some(Duration::from_millis(100)), ..Default::default() };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, w, y] = [0, 1, 2, 3].map(AssetId);
+        sites.get_mut(&bob).unwrap().insert_asset(x, AssetData::from(1));
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.insert_asset(f, AssetData::from(0));
+        // the compute waits on w long after x was acquired
+        amy_site.todo_instructions.extend(vec![
+            acquire(x, bob),
+            Instruction::ComputeAssetData(compute(vec![x, w], y, f)),
+        ]);
+        amy_site.step().unwrap();
+        sites.get_mut(&bob).unwrap().step().unwrap();
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.step().unwrap();
+        assert!(amy_site.has_asset(&x));
+        std::thread::sleep(Duration::from_millis(200));
+        amy_site.step().unwrap();
+        assert!(amy_site.has_asset(&x));
+        amy_site.insert_asset(w, AssetData::from(2));
+        amy_site.step().unwrap();
+        assert!(amy_site.has_asset(&y));
+        assert!(amy_site.todo_instructions.is_empty());
+    }
+
+    #[test]
+    fn unknown_sender_is_dropped_only_under_the_strict_policy() {
+        let x = AssetId(0);
+        let stranger = Keypair::generate(&mut rand_core::OsRng);
+        for &sender_policy in [SenderPolicy::AnySigner, SenderPolicy::KnownPeers].iter() {
+            let lines = Arc::new(Mutex::new(vec![]));
+            let config = SiteConfig { sender_policy, ..Default::default() };
+            let loggers = vec![Box::new(LineLogger { line: vec![], lines: lines.clone() }) as _];
+            let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            let asset_data = AssetData::from(0);
+            site.handle_msg(Msg::AssetData { asset_id: x, asset_data }.sign(&stranger));
+            let dropped = format!("Dropped msg from unknown {}", SiteId::from(&stranger).full());
+            let strict = sender_policy == SenderPolicy::KnownPeers;
+            assert_eq!(lines.lock().unwrap().contains(&dropped), strict);
+            assert_eq!(site.has_asset(&x), !strict);
+        }
+    }
+
+    #[test]
+    fn compute_label_appears_in_the_log() {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let logger = Box::new(LineLogger { line: vec![], lines: lines.clone() });
+        let (site_ids, mut sites) = new_sites(vec![logger]);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        site.insert_asset(f, AssetData::from(0));
+        site.insert_asset(x, AssetData::from(1));
+        let labeled = ComputeArgs { label: Some("normalize".to_owned()), ..compute(vec![x], y, f) };
+        site.todo_instructions.push(Instruction::ComputeAssetData(labeled));
+        site.step().unwrap();
+        let lines = lines.lock().unwrap();
+        assert!(
+            lines.contains(&"Did a computation: normalize: 0(1)->2".to_owned()),
+            "{:#?}",
+            lines
+        );
+    }
+
+    #[test]
+    fn site_id_conversions_agree_with_the_reference_path() {
+        let keypair = Keypair::generate(&mut rand_core::OsRng);
+        let site_id = SiteId::from(keypair.public);
+        assert_eq!(&site_id, SiteId::from_public_key_ref(&keypair.public));
+        assert_eq!(SiteId::from(&keypair), site_id);
+        assert_eq!(site_id.as_bytes(), keypair.public.as_bytes());
+    }
+
+    #[test]
+    fn full_inbox_drops_heartbeats_but_keeps_asset_data() {
+        let config = SiteConfig {
+            channel_capacity: Some(4),
+            evict_when_inbox_full: true,
+            ..Default::default()
+        };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let outbox = sites[&bob].inner.outboxes.read().unwrap()[&amy].clone();
+        let flood = |sites: &mut HashMap<SiteId, Site>, asset_ids: std::ops::Range<u32>| {
+            for asset_id in asset_ids.map(AssetId) {
+                let bob_site = &sites[&bob];
+                let asset_data = AssetData::from(0);
+                for msg in [Msg::Heartbeat, Msg::AssetData { asset_id, asset_data }] {
+                    outbox.try_send(msg.sign(&bob_site.inner.keypair)).unwrap();
+                }
+            }
+            sites.get_mut(&amy).unwrap().inner.buffer_inbox();
+        };
+        flood(&mut sites, 0..2);
+        flood(&mut sites, 2..4);
+        let amy_site = sites.get_mut(&amy).unwrap();
+        assert!(amy_site.metrics().messages_evicted > 0);
+        let (mut heartbeats, mut delivered) = (0, vec![]);
+        for signed_msg in amy_site.drain_inbox() {
+            match signed_msg.msg {
+                Msg::Heartbeat => heartbeats += 1,
+                Msg::AssetData { asset_id, .. } => delivered.push(asset_id.0),
+                msg => panic!("unexpected {:?}", msg),
+            }
+        }
+        assert!(heartbeats < 4);
+        assert_eq!(delivered, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn verify_into_agrees_with_verify() {
+        let keypair = Keypair::generate(&mut rand_core::OsRng);
+        let msg = |i: u32| match i {
+            0 => Msg::AssetData {
+                asset_id: AssetId(0),
+                asset_data: AssetData { bytes: vec![3; 100] },
+            },
+            1 => Msg::AssetDataRequest { asset_id: AssetId(1), priority: 2 },
+            _ => Msg::Heartbeat,
+        };
+        let mut buffer = vec![];
+        for i in 0..3 {
+            // tampered messages carry the signature of another
+            let mut tampered = msg(i).sign(&keypair);
+            tampered.msg = msg((i + 1) % 3);
+            let cases = vec![
+                (msg(i).sign(&keypair), true),
+                (tampered, false),
+                (msg(i).unsigned(keypair.public), false),
+            ];
+            for (signed_msg, valid) in cases {
+                assert_eq!(signed_msg.verify().is_ok(), valid);
+                assert_eq!(signed_msg.verify_into(&mut buffer).is_ok(), valid);
+            }
+        }
+    }
+
+    #[test]
+    fn wire_len_scales_with_the_payload() {
+        let keypair = Keypair::generate(&mut rand_core::OsRng);
+        let wire_len = |len: usize| {
+            let asset_data = AssetData { bytes: vec![0; len] };
+            Msg::AssetData { asset_id: AssetId(0), asset_data }.sign(&keypair).wire_len()
+        };
+        let empty = wire_len(0);
+        assert_eq!(wire_len(100), empty + 100);
+        assert_eq!(wire_len(100_000), empty + 100_000);
+        // measured without serializing, yet as long as the signed bytes plus their signature
+        let asset_data = AssetData { bytes: vec![0; 100] };
+        let signing_bytes = Msg::AssetData { asset_id: AssetId(0), asset_data }.to_signing_bytes();
+        assert_eq!(wire_len(100), signing_bytes.len() + SignedMsg::OVERHEAD_LEN);
+        // and counted as sent
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let site = sites.get_mut(&amy).unwrap();
+        site.insert_asset(AssetId(0), AssetData { bytes: vec![0; 100] });
+        let send = Instruction::SendAssetTo { asset_id: AssetId(0), site_id: bob, priority: 0 };
+        site.todo_instructions.push(send);
+        site.step().unwrap();
+        assert_eq!(
+            site.metrics().bytes_sent,
+            (signing_bytes.len() + SignedMsg::OVERHEAD_LEN) as u64
+        );
+    }
+
+    #[test]
+    fn idle_site_serves_a_late_request_before_exiting() {
+        let config = SiteConfig {
+            await_all_goals: true,
+            idle_timeout: Duration::from_millis(50),
+            quiescence_timeout: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let completion_watcher = CompletionWatcher::new(Some((bob, x)));
+        for site in sites.values_mut() {
+            site.set_completion_watcher(completion_watcher.clone());
+        }
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.insert_asset(x, AssetData::from(0));
+        let bob_site = sites.get_mut(&bob).unwrap();
+        crossbeam_utils::thread::scope(|s| {
+            let amy_thread = s.spawn(move |_| {
+                let start = Instant::now();
+                amy_site.execute().unwrap();
+                start.elapsed()
+            });
+            // bob only requests x once amy has been idle for longer than its idle timeout
+            std::thread::sleep(Duration::from_millis(200));
+            bob_site.todo_instructions.push(acquire(x, amy));
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while !bob_site.has_asset(&x) && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert!(bob_site.has_asset(&x));
+            // with every goal reached, amy exits soon after
+            let amy_ran_for = amy_thread.join().unwrap();
+            assert!(amy_ran_for < Duration::from_secs(2), "{:?}", amy_ran_for);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn computes_dispatch_on_their_compute_asset() {
+        /// Outputs its constant
+        #[derive(Debug)]
+        struct Constant(u64);
+        impl ComputeFn for Constant {
+            fn compute(
+                &self,
+                _store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+            ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+                let outputs = compute_args.outputs.iter();
+                Ok(outputs.map(|&asset_id| (asset_id, AssetData::from(self.0))).collect())
+            }
+        }
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let [f, g, h, x, y, z, w] = [0, 1, 2, 3, 4, 5, 6].map(AssetId);
+        site.set_compute_fn(None);
+        site.register_compute_fn(f, Arc::new(Constant(1)));
+        site.register_compute_fn(g, Arc::new(Constant(2)));
+        for (asset_id, asset_data) in [(f, 0), (g, 0), (h, 0), (x, 0)] {
+            site.insert_asset(asset_id, AssetData::from(asset_data));
+        }
+        site.todo_instructions.extend(vec![
+            Instruction::ComputeAssetData(compute(vec![x], y, f)),
+            Instruction::ComputeAssetData(compute(vec![x], z, g)),
+        ]);
+        site.step().unwrap();
+        let get =
+            |site: &Site, asset_id| site.inner.asset_store.get(&asset_id).unwrap().into_owned();
+        assert_eq!(get(site, y), AssetData::from(1));
+        assert_eq!(get(site, z), AssetData::from(2));
+        // nothing is registered for h, and there is no default
+        site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![x], w, h)));
+        match site.step() {
+            Err(ExecError::ComputeFailed { error, .. }) => {
+                assert_eq!(error.input, Some(h));
+                assert!(error.message.contains("no compute function is registered"));
+            }
+            other => panic!("expected no compute function for h, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sorted_inputs_hash_independently_of_their_order() {
+        let [f, x, y, z] = [AssetId(0), AssetId(1), AssetId(2), AssetId(3)];
+        let mut store = HashMap::<AssetId, AssetData>::default();
+        store.insert(f, AssetData { bytes: b"f".to_vec() });
+        store.insert(x, AssetData { bytes: b"x".to_vec() });
+        store.insert(y, AssetData { bytes: b"y".to_vec() });
+        let [forward, backward] = [compute(vec![x, y], z, f), compute(vec![y, x], z, f)];
+        let listed = HashCompute::<fnv::FnvHasher>::default();
+        assert_ne!(
+            listed.compute(&store, &forward).unwrap(),
+            listed.compute(&store, &backward).unwrap()
+        );
+        let sorted = HashCompute::<fnv::FnvHasher>::with_sorted_inputs();
+        assert_eq!(
+            sorted.compute(&store, &forward).unwrap(),
+            sorted.compute(&store, &backward).unwrap()
+        );
+    }
+
+    #[test]
+    fn paused_site_completes_only_once_resumed() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.todo_instructions.push(acquire(x, bob));
+        let progress = amy_site.progress();
+        let pause = amy_site.pause_handle();
+        pause.pause();
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(0));
+        crossbeam_utils::thread::scope(|s| {
+            let amy_thread = s.spawn(move |_| amy_site.execute());
+            // bob serves x, but the paused amy leaves it waiting in its inbox
+            for _ in 0..10 {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert!(pause.is_paused());
+            assert_eq!((progress.asset_count(), progress.pending_count()), (0, 1));
+            pause.resume();
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while progress.pending_count() > 0 && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            assert_eq!((progress.asset_count(), progress.pending_count()), (1, 0));
+            amy_thread.join().unwrap().unwrap();
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn short_acquire_timeout_fails_while_a_long_one_keeps_trying() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let amy_site = sites.get_mut(&amy).unwrap();
+        // bob never serves either
+        let with_timeout = |asset_id, timeout| Instruction::AcquireAssetFrom {
+            asset_id,
+            site_id: bob,
+            priority: 0,
+            timeout: Some(timeout),
+        };
+        amy_site.todo_instructions.extend(vec![
+            with_timeout(y, Duration::from_secs(60)),
+            with_timeout(x, Duration::from_millis(20)),
+        ]);
+        amy_site.step().unwrap();
+        std::thread::sleep(Duration::from_millis(40));
+        match amy_site.step() {
+            Err(ExecError::AcquireTimedOut { asset_id, timeout }) => {
+                assert_eq!((asset_id, timeout), (x, Duration::from_millis(20)));
+            }
+            other => panic!("expected x to time out, got {:?}", other),
+        }
+        amy_site.todo_instructions.retain(|ins| ins != &with_timeout(x, Duration::from_millis(20)));
+        amy_site.step().unwrap();
+        assert_eq!(amy_site.todo_instructions, vec![with_timeout(y, Duration::from_secs(60))]);
+    }
+
+    #[test]
+    fn reduce_fires_over_inputs_discovered_in_its_range() {
+        let [f, sum, outside] = [0, 1, 30].map(AssetId);
+        let parts = [10, 11, 12].map(AssetId);
+        let reduce = ComputeArgs {
+            min_optional_inputs: 3,
+            input_range: Some(AssetRange { start: AssetId(10), end: AssetId(20) }),
+            ..compute(vec![], sum, f)
+        };
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        site.insert_asset(f, AssetData::from(7));
+        site.insert_asset(outside, AssetData::from(30));
+        site.todo_instructions.push(Instruction::ComputeAssetData(reduce.clone()));
+        for &part in &parts[..2] {
+            site.insert_asset(part, AssetData::from(part.0 as u64));
+        }
+        // two of the three required inputs are here so far
+        site.step().unwrap();
+        assert!(!site.inner.asset_store.contains_key(&sum));
+        site.insert_asset(parts[2], AssetData::from(parts[2].0 as u64));
+        site.step().unwrap();
+        assert!(site.todo_instructions.is_empty());
+        // the output is that of listing the discovered inputs, but not `outside`, as optional
+        let concrete = ComputeArgs { optional_inputs: parts.to_vec(), input_range: None, ..reduce };
+        let expected =
+            HashCompute::<fnv::FnvHasher>::default().compute(&*site.inner.asset_store, &concrete);
+        assert_eq!(site.inner.asset_store.get(&sum).unwrap().into_owned(), expected.unwrap()[&sum]);
+    }
+
+    #[test]
+    fn heavier_ready_compute_runs_first() {
+        /// Records the compute assets of the computes run, in order
+        #[derive(Debug, Default)]
+        struct Recorder(Mutex<Vec<AssetId>>);
+        impl ComputeFn for Recorder {
+            fn compute(
+                &self,
+                _store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+            ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+                self.0.lock().unwrap().push(compute_args.compute_asset);
+                let outputs = compute_args.outputs.iter();
+                Ok(outputs.map(|&asset_id| (asset_id, AssetData::from(0))).collect())
+            }
+        }
+        let [light, heavy, x, y, z] = [0, 1, 2, 3, 4].map(AssetId);
+        let run_order = |heaviest_computes_first: bool| {
+            let config = SiteConfig { heaviest_computes_first, ..Default::default() };
+            let (site_ids, mut sites) =
+                new_sites_with_config(vec![Box::new(NullLogger)], &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            let recorder = Arc::new(Recorder::default());
+            site.set_compute_fn(Some(recorder.clone()));
+            for asset_id in [light, heavy, x] {
+                site.insert_asset(asset_id, AssetData::from(0));
+            }
+            site.todo_instructions.extend(vec![
+                Instruction::ComputeAssetData(ComputeArgs {
+                    weight: 1,
+                    ..compute(vec![x], y, light)
+                }),
+                Instruction::ComputeAssetData(ComputeArgs {
+                    weight: 5,
+                    ..compute(vec![x], z, heavy)
+                }),
+            ]);
+            site.step().unwrap();
+            assert!(site.todo_instructions.is_empty());
+            let order = recorder.0.lock().unwrap().clone();
+            order
+        };
+        // FIFO by default
+        assert_eq!(run_order(false), vec![light, heavy]);
+        assert_eq!(run_order(true), vec![heavy, light]);
+    }
+
+    #[test]
+    fn verifier_thread_forwards_only_verified_msgs_in_order() {
+        let keypair = Keypair::generate(&mut rand_core::OsRng);
+        let request = |i| Msg::AssetDataRequest { asset_id: AssetId(i), priority: 0 };
+        let tampered = |i| {
+            let mut signed_msg = request(i).sign(&keypair);
+            signed_msg.msg = request(i + 100);
+            signed_msg
+        };
+        // some were buffered before the thread started, and the rest arrive after
+        let unverified = vec![request(0).sign(&keypair), tampered(1), request(2).sign(&keypair)];
+        let (inbox_sender, inbox) = crossbeam_channel::unbounded();
+        inbox_sender.send(tampered(3)).unwrap();
+        inbox_sender.send(request(4).sign(&keypair)).unwrap();
+        let (verified_sender, verified) = crossbeam_channel::unbounded();
+        let (stop_sender, stop) = crossbeam_channel::bounded::<()>(0);
+        let verifier = std::thread::spawn(move || {
+            forward_verified(unverified.into(), inbox, verified_sender, stop)
+        });
+        let forwarded: Vec<AssetId> = (0..3)
+            .map(|_| match verified.recv_timeout(Duration::from_secs(2)).unwrap().msg {
+                Msg::AssetDataRequest { asset_id, .. } => asset_id,
+                msg => panic!("unexpected {:?}", msg),
+            })
+            .collect();
+        assert_eq!(forwarded, vec![AssetId(0), AssetId(2), AssetId(4)]);
+        drop(stop_sender);
+        let (_inbox, unforwarded, failures) = verifier.join().unwrap();
+        assert!(unforwarded.is_empty());
+        assert_eq!(failures, 2);
+        assert!(verified.try_recv().is_err());
+    }
+
+    #[test]
+    fn site_verifying_on_a_thread_drops_tampered_msgs() {
+        let config = SiteConfig { verify_on_thread: true, ..Default::default() };
+        let loggers = vec![Box::new(NullLogger) as _, Box::new(NullLogger) as _];
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let mut amy_site = sites.remove(&amy).unwrap();
+        amy_site.todo_instructions.push(acquire(x, bob));
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(1));
+        // delivers bogus x under the signature of another message
+        let mut tampered = Msg::Heartbeat.sign(&Keypair::generate(&mut rand_core::OsRng));
+        tampered.msg = Msg::AssetData { asset_id: x, asset_data: AssetData::from(2) };
+        let amy_inbox = bob_site.inner.outboxes.read().unwrap()[&amy].clone();
+        amy_inbox.send(tampered).unwrap();
+        let progress = amy_site.progress();
+        let amy_site = crossbeam_utils::thread::scope(|s| {
+            let amy_thread = s.spawn(move |_| {
+                amy_site.execute().unwrap();
+                amy_site
+            });
+            let deadline = Instant::now() + Duration::from_secs(2);
+            while progress.pending_count() > 0 && Instant::now() < deadline {
+                bob_site.step().unwrap();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            amy_thread.join().unwrap()
+        })
+        .unwrap();
+        assert_eq!(amy_site.metrics().verification_failures, 1);
+        let held = amy_site.inner.asset_store.get(&x).unwrap().into_owned();
+        assert_eq!(held, AssetData::from(1));
+    }
+
+    #[test]
+    fn duplicate_instructions_are_recognized_and_added_once() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        let instructions =
+            vec![acquire(x, bob), Instruction::ComputeAssetData(compute(vec![x], y, f))];
+        let duplicates: HashSet<Instruction> =
+            instructions.iter().chain(instructions.iter()).cloned().collect();
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(acquire(x, bob), acquire(x, bob));
+        assert_ne!(acquire(x, bob), acquire(y, bob));
+
+        let planner = Keypair::generate(&mut rand_core::OsRng);
+        let site = sites.get_mut(&amy).unwrap();
+        let signed = SignedInstructions::sign(&planner, amy, instructions.clone());
+        site.accept_instructions(signed.clone()).unwrap();
+        // e.g., re-delivered by the planner
+        site.accept_instructions(signed).unwrap();
+        assert_eq!(site.todo_instructions, instructions);
+    }
+
+    #[test]
+    fn differing_replies_are_kept_per_the_reply_policy() {
+        let x = AssetId(0);
+        let source = Keypair::generate(&mut rand_core::OsRng);
+        let reply =
+            |i| Msg::AssetData { asset_id: x, asset_data: AssetData::from(i) }.sign(&source);
+        let policies = [
+            (ReplyPolicy::LatestWins, 2, 0),
+            (ReplyPolicy::FirstWins, 1, 0),
+            (ReplyPolicy::FlagConflicts, 1, 1),
+        ];
+        for &(reply_policy, kept, conflicts) in policies.iter() {
+            let lines = Arc::new(Mutex::new(vec![]));
+            let config = SiteConfig { reply_policy, ..Default::default() };
+            let loggers = vec![Box::new(LineLogger { line: vec![], lines: lines.clone() }) as _];
+            let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            site.handle_msg(reply(1));
+            site.handle_msg(reply(2));
+            let held = site.inner.asset_store.get(&x).unwrap().into_owned();
+            assert_eq!(held, AssetData::from(kept), "{:?}", reply_policy);
+            assert_eq!(site.metrics().conflicting_replies, conflicts);
+            let logged = lines.lock().unwrap().iter().any(|line| line.starts_with("Conflict!"));
+            assert_eq!(logged, conflicts > 0);
+        }
+    }
+
+    #[cfg(feature = "test-hooks")]
+    #[test]
+    fn injected_input_lets_a_pending_compute_run() {
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        site.insert_asset(f, AssetData::from(0));
+        site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![x], y, f)));
+        site.step().unwrap();
+        assert_eq!(site.todo_instructions.len(), 1);
+        site.inject_asset(x, AssetData::from(1));
+        site.step().unwrap();
+        assert!(site.todo_instructions.is_empty());
+        assert!(site.has_asset(&y));
+    }
+
+    #[test]
+    fn initial_instructions_are_logged_one_per_line() {
+        let [f, x, y] = [AssetId(0), AssetId(1), AssetId(2)];
+        for &log_initial_instructions in [false, true].iter() {
+            let lines = Arc::new(Mutex::new(vec![]));
+            let idle_timeout = Duration::from_millis(10);
+            let config =
+                SiteConfig { log_initial_instructions, idle_timeout, ..Default::default() };
+            let loggers = vec![
+                Box::new(LineLogger { line: vec![], lines: lines.clone() }) as _,
+                Box::new(NullLogger) as _,
+            ];
+            let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            site.insert_asset(f, AssetData::from(0));
+            site.insert_asset(x, AssetData::from(1));
+            // completable at once, so execution ends promptly
+            let instructions =
+                [acquire(x, site_ids[1]), Instruction::ComputeAssetData(compute(vec![x], y, f))];
+            site.todo_instructions.extend(instructions.iter().cloned());
+            site.execute().unwrap();
+            let lines = lines.lock().unwrap();
+            let start = lines.iter().position(|line| line == "Starting with 2 instructions:");
+            assert_eq!(start.is_some(), log_initial_instructions);
+            if let Some(start) = start {
+                let logged = &lines[start + 1..start + 1 + instructions.len()];
+                let expected: Vec<String> =
+                    instructions.iter().map(|ins| format!("  {}", ins)).collect();
+                assert_eq!(logged, &expected[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_msg_is_rejected_for_its_structure() {
+        let keypair = Keypair::generate(&mut rand_core::OsRng);
+        let chunk = |index, count| Msg::AssetChunk {
+            asset_id: AssetId(0),
+            index,
+            count,
+            chunk: AssetData::from(0),
+        };
+        // validly signed, but no site sends a third chunk of two
+        let malformed = chunk(2, 2).sign(&keypair);
+        assert!(malformed.verify().is_ok());
+        assert!(matches!(
+            malformed.verify_structure(),
+            Err(MsgRejection::Malformed(MalformedMsg::ChunkIndexOutOfRange { index: 2, count: 2 }))
+        ));
+        let mut tampered = chunk(0, 2).sign(&keypair);
+        tampered.msg = chunk(1, 2);
+        assert!(matches!(tampered.verify_structure(), Err(MsgRejection::BadSignature(_))));
+        assert!(chunk(1, 2).sign(&keypair).verify_structure().is_ok());
+
+        for &check_msg_structure in [false, true].iter() {
+            let config = SiteConfig { check_msg_structure, ..Default::default() };
+            let (site_ids, mut sites) =
+                new_sites_with_config(vec![Box::new(NullLogger)], &config).unwrap();
+            let site = sites.get_mut(&site_ids[0]).unwrap();
+            site.handle_msg(chunk(2, 2).sign(&keypair));
+            assert_eq!(site.metrics().malformed_msgs, check_msg_structure as u64);
+            assert_eq!(site.metrics().verification_failures, 0);
+        }
+    }
+
+    #[test]
+    fn audit_trail_records_granted_and_denied_requests() {
+        let config = SiteConfig { audit_access: true, ..Default::default() };
+        let loggers = vec![Box::new(NullLogger) as _, Box::new(NullLogger) as _];
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let request = |asset_id| {
+            let keypair = &sites[&amy].inner.keypair;
+            Msg::AssetDataRequest { asset_id, priority: 0 }.sign(keypair)
+        };
+        let [request_x, request_y] = [request(x), request(y)];
+        let bob_site = sites.get_mut(&bob).unwrap();
+        bob_site.insert_asset(x, AssetData::from(0));
+        bob_site.insert_asset(y, AssetData::from(1));
+        // amy may access x only
+        let mut access_control = AccessControl::new(Arc::new(SystemClock));
+        access_control.grant(amy, x, None);
+        bob_site.set_access_control(Some(access_control));
+        let before = Instant::now();
+        bob_site.handle_msg(request_x);
+        bob_site.handle_msg(request_y);
+        let outcomes: Vec<(SiteId, AssetId, bool)> = bob_site
+            .access_log()
+            .iter()
+            .map(|record| (record.requester, record.asset_id, record.granted))
+            .collect();
+        assert_eq!(outcomes, vec![(amy, x, true), (amy, y, false)]);
+        assert!(bob_site.access_log().iter().all(|record| record.at >= before));
+        assert_eq!(bob_site.take_access_log().len(), 2);
+        assert!(bob_site.access_log().is_empty());
+    }
+
+    #[test]
+    fn missing_source_is_asked_again_after_a_request_period() {
+        let (site_ids, mut sites) = sites(2);
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        let requests = |site: &mut Site| {
+            site.drain_inbox()
+                .into_iter()
+                .filter(|signed_msg| matches!(signed_msg.msg, Msg::AssetDataRequest { .. }))
+                .count()
+        };
+        sites.get_mut(&amy).unwrap().todo_instructions.push(acquire(x, bob));
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        // bob lacks x, so declines, queueing the send
+        sites.get_mut(&bob).unwrap().step().unwrap();
+        let bob_site = sites.get_mut(&bob).unwrap();
+        assert_eq!(bob_site.todo_instructions.len(), 1);
+        // ... which is lost, e.g., as bob restarts
+        bob_site.todo_instructions.clear();
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.step().unwrap();
+        assert_eq!(requests(sites.get_mut(&bob).unwrap()), 0);
+        std::thread::sleep(SiteInner::REQUEST_PERIOD);
+        sites.get_mut(&amy).unwrap().step().unwrap();
+        assert_eq!(requests(sites.get_mut(&bob).unwrap()), 1);
+        // ... unlike a site which denied amy access
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.inner.nack(bob, x, NackReason::Denied);
+        std::thread::sleep(SiteInner::REQUEST_PERIOD);
+        amy_site.step().unwrap();
+        assert_eq!(requests(sites.get_mut(&bob).unwrap()), 0);
+    }
+
+    #[test]
+    fn chunked_send_resumes_where_a_full_outbox_stopped_it() {
+        let max_msg_bytes = SignedMsg::OVERHEAD_LEN + Msg::CHUNK_HEADER_LEN + 20;
+        let config = SiteConfig {
+            channel_capacity: Some(2),
+            max_msg_bytes: Some(max_msg_bytes),
+            ..Default::default()
+        };
+        let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+        let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+        let [amy, bob] = [site_ids[0], site_ids[1]];
+        let x = AssetId(0);
+        // six chunks, of which bob's inbox fits two at a time
+        let asset_data = AssetData { bytes: (0..120).collect() };
+        let amy_site = sites.get_mut(&amy).unwrap();
+        amy_site.insert_asset(x, asset_data.clone());
+        amy_site.todo_instructions.push(Instruction::SendAssetTo {
+            asset_id: x,
+            site_id: bob,
+            priority: 0,
+        });
+        let mut indices = vec![];
+        for _ in 0..10 {
+            sites.get_mut(&amy).unwrap().step().unwrap();
+            let bob_site = sites.get_mut(&bob).unwrap();
+            for signed_msg in bob_site.drain_inbox() {
+                if let Msg::AssetChunk { index, .. } = signed_msg.msg {
+                    indices.push(index);
+                }
+                bob_site.handle_msg(signed_msg);
+            }
+            if sites[&amy].todo_instructions.is_empty() {
+                break;
+            }
+        }
+        assert_eq!(indices, (0..6).collect::<Vec<u32>>());
+        assert_eq!(sites[&bob].inner.asset_store.get(&x).unwrap().into_owned(), asset_data);
+        assert_eq!(sites[&amy].metrics().retransmissions, 0);
+    }
+
+    #[test]
+    fn chunks_from_different_senders_are_reassembled_apart() {
+        let (site_ids, mut sites) = sites(1);
+        let site = sites.get_mut(&site_ids[0]).unwrap();
+        let x = AssetId(0);
+        let [amy, cho] = [0, 1].map(|_| Keypair::generate(&mut rand_core::OsRng));
+        let chunk = |keypair: &Keypair, index, byte| {
+            let chunk = AssetData { bytes: vec![byte] };
+            Msg::AssetChunk { asset_id: x, index, count: 2, chunk }.sign(keypair)
+        };
+        site.handle_msg(chunk(&amy, 0, 1));
+        site.handle_msg(chunk(&cho, 0, 7));
+        site.handle_msg(chunk(&amy, 1, 2));
+        assert_eq!(site.inner.asset_store.get(&x).unwrap().into_owned().bytes, vec![1, 2]);
+        site.handle_msg(chunk(&cho, 1, 8));
+        assert_eq!(site.inner.asset_store.get(&x).unwrap().into_owned().bytes, vec![7, 8]);
+    }
+
+    #[test]
+    fn prefetched_input_arrives_sooner_over_a_slow_link() {
+        /// Takes its time, as a real compute would
+        #[derive(Debug)]
+        struct SlowCompute;
+        impl ComputeFn for SlowCompute {
+            fn compute(
+                &self,
+                store: &dyn AssetStore,
+                compute_args: &ComputeArgs,
+            ) -> Result<HashMap<AssetId, AssetData>, ComputeError> {
+                std::thread::sleep(Duration::from_millis(50));
+                HashCompute::<fnv::FnvHasher>::default().compute(store, compute_args)
+            }
+        }
+        const LINK_DELAY: Duration = Duration::from_millis(150);
+        let [f, x, a, z] = [0, 1, 2, 3].map(AssetId);
+        let time_to_complete = |prefetch_inputs: bool| {
+            let config = SiteConfig {
+                prefetch_inputs,
+                idle_timeout: Duration::from_millis(20),
+                quiescence_timeout: Duration::from_secs(5),
+                ..Default::default()
+            };
+            let loggers = (0..2).map(|_| Box::new(NullLogger) as Box<dyn Logger>).collect();
+            let (site_ids, mut sites) = new_sites_with_config(loggers, &config).unwrap();
+            let [amy, bob] = [site_ids[0], site_ids[1]];
+            let mut amy_site = sites.remove(&amy).unwrap();
+            let mut bob_site = sites.remove(&bob).unwrap();
+            bob_site.insert_asset(a, AssetData::from(2));
+            amy_site.set_compute_fn(Some(Arc::new(SlowCompute)));
+            amy_site.insert_asset(f, AssetData::from(0));
+            amy_site.insert_asset(x, AssetData::from(1));
+            // four local computes, each cutting the scan short, ahead of acquiring a from bob
+            let local = (0..4).map(|i| compute(vec![x], AssetId(10 + i), f));
+            amy_site.todo_instructions.extend(local.map(Instruction::ComputeAssetData));
+            amy_site.todo_instructions.push(acquire(a, bob));
+            amy_site.todo_instructions.push(Instruction::ComputeAssetData(compute(vec![a], z, f)));
+            let progress = amy_site.progress();
+            let start = Instant::now();
+            crossbeam_utils::thread::scope(|s| {
+                s.spawn(move |_| amy_site.execute().unwrap());
+                // bob's link delays each message it receives
+                let mut in_flight = VecDeque::new();
+                let deadline = start + Duration::from_secs(5);
+                while progress.pending_count() > 0 && Instant::now() < deadline {
+                    let arrived = bob_site.drain_inbox().into_iter();
+                    in_flight.extend(
+                        arrived.map(|signed_msg| (Instant::now() + LINK_DELAY, signed_msg)),
+                    );
+                    while in_flight.front().is_some_and(|&(due, _)| due <= Instant::now()) {
+                        bob_site.handle_msg(in_flight.pop_front().unwrap().1);
+                    }
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })
+            .unwrap();
+            assert_eq!(progress.pending_count(), 0);
+            start.elapsed()
+        };
+        // prefetching overlaps the transfer of a with the local computes
+        let (prefetched, sequential) = (time_to_complete(true), time_to_complete(false));
+        assert!(
+            prefetched + Duration::from_millis(75) < sequential,
+            "prefetched in {:?}, sequentially in {:?}",
+            prefetched,
+            sequential
+        );
     }
 }
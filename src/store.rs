@@ -0,0 +1,205 @@
+use super::*;
+use std::io::Read;
+
+impl AssetStore for HashMap<AssetId, AssetData> {
+    fn get(&self, asset_id: &AssetId) -> Option<Cow<'_, AssetData>> {
+        HashMap::get(self, asset_id).map(Cow::Borrowed)
+    }
+    fn insert(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        HashMap::insert(self, asset_id, asset_data);
+    }
+    fn contains_key(&self, asset_id: &AssetId) -> bool {
+        HashMap::contains_key(self, asset_id)
+    }
+    fn remove(&mut self, asset_id: &AssetId) -> bool {
+        HashMap::remove(self, asset_id).is_some()
+    }
+    fn asset_ids(&self) -> Vec<AssetId> {
+        self.keys().copied().collect()
+    }
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+impl DirAssetStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).expect("creating asset store dir");
+        Self { dir }
+    }
+    fn asset_path(&self, asset_id: &AssetId) -> PathBuf {
+        self.dir.join(format!("{}.asset", asset_id.0))
+    }
+}
+impl AssetStore for DirAssetStore {
+    fn get(&self, asset_id: &AssetId) -> Option<Cow<'_, AssetData>> {
+        let bytes = std::fs::read(self.asset_path(asset_id)).ok()?;
+        Some(Cow::Owned(AssetData { bytes }))
+    }
+    fn insert(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        std::fs::write(self.asset_path(&asset_id), &asset_data.bytes).expect("writing asset");
+    }
+    fn contains_key(&self, asset_id: &AssetId) -> bool {
+        self.asset_path(asset_id).exists()
+    }
+    fn remove(&mut self, asset_id: &AssetId) -> bool {
+        std::fs::remove_file(self.asset_path(asset_id)).is_ok()
+    }
+    fn asset_ids(&self) -> Vec<AssetId> {
+        let entries = std::fs::read_dir(&self.dir).expect("reading asset store dir");
+        entries
+            .filter_map(|entry| {
+                let file_name = entry.ok()?.file_name();
+                let id = file_name.to_str()?.strip_suffix(".asset")?.parse().ok()?;
+                Some(AssetId(id))
+            })
+            .collect()
+    }
+}
+
+impl AssetStore for GzipAssetStore {
+    fn get(&self, asset_id: &AssetId) -> Option<Cow<'_, AssetData>> {
+        let mut decoder = flate2::read::GzDecoder::new(self.assets.get(asset_id)?.as_slice());
+        let mut bytes = vec![];
+        decoder.read_to_end(&mut bytes).expect("decoding stored asset");
+        Some(Cow::Owned(AssetData { bytes }))
+    }
+    fn insert(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&asset_data.bytes).expect("encoding asset");
+        self.assets.insert(asset_id, encoder.finish().expect("encoding asset"));
+    }
+    fn contains_key(&self, asset_id: &AssetId) -> bool {
+        self.assets.contains_key(asset_id)
+    }
+    fn remove(&mut self, asset_id: &AssetId) -> bool {
+        self.assets.remove(asset_id).is_some()
+    }
+    fn asset_ids(&self) -> Vec<AssetId> {
+        self.assets.keys().copied().collect()
+    }
+    fn len(&self) -> usize {
+        self.assets.len()
+    }
+}
+
+impl SharedContentStore {
+    /// How many distinct contents are stored, however many assets share them
+    pub fn content_count(&self) -> usize {
+        self.contents.values().map(Vec::len).sum()
+    }
+}
+impl AssetStore for SharedContentStore {
+    fn get(&self, asset_id: &AssetId) -> Option<Cow<'_, AssetData>> {
+        self.assets.get(asset_id).map(|asset_data| Cow::Borrowed(&**asset_data))
+    }
+    fn insert(&mut self, asset_id: AssetId, asset_data: AssetData) {
+        self.remove(&asset_id);
+        let same_hash = self.contents.entry(asset_data.content_hash()).or_default();
+        let shared = match same_hash.iter().find(|held_data| ***held_data == asset_data) {
+            // The given bytes are dropped in favor of those held already
+            Some(held_data) => held_data.clone(),
+            None => {
+                let asset_data = Arc::new(asset_data);
+                same_hash.push(asset_data.clone());
+                asset_data
+            }
+        };
+        self.assets.insert(asset_id, shared);
+    }
+    fn contains_key(&self, asset_id: &AssetId) -> bool {
+        self.assets.contains_key(asset_id)
+    }
+    fn remove(&mut self, asset_id: &AssetId) -> bool {
+        let asset_data = match self.assets.remove(asset_id) {
+            Some(asset_data) => asset_data,
+            None => return false,
+        };
+        if Arc::strong_count(&asset_data) == 2 {
+            // No other asset shares this content. Only `contents` still refers to it.
+            let content_hash = asset_data.content_hash();
+            let same_hash = self.contents.get_mut(&content_hash).unwrap();
+            same_hash.retain(|held_data| !Arc::ptr_eq(held_data, &asset_data));
+            if same_hash.is_empty() {
+                self.contents.remove(&content_hash);
+            }
+        }
+        true
+    }
+    fn asset_ids(&self) -> Vec<AssetId> {
+        self.assets.keys().copied().collect()
+    }
+    fn len(&self) -> usize {
+        self.assets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shares_bytes(store: &dyn AssetStore, a: AssetId, b: AssetId) -> bool {
+        match (store.get(&a), store.get(&b)) {
+            (Some(Cow::Borrowed(a)), Some(Cow::Borrowed(b))) => std::ptr::eq(a, b),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn identical_content_is_stored_once() {
+        let [x, y, z] = [AssetId(0), AssetId(1), AssetId(2)];
+        let mut store = SharedContentStore::default();
+        store.insert(x, AssetData::from(7));
+        store.insert(y, AssetData::from(7));
+        store.insert(z, AssetData::from(8));
+        assert_eq!((store.len(), store.content_count()), (3, 2));
+        assert!(shares_bytes(&store, x, y));
+        assert!(!shares_bytes(&store, x, z));
+        // The content outlives all but the last asset sharing it
+        assert!(store.remove(&x));
+        assert_eq!(store.get(&y).unwrap().into_owned(), AssetData::from(7));
+        assert_eq!(store.content_count(), 2);
+        assert!(store.remove(&y));
+        assert!(!store.remove(&y));
+        assert_eq!((store.len(), store.content_count()), (1, 1));
+    }
+
+    #[test]
+    fn gzipped_assets_are_got_decoded() {
+        let x = AssetId(0);
+        let asset_data = AssetData { bytes: vec![7; 1000] };
+        let mut store = GzipAssetStore::default();
+        store.insert(x, asset_data.clone());
+        assert!(store.assets[&x].len() < asset_data.bytes.len());
+        assert_eq!(store.get(&x).unwrap().into_owned(), asset_data);
+        assert_eq!(store.get(&x).unwrap().content_hash(), asset_data.content_hash());
+    }
+
+    #[test]
+    fn replacing_content_unshares_it() {
+        let [x, y] = [AssetId(0), AssetId(1)];
+        let mut store = SharedContentStore::default();
+        store.insert(x, AssetData::from(7));
+        store.insert(y, AssetData::from(7));
+        store.insert(x, AssetData::from(8));
+        assert!(!shares_bytes(&store, x, y));
+        assert_eq!(store.get(&y).unwrap().into_owned(), AssetData::from(7));
+        assert_eq!(store.content_count(), 2);
+    }
+
+    #[test]
+    fn dir_store_round_trips_large_assets() {
+        let dir = std::env::temp_dir().join(format!("site_exec_store_{}", std::process::id()));
+        let mut store = DirAssetStore::new(&dir);
+        let x = AssetId(0);
+        let asset_data = AssetData { bytes: (0..4 << 20).map(|i| i as u8).collect() };
+        store.insert(x, asset_data.clone());
+        assert!(store.contains_key(&x));
+        assert_eq!(store.asset_ids(), vec![x]);
+        assert_eq!(store.get(&x).unwrap().into_owned(), asset_data);
+        assert!(store.remove(&x));
+        assert!(store.get(&x).is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}